@@ -0,0 +1,116 @@
+//! Planning benchmarks for buildings with hundreds of floors.
+//!
+//! `linear_slice` is the planner scanning the slice from `LiftSensors::floors_to_stop_at`,
+//! `sorted_queue` and `floor_set` are the sorted `CallQueue` and the `FloorSet` bitset.
+//! Run with `cargo bench` and compare the three within each group.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use lift::{
+    BoundedLiftController, CallQueue, Floor, LiftController, LiftSensors, Position, Velocity,
+};
+
+const FLOORS: Floor = 512;
+const CALL_COUNTS: [usize; 3] = [16, 128, 512];
+
+struct Building {
+    current_floor: Position,
+    floors_to_stop_at: Vec<Floor>,
+}
+
+impl LiftSensors for Building {
+    fn current_floor(&self) -> Position {
+        self.current_floor
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        1.0
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        &self.floors_to_stop_at
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        false
+    }
+}
+
+/// Calls spread over the building in a scrambled order, as they would be registered
+fn building(calls: usize) -> Building {
+    let step = FLOORS as usize / calls;
+    let mut floors_to_stop_at: Vec<Floor> = (0..calls).map(|i| (i * step) as Floor).collect();
+    floors_to_stop_at.reverse();
+    floors_to_stop_at.rotate_left(calls / 3);
+    Building {
+        current_floor: FLOORS as Position / 2.0 + 0.5,
+        floors_to_stop_at,
+    }
+}
+
+fn controller() -> LiftController {
+    LiftController::new(1.0, 0.05, 0.01)
+}
+
+fn poll(c: &mut Criterion) {
+    let mut group = c.benchmark_group("poll");
+    for calls in CALL_COUNTS {
+        let sensors = building(calls);
+
+        let mut linear = controller();
+        group.bench_with_input(BenchmarkId::new("linear_slice", calls), &sensors, |b, s| {
+            b.iter(|| linear.poll(black_box(s), 0.1))
+        });
+
+        let mut queue = CallQueue::<512>::new();
+        for floor in &sensors.floors_to_stop_at {
+            queue.insert(*floor).unwrap();
+        }
+        let mut sorted = controller();
+        group.bench_with_input(BenchmarkId::new("sorted_queue", calls), &sensors, |b, s| {
+            b.iter(|| sorted.poll_with_calls(black_box(s), &queue, 0.1))
+        });
+
+        let mut bounded = BoundedLiftController::<16>::new(controller(), 0);
+        for floor in &sensors.floors_to_stop_at {
+            bounded.register_call(*floor).unwrap();
+        }
+        group.bench_with_input(BenchmarkId::new("floor_set", calls), &sensors, |b, s| {
+            b.iter(|| bounded.poll(black_box(s), 0.1))
+        });
+    }
+    group.finish();
+}
+
+fn time_to_floor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("time_to_floor");
+    for calls in CALL_COUNTS {
+        let sensors = building(calls);
+        let mut controller = controller();
+        controller.poll(&sensors, 0.1);
+        group.bench_with_input(BenchmarkId::from_parameter(calls), &sensors, |b, s| {
+            b.iter(|| controller.time_to_floor(black_box(s), black_box(3), 5.0))
+        });
+    }
+    group.finish();
+}
+
+fn planned_route(c: &mut Criterion) {
+    let mut group = c.benchmark_group("planned_route");
+    for calls in CALL_COUNTS {
+        let sensors = building(calls);
+        let mut controller = controller();
+        controller.poll(&sensors, 0.1);
+        group.bench_with_input(BenchmarkId::from_parameter(calls), &sensors, |b, s| {
+            b.iter(|| {
+                controller
+                    .planned_route(s.current_floor, black_box(&s.floors_to_stop_at))
+                    .take(8)
+                    .sum::<Floor>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, poll, time_to_floor, planned_route);
+criterion_main!(benches);
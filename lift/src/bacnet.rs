@@ -0,0 +1,166 @@
+//! Mapping of the controller state to the properties of a BACnet Lift object
+//! (object type 59, ANSI/ASHRAE 135-2016), for integration with building automation systems.
+//!
+//! The module does not encode BACnet APDUs, it provides the values the BACnet stack of the host
+//! should report for each property, and decodes writes to the call properties.
+//! BACnet floor numbers are unsigned, floors are numbered from 1 at the lowest floor of the building.
+
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::{Direction, Floor, LiftController, LiftSensors, Mode};
+
+/// BACnet object type of a lift
+pub const OBJECT_TYPE_LIFT: u16 = 59;
+
+/// The Lift object properties covered by this mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    CarDoorStatus = 450,
+    CarMode = 456,
+    CarMovingDirection = 457,
+    CarPosition = 458,
+    FaultSignals = 463,
+    MakingCarCall = 475,
+    NextStoppingFloor = 476,
+}
+
+/// BACnetLiftCarDirection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarDirection {
+    Unknown = 0,
+    None = 1,
+    Stopped = 2,
+    Up = 3,
+    Down = 4,
+}
+
+/// BACnetDoorStatus, supplied by the host as the controller does not operate the doors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorStatus {
+    Closed = 0,
+    Opened = 1,
+    Unknown = 2,
+    DoorFault = 3,
+    Closing = 6,
+    Opening = 7,
+}
+
+/// BACnetLiftCarMode values used by the mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarMode {
+    Unknown = 0,
+    Normal = 1,
+    OutOfService = 12,
+}
+
+/// The value of a property, in terms of BACnet application data types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyValue {
+    Unsigned(u32),
+    Enumerated(u32),
+    /// An empty list, the controller has nothing to report for list properties
+    EmptyList,
+    Null,
+}
+
+/// Maps a single lift to a BACnet Lift object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiftObject {
+    /// The lowest floor of the building, BACnet floor number 1
+    pub lowest_floor: Floor,
+    /// The highest floor of the building
+    pub highest_floor: Floor,
+}
+
+impl LiftObject {
+    /// Read a property of the lift
+    pub fn read(
+        &self,
+        property: Property,
+        controller: &LiftController,
+        sensors: &dyn LiftSensors,
+        door: DoorStatus,
+    ) -> PropertyValue {
+        let snapshot = controller.snapshot();
+
+        match property {
+            Property::CarPosition => {
+                let floor = sensors.current_floor().round() as Floor;
+                match self.floor_number(floor) {
+                    Some(number) => PropertyValue::Unsigned(number as u32),
+                    None => PropertyValue::Null,
+                }
+            }
+            Property::CarMovingDirection => {
+                let moving = sensors.current_velocity().abs() >= snapshot.velocity_epsilon;
+                let direction = match (moving, snapshot.direction) {
+                    (false, _) => CarDirection::Stopped,
+                    (true, Direction::Up) => CarDirection::Up,
+                    (true, Direction::Down) => CarDirection::Down,
+                    (true, Direction::Neutral) => CarDirection::Unknown,
+                };
+                PropertyValue::Enumerated(direction as u32)
+            }
+            Property::CarDoorStatus => PropertyValue::Enumerated(door as u32),
+            Property::CarMode => {
+                let mode = match snapshot.mode {
+                    Mode::EmergencyStop => CarMode::OutOfService,
+                    Mode::Idle | Mode::Serving => CarMode::Normal,
+                };
+                PropertyValue::Enumerated(mode as u32)
+            }
+            // The controller does not detect faults
+            Property::FaultSignals => PropertyValue::EmptyList,
+            Property::NextStoppingFloor => match snapshot.target.and_then(|t| self.floor_number(t))
+            {
+                Some(number) => PropertyValue::Unsigned(number as u32),
+                None => PropertyValue::Null,
+            },
+            Property::MakingCarCall => PropertyValue::EmptyList,
+        }
+    }
+
+    /// Decode a write of a floor number to the Making_Car_Call property into the floor to stop at
+    pub fn decode_car_call(&self, floor_number: u8) -> Option<Floor> {
+        let floor = self.lowest_floor + floor_number as Floor - 1;
+        if floor_number >= 1 && floor <= self.highest_floor {
+            Some(floor)
+        } else {
+            None
+        }
+    }
+
+    /// The BACnet floor number of a floor, `None` if it is outside of the building
+    pub fn floor_number(&self, floor: Floor) -> Option<u8> {
+        if floor < self.lowest_floor || floor > self.highest_floor {
+            return None;
+        }
+        let number = floor - self.lowest_floor + 1;
+        if number <= u8::MAX as i32 {
+            Some(number as u8)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn floor_numbers() {
+        let object = LiftObject {
+            lowest_floor: -2,
+            highest_floor: 10,
+        };
+
+        assert_eq!(Some(1), object.floor_number(-2));
+        assert_eq!(Some(3), object.floor_number(0));
+        assert_eq!(None, object.floor_number(11));
+        assert_eq!(Some(0), object.decode_car_call(3));
+        assert_eq!(None, object.decode_car_call(0));
+        assert_eq!(None, object.decode_car_call(14));
+    }
+}
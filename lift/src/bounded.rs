@@ -0,0 +1,332 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::calls::Calls;
+use crate::{Action, Floor, LiftController, LiftObserver, LiftSensors};
+
+/// Fixed-size bitset of floors, covering `32 * WORDS` floors starting at a configurable lowest floor.
+///
+/// The memory used is known at compile time and no allocation ever takes place,
+/// which makes it suitable for safety-critical builds. Nearest-floor lookups use bit scans
+/// and skip empty words, so they cost a handful of instructions per 32 floors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloorSet<const WORDS: usize> {
+    lowest_floor: Floor,
+    words: [u32; WORDS],
+}
+
+/// The floor is outside of the range covered by a `FloorSet`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FloorOutOfRange;
+
+impl<const WORDS: usize> FloorSet<WORDS> {
+    /// Number of floors the set can cover
+    pub const CAPACITY: usize = 32 * WORDS;
+
+    /// Create an empty set covering the floors from `lowest_floor` and up
+    pub const fn new(lowest_floor: Floor) -> Self {
+        FloorSet {
+            lowest_floor,
+            words: [0; WORDS],
+        }
+    }
+
+    /// The lowest floor covered by the set
+    pub fn lowest_floor(&self) -> Floor {
+        self.lowest_floor
+    }
+
+    /// The highest floor covered by the set
+    pub fn highest_floor(&self) -> Floor {
+        self.lowest_floor + Self::CAPACITY as Floor - 1
+    }
+
+    /// Add a floor, returns true if it was not already in the set
+    pub fn insert(&mut self, floor: Floor) -> Result<bool, FloorOutOfRange> {
+        let (word, bit) = self.index(floor).ok_or(FloorOutOfRange)?;
+        let added = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        Ok(added)
+    }
+
+    /// Remove a floor, returns true if it was in the set
+    pub fn remove(&mut self, floor: Floor) -> bool {
+        match self.index(floor) {
+            Some((word, bit)) => {
+                let removed = self.words[word] & bit != 0;
+                self.words[word] &= !bit;
+                removed
+            }
+            None => false,
+        }
+    }
+
+    /// If the floor is in the set
+    pub fn contains(&self, floor: Floor) -> bool {
+        match self.index(floor) {
+            Some((word, bit)) => self.words[word] & bit != 0,
+            None => false,
+        }
+    }
+
+    /// Remove all floors
+    pub fn clear(&mut self) {
+        self.words = [0; WORDS];
+    }
+
+    /// Number of floors in the set
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// If there are no floors in the set
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    /// Iterator over the floors in the set, lowest first
+    pub fn iter(&self) -> impl Iterator<Item = Floor> + '_ {
+        let mut offset = 0;
+        core::iter::from_fn(move || {
+            let next = self.first_at_or_after(offset)?;
+            offset = next + 1;
+            Some(self.floor_at(next))
+        })
+    }
+
+    /// Offset of the first floor in the set at or after `offset`, skipping empty words
+    fn first_at_or_after(&self, offset: usize) -> Option<usize> {
+        if offset >= Self::CAPACITY {
+            return None;
+        }
+        let first = offset / 32;
+        let masked = self.words[first] & (!0u32 << (offset % 32));
+        if masked != 0 {
+            return Some(first * 32 + masked.trailing_zeros() as usize);
+        }
+        (first + 1..WORDS)
+            .find(|word| self.words[*word] != 0)
+            .map(|word| word * 32 + self.words[word].trailing_zeros() as usize)
+    }
+
+    /// Offset of the last floor in the set at or before `offset`, skipping empty words
+    fn last_at_or_before(&self, offset: usize) -> Option<usize> {
+        let offset = offset.min(Self::CAPACITY.checked_sub(1)?);
+        let last = offset / 32;
+        let masked = self.words[last] & (!0u32 >> (31 - offset % 32));
+        if masked != 0 {
+            return Some(last * 32 + 31 - masked.leading_zeros() as usize);
+        }
+        (0..last)
+            .rev()
+            .find(|word| self.words[*word] != 0)
+            .map(|word| word * 32 + 31 - self.words[word].leading_zeros() as usize)
+    }
+
+    /// Offset of a floor from the lowest floor, may be negative or beyond the capacity
+    fn offset(&self, floor: Floor) -> i64 {
+        floor as i64 - self.lowest_floor as i64
+    }
+
+    fn floor_at(&self, offset: usize) -> Floor {
+        self.lowest_floor + offset as Floor
+    }
+
+    /// Word index and bit mask of a floor, `None` if it is out of range
+    fn index(&self, floor: Floor) -> Option<(usize, u32)> {
+        let offset = floor.checked_sub(self.lowest_floor)?;
+        if offset < 0 || offset as usize >= Self::CAPACITY {
+            return None;
+        }
+        let offset = offset as usize;
+        Some((offset / 32, 1 << (offset % 32)))
+    }
+}
+
+impl<const WORDS: usize> Calls for FloorSet<WORDS> {
+    fn nearest_floor_above(&self, current_floor: Floor) -> Option<Floor> {
+        let offset = self.offset(current_floor).max(0);
+        if offset >= Self::CAPACITY as i64 {
+            return None;
+        }
+        self.first_at_or_after(offset as usize)
+            .map(|offset| self.floor_at(offset))
+    }
+
+    fn nearest_floor_below(&self, current_floor: Floor) -> Option<Floor> {
+        let offset = self.offset(current_floor);
+        if offset < 0 {
+            return None;
+        }
+        let offset = offset.min(Self::CAPACITY as i64) as usize;
+        self.last_at_or_before(offset)
+            .map(|offset| self.floor_at(offset))
+    }
+
+    fn nearest_floor(&self, current_floor: Floor) -> Option<Floor> {
+        match (
+            self.nearest_floor_below(current_floor),
+            self.nearest_floor_above(current_floor),
+        ) {
+            (Some(below), Some(above))
+                if above.abs_diff(current_floor) < below.abs_diff(current_floor) =>
+            {
+                Some(above)
+            }
+            (Some(below), _) => Some(below),
+            (None, above) => above,
+        }
+    }
+}
+
+/// A controller owning its calls in a `FloorSet`, for builds that must guarantee bounded memory.
+///
+/// Calls are registered with the controller rather than reported by the sensors, the result of
+/// `LiftSensors::floors_to_stop_at` is ignored. A call is cleared when the lift stops at its floor.
+#[derive(Debug)]
+pub struct BoundedLiftController<const WORDS: usize> {
+    controller: LiftController,
+    calls: FloorSet<WORDS>,
+}
+
+impl<const WORDS: usize> BoundedLiftController<WORDS> {
+    /// Wrap a controller, serving the floors from `lowest_floor` and up to the capacity of the call set
+    pub const fn new(controller: LiftController, lowest_floor: Floor) -> Self {
+        BoundedLiftController {
+            controller,
+            calls: FloorSet::new(lowest_floor),
+        }
+    }
+
+    /// Register a call to a floor
+    pub fn register_call(&mut self, floor: Floor) -> Result<(), FloorOutOfRange> {
+        self.calls.insert(floor).map(|_| ())
+    }
+
+    /// Cancel a call to a floor, returns true if it was registered
+    pub fn cancel_call(&mut self, floor: Floor) -> bool {
+        self.calls.remove(floor)
+    }
+
+    /// The registered calls
+    pub fn calls(&self) -> &FloorSet<WORDS> {
+        &self.calls
+    }
+
+    /// The wrapped controller
+    pub fn controller(&self) -> &LiftController {
+        &self.controller
+    }
+
+    /// Mutable access to the wrapped controller, for instance to restore state or reset counters
+    pub fn controller_mut(&mut self) -> &mut LiftController {
+        &mut self.controller
+    }
+
+    /// Poll for the next action to perform, see `LiftController::poll`
+    pub fn poll(&mut self, sensors: &dyn LiftSensors, time_step: f32) -> Action {
+        self.poll_with_observer(sensors, time_step, &mut ())
+    }
+
+    /// Poll for the next action to perform, see `LiftController::poll_with_observer`
+    pub fn poll_with_observer(
+        &mut self,
+        sensors: &dyn LiftSensors,
+        time_step: f32,
+        observer: &mut dyn LiftObserver,
+    ) -> Action {
+        let action = self
+            .controller
+            .poll_calls(sensors, &self.calls, time_step, observer);
+        if action.is_stopped_at_current_floor {
+            self.calls.remove(sensors.current_floor().round() as Floor);
+        }
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimulatedLift;
+    use crate::Position;
+
+    #[test]
+    fn floor_set_lookups() {
+        let mut set = FloorSet::<2>::new(-2);
+        assert_eq!(61, set.highest_floor());
+        assert_eq!(Err(FloorOutOfRange), set.insert(62));
+        assert_eq!(Ok(true), set.insert(-2));
+        assert_eq!(Ok(true), set.insert(40));
+        assert_eq!(Ok(false), set.insert(40));
+
+        assert_eq!(Some(40), set.nearest_floor_above(3));
+        assert_eq!(Some(-2), set.nearest_floor_below(3));
+        assert_eq!(Some(-2), set.nearest_floor(3));
+        assert_eq!(Some(40), set.nearest_floor(30));
+        assert_eq!(2, set.len());
+    }
+
+    #[test]
+    fn floor_set_matches_linear_scan() {
+        let mut set = FloorSet::<16>::new(-20);
+        let mut floors = [0; 64];
+        let mut seed = 12345u32;
+        for floor in floors.iter_mut() {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            *floor = (seed >> 16) as Floor % 512 - 20;
+            set.insert(*floor).unwrap();
+        }
+
+        for current_floor in -40..520 {
+            let linear: &[Floor] = &floors;
+            assert_eq!(
+                linear.nearest_floor_above(current_floor),
+                set.nearest_floor_above(current_floor)
+            );
+            assert_eq!(
+                linear.nearest_floor_below(current_floor),
+                set.nearest_floor_below(current_floor)
+            );
+            assert_eq!(
+                linear.nearest_floor(current_floor),
+                set.nearest_floor(current_floor)
+            );
+        }
+    }
+
+    #[test]
+    fn bounded_controller_chooses_the_same_floors() {
+        // Ties between a floor above and below, and calls registered in a scrambled order
+        let trips: [(Position, &[Floor]); 3] = [
+            (2.0, &[3, 1]),
+            (4.0, &[6, 2, 5, 3]),
+            (10.0, &[17, 0, 3, 12, 8, 20]),
+        ];
+        for (start, calls) in trips.iter() {
+            let mut lift = SimulatedLift::new(*start);
+            let mut unbounded = LiftController::new(0.5, 0.001, 0.001);
+            let mut bounded_lift = SimulatedLift::new(*start);
+            let mut bounded = BoundedLiftController::<1>::new(unbounded.clone(), 0);
+            for floor in calls.iter() {
+                lift.register_call(*floor);
+                bounded.register_call(*floor).unwrap();
+            }
+
+            for _ in 0..1000 {
+                let action = unbounded.poll(&lift, 0.1);
+                lift.accept_action(action, 0.1);
+                let bounded_action = bounded.poll(&bounded_lift, 0.1);
+                bounded_lift.accept_action(bounded_action, 0.1);
+                assert_eq!(
+                    unbounded.snapshot().target,
+                    bounded.controller().snapshot().target
+                );
+                assert_eq!(action, bounded_action);
+            }
+            assert!(lift.floors_to_stop_at().is_empty());
+            assert!(bounded.calls().is_empty());
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::Floor;
+
+/// Fixed-capacity, heap-free list of floors to stop at, without duplicates.
+///
+/// Floors are kept sorted, so lookups are binary searches. The queue can be handed to the controller
+/// through `as_slice` when implementing `LiftSensors::floors_to_stop_at`, or directly to
+/// `LiftController::poll_with_calls` to let the planner take advantage of the ordering.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallQueue<const N: usize> {
+    floors: [Floor; N],
+    len: usize,
+}
+
+/// The call could not be registered as the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CallQueueFull;
+
+impl<const N: usize> CallQueue<N> {
+    /// Create an empty queue
+    pub const fn new() -> Self {
+        CallQueue {
+            floors: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Register a call to a floor.
+    /// Returns `Ok(true)` if the floor was added and `Ok(false)` if it was already registered
+    pub fn insert(&mut self, floor: Floor) -> Result<bool, CallQueueFull> {
+        let index = match self.as_slice().binary_search(&floor) {
+            Ok(_) => return Ok(false),
+            Err(index) => index,
+        };
+        if self.len == N {
+            return Err(CallQueueFull);
+        }
+
+        self.floors.copy_within(index..self.len, index + 1);
+        self.floors[index] = floor;
+        self.len += 1;
+        Ok(true)
+    }
+
+    /// Remove the call to a floor, typically when the lift has stopped there.
+    /// Returns true if the floor was registered
+    pub fn remove(&mut self, floor: Floor) -> bool {
+        match self.as_slice().binary_search(&floor) {
+            Ok(index) => {
+                self.floors.copy_within(index + 1..self.len, index);
+                self.len -= 1;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// If a call to the floor is registered
+    pub fn contains(&self, floor: Floor) -> bool {
+        self.as_slice().binary_search(&floor).is_ok()
+    }
+
+    /// Remove all calls
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// The registered floors, lowest first
+    pub fn as_slice(&self) -> &[Floor] {
+        &self.floors[..self.len]
+    }
+
+    /// Iterator over the registered floors, lowest first
+    pub fn iter(&self) -> core::slice::Iter<'_, Floor> {
+        self.as_slice().iter()
+    }
+
+    /// Number of registered calls
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// If there are no registered calls
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Maximum number of calls the queue can hold
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<const N: usize> Default for CallQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> AsRef<[Floor]> for CallQueue<N> {
+    fn as_ref(&self) -> &[Floor] {
+        self.as_slice()
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a CallQueue<N> {
+    type Item = &'a Floor;
+    type IntoIter = core::slice::Iter<'a, Floor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Lookup of registered calls relative to the current floor, as needed by the planner.
+/// Implemented for every call storage the controller can plan with
+pub(crate) trait Calls {
+    /// The nearest registered floor at or above the current floor
+    fn nearest_floor_above(&self, current_floor: Floor) -> Option<Floor>;
+
+    /// The nearest registered floor at or below the current floor
+    fn nearest_floor_below(&self, current_floor: Floor) -> Option<Floor>;
+
+    /// The nearest registered floor in any direction.
+    /// Ties between a floor above and below are resolved in favour of the floor below, in every implementation
+    fn nearest_floor(&self, current_floor: Floor) -> Option<Floor>;
+}
+
+impl Calls for [Floor] {
+    fn nearest_floor_above(&self, current_floor: Floor) -> Option<Floor> {
+        self.iter()
+            .filter(|floor| **floor >= current_floor)
+            .min_by_key(|floor| floor.abs_diff(current_floor))
+            .copied()
+    }
+
+    fn nearest_floor_below(&self, current_floor: Floor) -> Option<Floor> {
+        self.iter()
+            .filter(|floor| **floor <= current_floor)
+            .min_by_key(|floor| floor.abs_diff(current_floor))
+            .copied()
+    }
+
+    fn nearest_floor(&self, current_floor: Floor) -> Option<Floor> {
+        self.iter()
+            .min_by_key(|floor| (floor.abs_diff(current_floor), **floor))
+            .copied()
+    }
+}
+
+impl<const N: usize> Calls for CallQueue<N> {
+    fn nearest_floor_above(&self, current_floor: Floor) -> Option<Floor> {
+        let floors = self.as_slice();
+        floors
+            .get(floors.partition_point(|floor| *floor < current_floor))
+            .copied()
+    }
+
+    fn nearest_floor_below(&self, current_floor: Floor) -> Option<Floor> {
+        let floors = self.as_slice();
+        floors
+            .partition_point(|floor| *floor <= current_floor)
+            .checked_sub(1)
+            .map(|index| floors[index])
+    }
+
+    fn nearest_floor(&self, current_floor: Floor) -> Option<Floor> {
+        match (
+            self.nearest_floor_below(current_floor),
+            self.nearest_floor_above(current_floor),
+        ) {
+            (Some(below), Some(above))
+                if above.abs_diff(current_floor) < below.abs_diff(current_floor) =>
+            {
+                Some(above)
+            }
+            (Some(below), _) => Some(below),
+            (None, above) => above,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_remove_and_capacity() {
+        let mut queue = CallQueue::<3>::new();
+        assert_eq!(Ok(true), queue.insert(4));
+        assert_eq!(Ok(false), queue.insert(4));
+        assert_eq!(Ok(true), queue.insert(-1));
+        assert_eq!(Ok(true), queue.insert(7));
+        assert_eq!(Err(CallQueueFull), queue.insert(8));
+        assert_eq!(Ok(false), queue.insert(7));
+
+        assert!(queue.remove(-1));
+        assert!(!queue.remove(-1));
+        assert_eq!(&[4, 7], queue.as_slice());
+    }
+
+    #[test]
+    fn sorted_lookups_match_linear_scan() {
+        let floors: &[Floor] = &[9, -2, 4, 12, 0];
+        let mut queue = CallQueue::<8>::new();
+        for floor in floors {
+            queue.insert(*floor).unwrap();
+        }
+        assert_eq!(&[-2, 0, 4, 9, 12], queue.as_slice());
+
+        for current_floor in -4..15 {
+            assert_eq!(
+                floors.nearest_floor_above(current_floor),
+                queue.nearest_floor_above(current_floor)
+            );
+            assert_eq!(
+                floors.nearest_floor_below(current_floor),
+                queue.nearest_floor_below(current_floor)
+            );
+            assert_eq!(
+                floors.nearest_floor(current_floor),
+                queue.nearest_floor(current_floor)
+            );
+        }
+
+        // Ties go to the floor below, whatever order the calls were registered in
+        let floors: &[Floor] = &[3, 1];
+        let queue = CallQueue::<2> {
+            floors: [1, 3],
+            len: 2,
+        };
+        assert_eq!(Some(1), floors.nearest_floor(2));
+        assert_eq!(Some(1), queue.nearest_floor(2));
+        let floors: &[Floor] = &[9, -2, 4, 12, 0];
+        assert_eq!(Some(0), floors.nearest_floor(2));
+        assert_eq!(Some(-2), floors.nearest_floor(-1));
+    }
+}
@@ -0,0 +1,179 @@
+//! Mapping of controller inputs and outputs to CANopen frames, following the structure of the
+//! CANopen Lift application profile (CiA 417).
+//!
+//! The profile divides a lift into virtual devices communicating through process data objects (PDOs).
+//! This module covers the three units the controller interacts with:
+//!
+//! * **call units** (car and landing panels) send calls, decoded with `decode_call`
+//! * **door units** report the door state, decoded with `decode_door_status`
+//! * the **drive unit** receives the commanded velocity, encoded with `encode_drive_command`,
+//!   and reports position and velocity, decoded with `decode_drive_status`
+//!
+//! Frames are plain data, so any CAN driver can be used to send and receive them.
+//! Velocities and positions are transferred as signed integers in thousandths of a floor (per second),
+//! all multi-byte values are little endian as mandated by CANopen.
+
+use crate::{Action, Direction, Floor, Position, Velocity};
+
+/// Function code of the first transmit PDO, the COB-ID is the function code plus the node id
+pub const TPDO1: u16 = 0x180;
+
+/// Function code of the first receive PDO, the COB-ID is the function code plus the node id
+pub const RPDO1: u16 = 0x200;
+
+/// A classic CAN frame with an 11 bit identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanFrame {
+    pub id: u16,
+    pub len: u8,
+    pub data: [u8; 8],
+}
+
+/// Node ids of the virtual devices on the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeIds {
+    pub call_unit: u8,
+    pub door_unit: u8,
+    pub drive_unit: u8,
+}
+
+/// The kind of call received from a call unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Call {
+    /// Call from the car operating panel
+    Car(Floor),
+    /// Call from a landing panel, with the requested direction of travel
+    Landing(Floor, Direction),
+}
+
+/// Door state as reported by a door unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorStatus {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+    /// The door unit reports a fault, for instance an obstructed or locked door
+    Fault,
+}
+
+/// Position and velocity reported by the drive unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriveStatus {
+    pub position: Position,
+    pub velocity: Velocity,
+}
+
+/// Bit in the drive command control byte signalling the car is stopped at a floor and may open its doors
+const STOPPED_AT_FLOOR: u8 = 0x01;
+
+/// Encode an action into the receive PDO of the drive unit
+pub fn encode_drive_command(action: &Action, nodes: &NodeIds) -> CanFrame {
+    let mut data = [0u8; 8];
+    data[0..4].copy_from_slice(&to_milli(action.target_velocity).to_le_bytes());
+    if action.is_stopped_at_current_floor {
+        data[4] |= STOPPED_AT_FLOOR;
+    }
+
+    CanFrame {
+        id: RPDO1 + nodes.drive_unit as u16,
+        len: 5,
+        data,
+    }
+}
+
+/// Decode the transmit PDO of the drive unit, `None` if the frame is from another node
+pub fn decode_drive_status(frame: &CanFrame, nodes: &NodeIds) -> Option<DriveStatus> {
+    if frame.id != TPDO1 + nodes.drive_unit as u16 || frame.len < 8 {
+        return None;
+    }
+
+    Some(DriveStatus {
+        position: from_milli(i32_at(&frame.data, 0)),
+        velocity: from_milli(i32_at(&frame.data, 4)),
+    })
+}
+
+/// Decode the transmit PDO of a call unit, `None` if the frame is from another node or malformed.
+///
+/// Layout: floor (i32), call type (0 = car, 1 = landing up, 2 = landing down)
+pub fn decode_call(frame: &CanFrame, nodes: &NodeIds) -> Option<Call> {
+    if frame.id != TPDO1 + nodes.call_unit as u16 || frame.len < 5 {
+        return None;
+    }
+
+    let floor = i32_at(&frame.data, 0);
+    match frame.data[4] {
+        0 => Some(Call::Car(floor)),
+        1 => Some(Call::Landing(floor, Direction::Up)),
+        2 => Some(Call::Landing(floor, Direction::Down)),
+        _ => None,
+    }
+}
+
+/// Decode the transmit PDO of a door unit, `None` if the frame is from another node or malformed
+pub fn decode_door_status(frame: &CanFrame, nodes: &NodeIds) -> Option<DoorStatus> {
+    if frame.id != TPDO1 + nodes.door_unit as u16 || frame.len < 1 {
+        return None;
+    }
+
+    match frame.data[0] {
+        0 => Some(DoorStatus::Closed),
+        1 => Some(DoorStatus::Opening),
+        2 => Some(DoorStatus::Open),
+        3 => Some(DoorStatus::Closing),
+        4 => Some(DoorStatus::Fault),
+        _ => None,
+    }
+}
+
+fn to_milli(value: f32) -> i32 {
+    (value * 1000.0) as i32
+}
+
+fn from_milli(value: i32) -> f32 {
+    value as f32 / 1000.0
+}
+
+fn i32_at(data: &[u8; 8], offset: usize) -> i32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    i32::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODES: NodeIds = NodeIds {
+        call_unit: 0x10,
+        door_unit: 0x20,
+        drive_unit: 0x30,
+    };
+
+    #[test]
+    fn drive_command_and_calls() {
+        let frame = encode_drive_command(
+            &Action {
+                target_velocity: -0.5,
+                is_stopped_at_current_floor: false,
+            },
+            &NODES,
+        );
+        assert_eq!(0x230, frame.id);
+        assert_eq!(-500, i32_at(&frame.data, 0));
+
+        let mut call = CanFrame {
+            id: 0x190,
+            len: 5,
+            data: [0; 8],
+        };
+        call.data[0..4].copy_from_slice(&(-2i32).to_le_bytes());
+        call.data[4] = 2;
+        assert_eq!(
+            Some(Call::Landing(-2, Direction::Down)),
+            decode_call(&call, &NODES)
+        );
+        assert_eq!(None, decode_door_status(&call, &NODES));
+    }
+}
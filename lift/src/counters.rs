@@ -0,0 +1,175 @@
+use crate::{Direction, Floor, FloorMap, LiftObserver};
+
+/// Operational counters of a controller, the raw data for duty-cycle analysis and maintenance planning
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Counters {
+    /// Number of times the lift has started moving from a standstill at a floor
+    pub motor_starts: u32,
+
+    /// Total distance traveled in normalised units (floors).
+    /// Kept in double precision so small steps are not lost once the total grows large
+    pub distance: f64,
+
+    /// Number of times the lift reversed its direction of travel
+    pub reversals: u32,
+
+    /// Number of stops made at requested floors
+    pub stops_served: u32,
+
+    /// Number of times the doors opened and closed, as reported by the host with `LiftController::record_door_cycle`
+    pub door_cycles: u32,
+}
+
+impl Counters {
+    /// Counters with everything set to zero
+    pub const fn new() -> Self {
+        Counters {
+            motor_starts: 0,
+            distance: 0.0,
+            reversals: 0,
+            stops_served: 0,
+            door_cycles: 0,
+        }
+    }
+
+    /// Total distance traveled in meters, with the mean floor height of `floors`.
+    /// Exact for evenly spaced floors, with uneven floors the travel is taken to be spread evenly over the shaft
+    pub fn distance_meters(&self, floors: &FloorMap) -> f64 {
+        self.distance * floors.mean_floor_height() as f64
+    }
+
+    /// Add the distance between two consecutive position readings
+    pub(crate) fn add_distance(&mut self, from: f32, to: f32) {
+        let delta = (to - from) as f64;
+        self.distance += if delta < 0.0 { -delta } else { delta };
+    }
+}
+
+impl LiftObserver for Counters {
+    fn on_departed(&mut self, _floor: Floor, _direction: Direction) {
+        self.motor_starts = self.motor_starts.saturating_add(1);
+    }
+
+    fn on_arrived(&mut self, _floor: Floor) {
+        self.stops_served = self.stops_served.saturating_add(1);
+    }
+
+    fn on_direction_changed(&mut self, from: Direction, to: Direction) {
+        if from != Direction::Neutral && to != Direction::Neutral {
+            self.reversals = self.reversals.saturating_add(1);
+        }
+    }
+}
+
+/// Limits for the operational counters after which the lift is due for service.
+/// A limit of `None` is never exceeded.
+///
+/// The distance is in floors like `Counters::distance`, a limit in meters is converted with
+/// `FloorMap::mean_floor_height`, such as `10_000_000.0 / floors.mean_floor_height()` for 10 000 km.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MaintenanceThresholds {
+    pub motor_starts: Option<u32>,
+    pub distance: Option<f64>,
+    pub reversals: Option<u32>,
+    pub stops_served: Option<u32>,
+    pub door_cycles: Option<u32>,
+}
+
+/// Which of the operational counters have reached their maintenance threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ServiceDue {
+    pub motor_starts: bool,
+    pub distance: bool,
+    pub reversals: bool,
+    pub stops_served: bool,
+    pub door_cycles: bool,
+}
+
+impl MaintenanceThresholds {
+    /// Thresholds that are never exceeded
+    pub const fn none() -> Self {
+        MaintenanceThresholds {
+            motor_starts: None,
+            distance: None,
+            reversals: None,
+            stops_served: None,
+            door_cycles: None,
+        }
+    }
+}
+
+impl ServiceDue {
+    /// If any counter has reached its threshold
+    pub fn any(&self) -> bool {
+        self.motor_starts
+            || self.distance
+            || self.reversals
+            || self.stops_served
+            || self.door_cycles
+    }
+}
+
+impl Counters {
+    /// Compare the counters against the thresholds
+    pub fn service_due(&self, thresholds: &MaintenanceThresholds) -> ServiceDue {
+        fn exceeded<T: PartialOrd>(value: T, threshold: Option<T>) -> bool {
+            threshold.is_some_and(|threshold| value >= threshold)
+        }
+
+        ServiceDue {
+            motor_starts: exceeded(self.motor_starts, thresholds.motor_starts),
+            distance: exceeded(self.distance, thresholds.distance),
+            reversals: exceeded(self.reversals, thresholds.reversals),
+            stops_served: exceeded(self.stops_served, thresholds.stops_served),
+            door_cycles: exceeded(self.door_cycles, thresholds.door_cycles),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_due_when_threshold_reached() {
+        let counters = Counters {
+            motor_starts: 10,
+            distance: 250.0,
+            reversals: 3,
+            stops_served: 9,
+            door_cycles: 100_000,
+        };
+        let thresholds = MaintenanceThresholds {
+            motor_starts: Some(10),
+            distance: Some(1000.0),
+            door_cycles: Some(100_000),
+            ..MaintenanceThresholds::none()
+        };
+
+        let due = counters.service_due(&thresholds);
+        assert!(due.any());
+        assert!(due.motor_starts);
+        assert!(!due.distance);
+        assert!(!due.reversals);
+        assert!(due.door_cycles);
+        assert!(!counters.service_due(&MaintenanceThresholds::none()).any());
+    }
+
+    #[test]
+    fn distance_in_meters() {
+        let counters = Counters {
+            distance: 250.0,
+            ..Counters::new()
+        };
+        assert_eq!(750.0, counters.distance_meters(&FloorMap::uniform(0, 3.0)));
+        let elevations = [0.0, 5.0, 8.0, 11.0];
+        let floors = FloorMap::with_elevations(-1, &elevations).unwrap();
+        assert_eq!(250.0 * 11.0 / 3.0, counters.distance_meters(&floors) as f32);
+    }
+}
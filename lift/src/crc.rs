@@ -0,0 +1,24 @@
+/// CRC-16/CCITT-FALSE checksum (polynomial 0x1021, initial value 0xFFFF)
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, byte| {
+        let mut crc = crc ^ ((*byte as u16) << 8);
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(0x29B1, crc16(b"123456789"));
+    }
+}
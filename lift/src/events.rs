@@ -0,0 +1,194 @@
+use crate::{Direction, Faults, Floor, LiftObserver};
+
+/// Timestamp supplied by the caller when polling, in whatever unit the host uses (typically milliseconds)
+pub type Timestamp = u64;
+
+/// The transitions reported by the controller, mirroring the callbacks of `LiftObserver`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EventKind {
+    Departed {
+        floor: Floor,
+        direction: Direction,
+    },
+    Arrived {
+        floor: Floor,
+    },
+    DirectionChanged {
+        from: Direction,
+        to: Direction,
+    },
+    TargetSelected {
+        floor: Floor,
+    },
+    EmergencyStop {
+        activated: bool,
+    },
+    Chime {
+        floor: Floor,
+        direction: Direction,
+    },
+    /// Only the faults raised by the poll are set, see `LiftObserver::on_fault`
+    Fault {
+        faults: Faults,
+    },
+}
+
+/// A transition together with the time of the poll it happened in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Event {
+    pub timestamp: Timestamp,
+    pub kind: EventKind,
+}
+
+/// Fixed-capacity ring buffer of events, filled by `LiftController::poll_with_events` and drained by the host.
+///
+/// If the host does not keep up the oldest events are overwritten, the number of lost events
+/// is available through `overflowed`.
+#[derive(Debug, Clone)]
+pub struct EventQueue<const N: usize> {
+    events: [Option<Event>; N],
+    head: usize,
+    len: usize,
+    overflowed: u32,
+}
+
+impl<const N: usize> EventQueue<N> {
+    /// Create an empty queue
+    pub const fn new() -> Self {
+        EventQueue {
+            events: [None; N],
+            head: 0,
+            len: 0,
+            overflowed: 0,
+        }
+    }
+
+    /// Add an event to the back of the queue, overwriting the oldest event if the queue is full
+    pub fn push(&mut self, event: Event) {
+        if N == 0 {
+            self.overflowed = self.overflowed.saturating_add(1);
+            return;
+        }
+
+        let tail = (self.head + self.len) % N;
+        self.events[tail] = Some(event);
+        if self.len == N {
+            self.head = (self.head + 1) % N;
+            self.overflowed = self.overflowed.saturating_add(1);
+        } else {
+            self.len += 1;
+        }
+    }
+
+    /// Remove the oldest event from the queue
+    pub fn pop(&mut self) -> Option<Event> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        event
+    }
+
+    /// Iterator removing the events from the queue, oldest first
+    pub fn drain(&mut self) -> impl Iterator<Item = Event> + '_ {
+        core::iter::from_fn(move || self.pop())
+    }
+
+    /// Number of events in the queue
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// If there are no events in the queue
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of events lost because the queue was full
+    pub fn overflowed(&self) -> u32 {
+        self.overflowed
+    }
+}
+
+impl<const N: usize> Default for EventQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Observer pushing every transition into a queue with the given timestamp
+pub(crate) struct Stamped<'a, const N: usize> {
+    pub timestamp: Timestamp,
+    pub queue: &'a mut EventQueue<N>,
+}
+
+impl<'a, const N: usize> Stamped<'a, N> {
+    fn push(&mut self, kind: EventKind) {
+        self.queue.push(Event {
+            timestamp: self.timestamp,
+            kind,
+        });
+    }
+}
+
+impl<'a, const N: usize> LiftObserver for Stamped<'a, N> {
+    fn on_departed(&mut self, floor: Floor, direction: Direction) {
+        self.push(EventKind::Departed { floor, direction });
+    }
+
+    fn on_arrived(&mut self, floor: Floor) {
+        self.push(EventKind::Arrived { floor });
+    }
+
+    fn on_direction_changed(&mut self, from: Direction, to: Direction) {
+        self.push(EventKind::DirectionChanged { from, to });
+    }
+
+    fn on_target_selected(&mut self, floor: Floor) {
+        self.push(EventKind::TargetSelected { floor });
+    }
+
+    fn on_emergency_stop(&mut self, activated: bool) {
+        self.push(EventKind::EmergencyStop { activated });
+    }
+
+    fn on_chime(&mut self, floor: Floor, direction: Direction) {
+        self.push(EventKind::Chime { floor, direction });
+    }
+
+    fn on_fault(&mut self, faults: Faults) {
+        self.push(EventKind::Fault { faults });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arrived(timestamp: Timestamp) -> Event {
+        Event {
+            timestamp,
+            kind: EventKind::Arrived { floor: 0 },
+        }
+    }
+
+    #[test]
+    fn overwrites_oldest_when_full() {
+        let mut queue = EventQueue::<2>::new();
+        queue.push(arrived(1));
+        queue.push(arrived(2));
+        queue.push(arrived(3));
+
+        assert_eq!(1, queue.overflowed());
+        assert_eq!(Some(2), queue.pop().map(|e| e.timestamp));
+        assert_eq!(Some(3), queue.pop().map(|e| e.timestamp));
+        assert!(queue.is_empty());
+    }
+}
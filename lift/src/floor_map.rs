@@ -0,0 +1,186 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use core::cmp::Ordering;
+
+use crate::{Floor, Position};
+
+/// Maps the normalized position used by the controller, where floor `n` is at position `n`,
+/// to meters above a datum and back.
+///
+/// Floors are either evenly spaced, or placed at borrowed landing elevations so lobbies and
+/// plant rooms can be taller than the rest. Between two landings the position is interpolated
+/// linearly, beyond the outermost landings the nearest floor-to-floor height is extrapolated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloorMap<'a> {
+    lowest_floor: Floor,
+    heights: Heights<'a>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Heights<'a> {
+    /// Height of every floor in meters, the lowest floor is at the datum
+    Uniform(f32),
+    /// Elevation of each landing from the lowest floor up, in meters above the datum
+    Elevations(&'a [f32]),
+}
+
+/// The landing elevations cannot be used for a `FloorMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FloorMapError {
+    /// Fewer than two landings, so no floor height is known
+    TooFewLandings,
+    /// The landing at this index is not above the one below it
+    NotAscending(usize),
+}
+
+impl<'a> FloorMap<'a> {
+    /// Floors `height` meters apart, with `lowest_floor` at the datum
+    pub const fn uniform(lowest_floor: Floor, height: f32) -> Self {
+        FloorMap {
+            lowest_floor,
+            heights: Heights::Uniform(height),
+        }
+    }
+
+    /// Landings at the given elevations in meters above the datum, from `lowest_floor` and up
+    pub fn with_elevations(
+        lowest_floor: Floor,
+        elevations: &'a [f32],
+    ) -> Result<Self, FloorMapError> {
+        if elevations.len() < 2 {
+            return Err(FloorMapError::TooFewLandings);
+        }
+        // Comparing with partial_cmp also rejects NaN
+        if let Some(index) = (1..elevations.len())
+            .find(|i| elevations[*i].partial_cmp(&elevations[*i - 1]) != Some(Ordering::Greater))
+        {
+            return Err(FloorMapError::NotAscending(index));
+        }
+        Ok(FloorMap {
+            lowest_floor,
+            heights: Heights::Elevations(elevations),
+        })
+    }
+
+    pub fn lowest_floor(&self) -> Floor {
+        self.lowest_floor
+    }
+
+    /// Elevation of the landing at `floor` in meters above the datum
+    pub fn elevation(&self, floor: Floor) -> f32 {
+        self.to_meters(floor as Position)
+    }
+
+    /// Distance in meters from `floor` to the floor above
+    pub fn floor_height(&self, floor: Floor) -> f32 {
+        match self.heights {
+            Heights::Uniform(height) => height,
+            Heights::Elevations(elevations) => {
+                let index = self.interval(floor as Position, elevations.len());
+                elevations[index + 1] - elevations[index]
+            }
+        }
+    }
+
+    /// Mean distance in meters between two floors, over the landings of the map
+    pub fn mean_floor_height(&self) -> f32 {
+        match self.heights {
+            Heights::Uniform(height) => height,
+            Heights::Elevations(elevations) => {
+                (elevations[elevations.len() - 1] - elevations[0]) / (elevations.len() - 1) as f32
+            }
+        }
+    }
+
+    /// Meters above the datum of a normalized position
+    pub fn to_meters(&self, position: Position) -> f32 {
+        let relative = position - self.lowest_floor as Position;
+        match self.heights {
+            Heights::Uniform(height) => relative * height,
+            Heights::Elevations(elevations) => {
+                let index = self.interval(position, elevations.len());
+                let (below, above) = (elevations[index], elevations[index + 1]);
+                below + (relative - index as Position) * (above - below)
+            }
+        }
+    }
+
+    /// Normalized position of a point `meters` above the datum
+    pub fn to_position(&self, meters: f32) -> Position {
+        let lowest = self.lowest_floor as Position;
+        match self.heights {
+            Heights::Uniform(height) => lowest + meters / height,
+            Heights::Elevations(elevations) => {
+                // Index of the interval containing the point, clamped to the outermost intervals
+                let index = elevations[1..elevations.len() - 1]
+                    .partition_point(|elevation| *elevation <= meters);
+                let (below, above) = (elevations[index], elevations[index + 1]);
+                lowest + index as Position + (meters - below) / (above - below)
+            }
+        }
+    }
+
+    /// Index of the landing below the interval containing `position`, clamped to the outermost
+    /// intervals so positions beyond the landings extrapolate
+    fn interval(&self, position: Position, landings: usize) -> usize {
+        let relative = (position - self.lowest_floor as Position).floor();
+        if relative <= 0.0 {
+            0
+        } else {
+            (relative as usize).min(landings - 2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_floors() {
+        let map = FloorMap::uniform(-2, 3.0);
+        assert_eq!(0.0, map.elevation(-2));
+        assert_eq!(7.5, map.to_meters(0.5));
+        assert_eq!(0.5, map.to_position(7.5));
+        assert_eq!(3.0, map.floor_height(10));
+    }
+
+    #[test]
+    fn landing_elevations() {
+        // A tall lobby and a plant room between floors 2 and 3
+        let elevations = [0.0, 5.0, 8.0, 14.0, 17.0];
+        let map = FloorMap::with_elevations(0, &elevations).unwrap();
+
+        for (floor, elevation) in elevations.iter().enumerate() {
+            assert_eq!(*elevation, map.elevation(floor as Floor));
+            assert_eq!(floor as Position, map.to_position(*elevation));
+        }
+        assert_eq!(11.0, map.to_meters(2.5));
+        assert_eq!(2.5, map.to_position(11.0));
+        assert_eq!(6.0, map.floor_height(2));
+
+        // Beyond the landings the outermost floor heights are used
+        assert_eq!(-2.5, map.to_meters(-0.5));
+        assert_eq!(20.0, map.to_meters(5.0));
+        assert_eq!(5.0, map.to_position(20.0));
+        assert_eq!(-0.5, map.to_position(-2.5));
+    }
+
+    #[test]
+    fn invalid_elevations() {
+        assert_eq!(
+            Err(FloorMapError::TooFewLandings),
+            FloorMap::with_elevations(0, &[0.0])
+        );
+        assert_eq!(
+            Err(FloorMapError::NotAscending(2)),
+            FloorMap::with_elevations(0, &[0.0, 3.0, 3.0])
+        );
+        assert_eq!(
+            Err(FloorMapError::NotAscending(1)),
+            FloorMap::with_elevations(0, &[0.0, f32::NAN])
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Fuzzing entry point.
+//!
+//! `drive` decodes an arbitrary byte slice into controller tuning and a sequence of sensor
+//! readings and commands, and polls the controller along the way. A cargo-fuzz target only has
+//! to assert that it returns `Ok`, any panic, NaN velocity or invariant violation is a finding.
+//!
+//! The first three bytes select the tuning, the rest is read as five byte records of an
+//! operation followed by a four byte argument:
+//!
+//! | Operation | Argument |
+//! |-----------|----------|
+//! | 0 register call | floor as `i8` |
+//! | 1 cancel call | floor as `i8` |
+//! | 2 emergency stop | active if the lowest bit is set |
+//! | 3 position reading | `f32`, little endian |
+//! | 4 velocity reading | `f32`, little endian |
+//! | 5 poll and move the car | time step as `f32`, little endian |
+//!
+//! Operations are taken modulo six, trailing bytes are ignored.
+
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::{
+    CallQueue, Floor, InvariantViolation, LiftController, LiftSensors, Position, Velocity,
+};
+
+const HEADER_SIZE: usize = 3;
+const RECORD_SIZE: usize = 5;
+
+/// Decode `data` and drive a controller with it, stopping at the first invariant violation
+pub fn drive(data: &[u8]) -> Result<(), InvariantViolation> {
+    let (header, records) = match data.split_first_chunk::<HEADER_SIZE>() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    let mut controller = LiftController::new(
+        0.1 + header[0] as f32 / 64.0,
+        (1 + header[1] as u32) as f32 / 1024.0,
+        (1 + header[2] as u32) as f32 / 1024.0,
+    );
+    let mut sensors = Sensors {
+        position: 0.0,
+        velocity: 0.0,
+        calls: CallQueue::new(),
+        is_emergency_stop_activated: false,
+    };
+
+    for record in records.chunks_exact(RECORD_SIZE) {
+        let argument = [record[1], record[2], record[3], record[4]];
+        let value = f32::from_le_bytes(argument);
+        match record[0] % 6 {
+            0 => {
+                // A full queue is not interesting, the call is simply dropped
+                let _ = sensors.calls.insert(argument[0] as i8 as Floor);
+            }
+            1 => {
+                sensors.calls.remove(argument[0] as i8 as Floor);
+            }
+            2 => sensors.is_emergency_stop_activated = argument[0] & 1 == 1,
+            3 => sensors.position = value,
+            4 => sensors.velocity = value,
+            _ => {
+                let action = controller.poll(&sensors, value);
+                controller.check_invariants(&sensors)?;
+
+                sensors.position += action.target_velocity * value;
+                sensors.velocity = action.target_velocity;
+                if action.is_stopped_at_current_floor {
+                    sensors.calls.remove(sensors.position.round() as Floor);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct Sensors {
+    position: Position,
+    velocity: Velocity,
+    calls: CallQueue<32>,
+    is_emergency_stop_activated: bool,
+}
+
+impl LiftSensors for Sensors {
+    fn current_floor(&self) -> Position {
+        self.position
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        self.velocity
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        self.calls.as_slice()
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        self.is_emergency_stop_activated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    #[test]
+    fn random_inputs_hold_invariants() {
+        let mut seed = 0x2545_F491u32;
+        let mut data = [0u8; 512];
+        for _ in 0..2000 {
+            for byte in data.iter_mut() {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                *byte = seed as u8;
+            }
+            assert_eq!(Ok(()), drive(&data));
+        }
+    }
+
+    #[test]
+    fn extreme_readings_do_not_panic() {
+        let mut data = std::vec![0u8, 0, 0];
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, f32::MAX, -1e30] {
+            let bytes = value.to_le_bytes();
+            data.extend_from_slice(&[0, 7, 0, 0, 0]);
+            data.push(3);
+            data.extend_from_slice(&bytes);
+            data.push(5);
+            data.extend_from_slice(&0.1f32.to_le_bytes());
+            data.push(4);
+            data.extend_from_slice(&bytes);
+            data.push(5);
+            data.extend_from_slice(&bytes);
+        }
+        assert_eq!(Ok(()), drive(&data));
+    }
+}
@@ -0,0 +1,178 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::{Direction, Floor, LiftController, LiftSensors, Position};
+
+/// Assigns hall calls to the car of a group that is estimated to reach them first.
+///
+/// Each car keeps its own controller and stop list, the dispatcher only decides which car a new
+/// call is added to. Cars stopped by the emergency stop are never assigned calls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GroupDispatcher {
+    /// Seconds added for every stop a car makes on its way to the call
+    pub average_stop: f32,
+}
+
+impl GroupDispatcher {
+    pub const fn new(average_stop: f32) -> Self {
+        GroupDispatcher { average_stop }
+    }
+
+    /// Estimated seconds until a car reaches `floor`, `None` if it cannot serve calls
+    pub fn estimate(
+        &self,
+        controller: &LiftController,
+        sensors: &dyn LiftSensors,
+        floor: Floor,
+    ) -> Option<f32> {
+        if sensors.is_emergency_stop_activated() {
+            return None;
+        }
+        controller
+            .time_to_floor(sensors, floor, self.average_stop)
+            .or_else(|| {
+                // An idle car drives straight there, after serving the calls it already has
+                let distance = (floor as Position - sensors.current_floor()).abs();
+                let stops = sensors.floors_to_stop_at().len() as f32;
+                Some(distance / controller.prefered_velocity + stops * self.average_stop)
+            })
+    }
+
+    /// Estimated seconds until a car reaches `floor` ready to travel in `direction`, as needed
+    /// for a call from a landing, `None` if it cannot serve calls.
+    ///
+    /// A car arriving while travelling the other way first serves its stops beyond the floor,
+    /// and is charged for the detour there and back.
+    pub fn estimate_hall_call(
+        &self,
+        controller: &LiftController,
+        sensors: &dyn LiftSensors,
+        floor: Floor,
+        direction: Direction,
+    ) -> Option<f32> {
+        let eta = self.estimate(controller, sensors, floor)?;
+        let position = sensors.current_floor();
+        let arriving = match controller.direction {
+            Direction::Up if (floor as Position) < position => Direction::Down,
+            Direction::Down if (floor as Position) > position => Direction::Up,
+            Direction::Neutral => return Some(eta),
+            travelling => travelling,
+        };
+        if arriving == direction {
+            return Some(eta);
+        }
+
+        let stops = sensors.floors_to_stop_at().iter().copied();
+        let furthest = match arriving {
+            Direction::Up => stops.filter(|f| *f > floor).max(),
+            _ => stops.filter(|f| *f < floor).min(),
+        };
+        Some(furthest.map_or(eta, |furthest| {
+            let detour = 2.0 * (furthest - floor).abs() as f32;
+            eta + detour / controller.prefered_velocity + self.average_stop
+        }))
+    }
+
+    /// Index of the car that should serve a call at `floor` and its estimated time of arrival,
+    /// `None` if no car can serve it. Ties go to the car listed first
+    pub fn assign<'a, I>(&self, cars: I, floor: Floor) -> Option<(usize, f32)>
+    where
+        I: IntoIterator<Item = (&'a LiftController, &'a dyn LiftSensors)>,
+    {
+        earliest(
+            cars.into_iter()
+                .map(|(controller, sensors)| self.estimate(controller, sensors, floor)),
+        )
+    }
+
+    /// Same as `assign`, for a call from a landing wanting to travel in `direction`
+    pub fn assign_hall_call<'a, I>(
+        &self,
+        cars: I,
+        floor: Floor,
+        direction: Direction,
+    ) -> Option<(usize, f32)>
+    where
+        I: IntoIterator<Item = (&'a LiftController, &'a dyn LiftSensors)>,
+    {
+        earliest(cars.into_iter().map(|(controller, sensors)| {
+            self.estimate_hall_call(controller, sensors, floor, direction)
+        }))
+    }
+}
+
+/// Index and value of the lowest estimate, the first one on ties
+fn earliest<I: Iterator<Item = Option<f32>>>(estimates: I) -> Option<(usize, f32)> {
+    estimates
+        .enumerate()
+        .filter_map(|(index, eta)| eta.map(|eta| (index, eta)))
+        .fold(None, |best, (index, eta)| match best {
+            Some((_, best_eta)) if best_eta <= eta => best,
+            _ => Some((index, eta)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimulatedLift;
+
+    #[test]
+    fn assigns_nearest_available_car() {
+        let dispatcher = GroupDispatcher::new(5.0);
+        let controllers = [
+            LiftController::new(1.0, 0.01, 0.01),
+            LiftController::new(1.0, 0.01, 0.01),
+            LiftController::new(1.0, 0.01, 0.01),
+        ];
+        let mut lifts = [
+            SimulatedLift::new(0.0),
+            SimulatedLift::new(6.0),
+            SimulatedLift::new(11.0),
+        ];
+        let assign = |lifts: &[SimulatedLift]| {
+            let sensors = lifts.iter().map(|lift| lift as &dyn LiftSensors);
+            dispatcher.assign(controllers.iter().zip(sensors), 8)
+        };
+
+        assert_eq!(Some((1, 2.0)), assign(&lifts));
+
+        // Pending stops count against a car
+        lifts[1].register_call(3);
+        assert_eq!(Some((2, 3.0)), assign(&lifts));
+
+        lifts[2].set_emergency_stop(true);
+        assert_eq!(Some((1, 7.0)), assign(&lifts));
+
+        for lift in &mut lifts {
+            lift.set_emergency_stop(true);
+        }
+        assert_eq!(None, assign(&lifts));
+    }
+
+    #[test]
+    fn hall_calls_avoid_cars_going_the_other_way() {
+        let dispatcher = GroupDispatcher::new(5.0);
+        let mut controllers = [
+            LiftController::new(1.0, 0.01, 0.01),
+            LiftController::new(1.0, 0.01, 0.01),
+        ];
+        let mut lifts = [SimulatedLift::new(2.0), SimulatedLift::new(0.0)];
+
+        // The first car heads up to floor 9
+        lifts[0].register_call(9);
+        let action = controllers[0].poll(&lifts[0], 0.1);
+        lifts[0].accept_action(action, 0.1);
+
+        let assign = |lifts: &[SimulatedLift], direction| {
+            let sensors = lifts.iter().map(|lift| lift as &dyn LiftSensors);
+            dispatcher.assign_hall_call(controllers.iter().zip(sensors), 5, direction)
+        };
+        let (car, eta) = assign(&lifts, Direction::Up).unwrap();
+        assert_eq!(0, car);
+        assert!((eta - 2.9).abs() < 1e-3);
+        assert_eq!(Some((1, 5.0)), assign(&lifts, Direction::Down));
+    }
+}
@@ -0,0 +1,161 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::{Mode, Position, Snapshot, Velocity};
+
+/// Health indicators derived by the controller over a reporting period, that is since the last
+/// call to `LiftController::feed_health`.
+///
+/// Indicators are `None` if nothing was observed during the period that would allow calculating them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HealthIndicators {
+    /// Mean distance between the lift and the floor level when arriving at a floor, in floors
+    pub leveling_error: Option<Position>,
+
+    /// Change in mean leveling error compared to the previous period which had arrivals.
+    /// A steadily positive trend indicates a drifting position sensor or worn brakes
+    pub leveling_error_trend: Option<Position>,
+
+    /// Mean time in seconds from the controller commanding a departure until the
+    /// velocity sensor reports the lift is moving
+    pub start_latency: Option<f32>,
+
+    /// Number of arrivals during the period
+    pub arrivals: u32,
+
+    /// Number of departures during the period
+    pub departures: u32,
+
+    /// Number of times the doors re-opened before closing fully during the period, as reported by the host
+    pub door_reopens: u32,
+
+    /// Door re-opens per arrival. A rising frequency points to a failing door edge or a misaligned door
+    pub reopen_frequency: Option<f32>,
+}
+
+/// Trait to be implemented by predictive-maintenance backends that want to receive health indicators.
+/// The crate does not depend on any transport, the sink decides what to do with the indicators.
+pub trait HealthSink {
+    /// Called with the indicators of the period that just ended
+    fn report(&mut self, indicators: &HealthIndicators);
+}
+
+/// Accumulates the raw measurements behind the health indicators
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct HealthMonitor {
+    leveling_error_sum: f32,
+    arrivals: u32,
+    start_latency_sum: f32,
+    started: u32,
+    departures: u32,
+    door_reopens: u32,
+    previous_leveling_error: Option<Position>,
+
+    /// Time since the last departure was commanded, while waiting for the lift to start moving
+    pending_start: Option<f32>,
+}
+
+impl HealthMonitor {
+    pub const fn new() -> Self {
+        HealthMonitor {
+            leveling_error_sum: 0.0,
+            arrivals: 0,
+            start_latency_sum: 0.0,
+            started: 0,
+            departures: 0,
+            door_reopens: 0,
+            previous_leveling_error: None,
+            pending_start: None,
+        }
+    }
+
+    /// Mean leveling error of the last period with arrivals, the reference for the trend of the next period
+    pub fn leveling_error(&self) -> Option<Position> {
+        self.previous_leveling_error
+    }
+
+    /// Set the reference for the leveling error trend, such as one saved before a restart
+    pub fn set_leveling_error(&mut self, leveling_error: Option<Position>) {
+        self.previous_leveling_error = leveling_error;
+    }
+
+    pub fn record_door_reopen(&mut self) {
+        self.door_reopens = self.door_reopens.saturating_add(1);
+    }
+
+    /// Update the measurements after a poll
+    pub fn update(
+        &mut self,
+        previous: &Snapshot,
+        current: &Snapshot,
+        position: Position,
+        velocity: Velocity,
+        time_step: f32,
+    ) {
+        if previous.mode == Mode::Serving && current.mode == Mode::Idle {
+            self.leveling_error_sum += (position - position.round()).abs();
+            self.arrivals += 1;
+        }
+
+        if previous.mode == Mode::Idle && current.mode == Mode::Serving {
+            self.departures += 1;
+            self.pending_start = Some(0.0);
+        } else if let Some(elapsed) = self.pending_start {
+            let elapsed = elapsed + time_step;
+            if velocity.abs() >= current.velocity_epsilon {
+                self.start_latency_sum += elapsed;
+                self.started += 1;
+                self.pending_start = None;
+            } else if current.mode == Mode::Serving {
+                self.pending_start = Some(elapsed);
+            } else {
+                // The departure was abandoned before the lift moved
+                self.pending_start = None;
+            }
+        }
+    }
+
+    /// Calculate the indicators for the period and start a new one
+    pub fn finish_period(&mut self) -> HealthIndicators {
+        let leveling_error = if self.arrivals > 0 {
+            Some(self.leveling_error_sum / self.arrivals as f32)
+        } else {
+            None
+        };
+        let start_latency = if self.started > 0 {
+            Some(self.start_latency_sum / self.started as f32)
+        } else {
+            None
+        };
+        let reopen_frequency = if self.arrivals > 0 {
+            Some(self.door_reopens as f32 / self.arrivals as f32)
+        } else {
+            None
+        };
+        let leveling_error_trend = match (leveling_error, self.previous_leveling_error) {
+            (Some(current), Some(previous)) => Some(current - previous),
+            _ => None,
+        };
+
+        let indicators = HealthIndicators {
+            leveling_error,
+            leveling_error_trend,
+            start_latency,
+            arrivals: self.arrivals,
+            departures: self.departures,
+            door_reopens: self.door_reopens,
+            reopen_frequency,
+        };
+
+        *self = HealthMonitor {
+            previous_leveling_error: leveling_error.or(self.previous_leveling_error),
+            pending_start: self.pending_start,
+            ..HealthMonitor::new()
+        };
+
+        indicators
+    }
+}
@@ -0,0 +1,166 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use core::cmp::Ordering;
+
+use crate::kernel::{self, Limits, Readings};
+use crate::{Direction, Floor, LiftController, LiftSensors, Mode, Position, Velocity};
+
+/// An invariant of the controller that did not hold after a poll
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InvariantViolation {
+    /// The direction of travel points away from the floor being served
+    DirectionAwayFromTarget {
+        direction: Direction,
+        position: Position,
+        target: Floor,
+    },
+    /// The commanded velocity is above the prefered velocity, or not a number
+    SpeedAboveLimit { target_velocity: Velocity },
+    /// The lift was commanded to move while the emergency stop is active
+    MovingDuringEmergencyStop { target_velocity: Velocity },
+    /// The stopped flag does not agree with the position and velocity readings
+    StoppedFlagInconsistent {
+        position: Position,
+        velocity: Velocity,
+    },
+    /// The controller is idle but still has a target, or is serving without one
+    TargetInconsistentWithMode { mode: Mode, target: Option<Floor> },
+}
+
+pub(crate) fn check(
+    controller: &LiftController,
+    sensors: &dyn LiftSensors,
+) -> Result<(), InvariantViolation> {
+    let position = sensors.current_floor();
+    let velocity = sensors.current_velocity();
+
+    match (controller.mode, controller.target) {
+        (Mode::Serving, Some(target)) => {
+            // A target on the nearest floor but outside the leeway is approached without changing direction
+            let delta = target.cmp(&(position.round() as Floor));
+            let away = match controller.direction {
+                Direction::Up => delta == Ordering::Less,
+                Direction::Down => delta == Ordering::Greater,
+                Direction::Neutral => delta != Ordering::Equal,
+            };
+            if away {
+                return Err(InvariantViolation::DirectionAwayFromTarget {
+                    direction: controller.direction,
+                    position,
+                    target,
+                });
+            }
+        }
+        (Mode::Serving, None) | (Mode::Idle, Some(_)) => {
+            return Err(InvariantViolation::TargetInconsistentWithMode {
+                mode: controller.mode,
+                target: controller.target,
+            })
+        }
+        _ => {}
+    }
+
+    let action = match controller.last_action {
+        Some(action) => action,
+        None => return Ok(()),
+    };
+    let target_velocity = action.target_velocity;
+
+    if target_velocity.is_nan() || target_velocity.abs() > controller.prefered_velocity {
+        return Err(InvariantViolation::SpeedAboveLimit { target_velocity });
+    }
+
+    let stopped_flag_consistent = match controller.mode {
+        Mode::Serving => !action.is_stopped_at_current_floor,
+        Mode::Idle => action.is_stopped_at_current_floor,
+        Mode::EmergencyStop => {
+            if target_velocity != 0.0 {
+                return Err(InvariantViolation::MovingDuringEmergencyStop { target_velocity });
+            }
+            let limits = Limits {
+                prefered_velocity: controller.prefered_velocity,
+                floor_leeway: controller.floor_leeway,
+                velocity_epsilon: controller.velocity_epsilon,
+                max_acceleration: controller.max_acceleration,
+            };
+            let readings = Readings {
+                position,
+                velocity,
+                emergency_stop: true,
+            };
+            action.is_stopped_at_current_floor
+                == kernel::stopped_at_floor(&limits, readings).is_some()
+        }
+    };
+    if !stopped_flag_consistent {
+        return Err(InvariantViolation::StoppedFlagInconsistent { position, velocity });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{check_properties, Planner, PropertyConfig};
+    use crate::Action;
+
+    /// Checks the invariants after every poll of the property harness
+    struct Checked(LiftController);
+
+    impl Planner for Checked {
+        fn plan(&mut self, sensors: &dyn LiftSensors, time_step: f32) -> Action {
+            let action = self.0.poll(sensors, time_step);
+            assert_eq!(Ok(()), self.0.check_invariants(sensors));
+            action
+        }
+    }
+
+    #[test]
+    fn invariants_hold_while_serving_random_calls() {
+        let config = PropertyConfig {
+            cases: 16,
+            ..PropertyConfig::default()
+        };
+        assert_eq!(
+            Ok(()),
+            check_properties(&config, || Checked(LiftController::new(1.0, 0.01, 0.01)))
+        );
+    }
+
+    #[test]
+    fn direction_away_from_target_is_reported() {
+        struct Sensors;
+
+        impl LiftSensors for Sensors {
+            fn current_floor(&self) -> Position {
+                2.0
+            }
+            fn current_velocity(&self) -> Velocity {
+                0.0
+            }
+            fn floors_to_stop_at(&self) -> &[Floor] {
+                &[5]
+            }
+            fn is_emergency_stop_activated(&self) -> bool {
+                false
+            }
+        }
+
+        let mut controller = LiftController::new(1.0, 0.01, 0.01);
+        controller.poll(&Sensors, 0.1);
+        assert_eq!(Ok(()), controller.check_invariants(&Sensors));
+
+        controller.direction = Direction::Down;
+        assert_eq!(
+            Err(InvariantViolation::DirectionAwayFromTarget {
+                direction: Direction::Down,
+                position: 2.0,
+                target: 5,
+            }),
+            controller.check_invariants(&Sensors)
+        );
+    }
+}
@@ -0,0 +1,437 @@
+//! Side-effect free planning kernel.
+//!
+//! Everything the controller decides on a poll is computed here from plain values, which keeps
+//! the math small enough to be checked exhaustively with proptest and Kani. `LiftController`
+//! only feeds it sensor readings and stores the resulting plan.
+
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use core::cmp::Ordering;
+
+use crate::calls::Calls;
+use crate::{Action, Direction, Floor, Mode, Position, Velocity};
+
+/// Tuning of the controller, see `LiftController::new`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Limits {
+    pub prefered_velocity: Velocity,
+    pub floor_leeway: Position,
+    pub velocity_epsilon: Velocity,
+    pub max_acceleration: Option<f32>,
+}
+
+/// Sensor readings for a single poll
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Readings {
+    pub position: Position,
+    pub velocity: Velocity,
+    pub emergency_stop: bool,
+}
+
+/// Outcome of a poll, the new controller state and the action to perform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Plan {
+    pub direction: Direction,
+    pub mode: Mode,
+    pub target: Option<Floor>,
+    pub action: Action,
+}
+
+/// Plan the next action from the previous direction and target, the readings and the registered calls
+pub(crate) fn plan<C: Calls + ?Sized>(
+    limits: &Limits,
+    direction: Direction,
+    target: Option<Floor>,
+    readings: Readings,
+    calls: &C,
+    time_step: f32,
+) -> Plan {
+    // If the emergency step sensor is active this should take the absolutely highest proprity
+    if readings.emergency_stop {
+        return Plan {
+            direction,
+            mode: Mode::EmergencyStop,
+            target,
+            action: Action {
+                target_velocity: 0.0,
+                is_stopped_at_current_floor: stopped_at_floor(limits, readings).is_some(),
+            },
+        };
+    }
+
+    match next_target_floor(&direction, readings.position, limits.floor_leeway, calls) {
+        (next_direction, Some(next_target_floor)) => Plan {
+            /*
+            If a non-neutral direction is given change the direction.
+            In this case we treat Neutral as 'go the same direction'
+            */
+            direction: match next_direction {
+                Direction::Neutral => direction,
+                next_direction => next_direction,
+            },
+            mode: Mode::Serving,
+            target: Some(next_target_floor),
+            action: Action {
+                target_velocity: approach(limits, readings, next_target_floor, time_step),
+                is_stopped_at_current_floor: false,
+            },
+        },
+        // The target was cancelled while travelling to it, continue to the next floor to stop there.
+        // A car standing between floors without calls, such as after an emergency stop, stays put
+        (_, None)
+            if target.is_some_and(|target| calls.nearest_floor(target) != Some(target))
+                && readings.velocity.abs() >= limits.velocity_epsilon
+                && floor_within_leeway(readings.position, limits.floor_leeway).is_none() =>
+        {
+            let next_floor = match direction {
+                Direction::Up => readings.position.ceil(),
+                Direction::Down => readings.position.floor(),
+                Direction::Neutral => readings.position.round(),
+            } as Floor;
+            Plan {
+                direction,
+                mode: Mode::Serving,
+                target: Some(next_floor),
+                action: Action {
+                    target_velocity: approach(limits, readings, next_floor, time_step),
+                    is_stopped_at_current_floor: false,
+                },
+            }
+        }
+        // No target floor is set, we can simply wait at the current floor
+        (_, None) => Plan {
+            direction,
+            mode: Mode::Idle,
+            target: None,
+            action: Action {
+                target_velocity: 0.0,
+                is_stopped_at_current_floor: true,
+            },
+        },
+    }
+}
+
+/// The floor the lift is standing still at, if any
+pub(crate) fn stopped_at_floor(limits: &Limits, readings: Readings) -> Option<Floor> {
+    if readings.velocity.abs() < limits.velocity_epsilon {
+        floor_within_leeway(readings.position, limits.floor_leeway)
+    } else {
+        None
+    }
+}
+
+/// Check if it is possible to stop at the current position
+/// Returns Some(Floor) if it is possible to stop, and None if it is impossible
+pub(crate) fn floor_within_leeway(position: Position, floor_leeway: Position) -> Option<Floor> {
+    let nearest_floor = position.round();
+    let floor_distance = (position - nearest_floor).abs();
+
+    if floor_distance < floor_leeway {
+        Some(nearest_floor as Floor)
+    } else {
+        None
+    }
+}
+
+/// Velocity towards the target floor, clamped to the prefered velocity.
+///
+/// We take special consideration here when calculating the target velocity.
+/// If the time_step is too high we need to make sure we don't overshoot the floor.
+pub(crate) fn target_velocity(
+    position: Position,
+    target: Floor,
+    prefered_velocity: Velocity,
+    time_step: f32,
+) -> Velocity {
+    let signed_distance = target as f32 - position;
+
+    let exact_target_velocity = (signed_distance / time_step).abs();
+
+    f32::min(prefered_velocity, exact_target_velocity).copysign(signed_distance)
+}
+
+/// Velocity towards `target` within the limits
+fn approach(limits: &Limits, readings: Readings, target: Floor, time_step: f32) -> Velocity {
+    limit_acceleration(
+        target_velocity(
+            readings.position,
+            target,
+            limits.prefered_velocity,
+            time_step,
+        ),
+        readings.velocity,
+        target as Position - readings.position,
+        limits.max_acceleration,
+        time_step,
+    )
+}
+
+/// Limit the change from the measured velocity to `max_acceleration` over a step, and slow down
+/// in time to stop `distance` away at that deceleration.
+///
+/// The result is never faster than `velocity` and never points the other way, so the limit
+/// cannot cause an overshoot. A lift travelling away from the target is stopped before turning.
+pub(crate) fn limit_acceleration(
+    velocity: Velocity,
+    measured: Velocity,
+    distance: Position,
+    max_acceleration: Option<f32>,
+    time_step: f32,
+) -> Velocity {
+    let max_acceleration = match max_acceleration {
+        Some(max_acceleration) if max_acceleration > 0.0 => max_acceleration,
+        _ => return velocity,
+    };
+    let step = max_acceleration * time_step;
+    // Fastest velocity that still leaves room to brake after travelling at it for this step,
+    // the solution of v² = 2a(d - v·dt)
+    let braking = (step * step + 2.0 * max_acceleration * distance.abs()).sqrt() - step;
+    // min and max instead of clamp, so a NaN reading does not panic
+    let limited = velocity
+        .abs()
+        .min(braking)
+        .copysign(velocity)
+        .max(measured - step)
+        .min(measured + step);
+
+    if limited * velocity > 0.0 {
+        limited.abs().min(velocity.abs()).copysign(velocity)
+    } else {
+        0.0
+    }
+}
+
+/// Find the next target floor and the direction to it
+pub(crate) fn next_target_floor<C: Calls + ?Sized>(
+    direction: &Direction,
+    current_floor: Position,
+    floor_leeway: Position,
+    floors: &C,
+) -> (Direction, Option<Floor>) {
+    /*
+    Find the nearest floor. If there is no floor in the current direction, try to look in the other direction.
+    This strategy of priorizing the current direction is important to reduce (acutally make bounds on)
+    the worst case pickup time of for any passengers. With this strategy we can ensure that for a building
+    that is N stories tall the lift will make no more than (N - 1) stops before picking up a passenger,
+    and likewise will make at most (N - 1) stops before dropping them off.
+    */
+    let target_floor = match direction {
+        Direction::Up => floors
+            .nearest_floor_above(current_floor.round() as i32)
+            .or_else(|| floors.nearest_floor_below(current_floor.round() as i32)),
+        Direction::Down => floors
+            .nearest_floor_below(current_floor.round() as i32)
+            .or_else(|| floors.nearest_floor_above(current_floor.round() as i32)),
+        Direction::Neutral => floors.nearest_floor(current_floor.round() as i32),
+    }
+    /*
+    We filter away the current floor from the consideration, this may not be strictly
+    necessary, but since we have made the Lift implementation generic we can't make
+    any assumptions about when the floor-list is cleared
+    */
+    .filter(|floor| (*floor as f32 - current_floor).abs() > floor_leeway);
+
+    match target_floor {
+        Some(target_floor) => {
+            let direction = match target_floor.cmp(&(current_floor.round() as i32)) {
+                Ordering::Greater => Direction::Up,
+                Ordering::Equal => Direction::Neutral,
+                Ordering::Less => Direction::Down,
+            };
+
+            (direction, Some(target_floor))
+        }
+        None => (Direction::Neutral, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use proptest::prelude::*;
+
+    const LIMITS: Limits = Limits {
+        prefered_velocity: 1.0,
+        floor_leeway: 0.05,
+        velocity_epsilon: 0.01,
+        max_acceleration: None,
+    };
+
+    fn direction() -> impl Strategy<Value = Direction> {
+        prop_oneof![
+            Just(Direction::Up),
+            Just(Direction::Down),
+            Just(Direction::Neutral)
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn never_passes_target_within_a_step(
+            position in -200.0f32..200.0,
+            target in -200i32..200,
+            prefered_velocity in 0.0f32..20.0,
+            time_step in 0.001f32..10.0,
+        ) {
+            let velocity = target_velocity(position, target, prefered_velocity, time_step);
+            let distance = target as f32 - position;
+            let travelled = velocity * time_step;
+
+            prop_assert!(velocity.abs() <= prefered_velocity);
+            prop_assert!(travelled * distance >= 0.0);
+            prop_assert!(travelled.abs() <= distance.abs() * (1.0 + 1e-5) + 1e-5);
+        }
+
+        #[test]
+        fn acceleration_is_limited(
+            position in -200.0f32..200.0,
+            target in -200i32..200,
+            measured in -5.0f32..5.0,
+            max_acceleration in 0.01f32..5.0,
+            time_step in 0.001f32..10.0,
+        ) {
+            let desired = target_velocity(position, target, 1.0, time_step);
+            let distance = target as f32 - position;
+            let velocity = limit_acceleration(desired, measured, distance, Some(max_acceleration), time_step);
+
+            prop_assert!(velocity * desired >= 0.0);
+            prop_assert!(velocity.abs() <= desired.abs());
+            // Only the cap to the desired velocity may brake harder than the limit
+            if velocity != 0.0 && velocity != desired {
+                prop_assert!((velocity - measured).abs() <= max_acceleration * time_step * (1.0 + 1e-5) + 1e-5);
+            }
+        }
+
+        #[test]
+        fn emergency_stop_yields_zero_velocity(
+            position in -200.0f32..200.0,
+            velocity in -5.0f32..5.0,
+            direction in direction(),
+            calls in proptest::collection::vec(-200i32..200, 0..16),
+            time_step in 0.001f32..10.0,
+        ) {
+            let readings = Readings { position, velocity, emergency_stop: true };
+            let plan = plan(&LIMITS, direction, None, readings, calls.as_slice(), time_step);
+
+            prop_assert_eq!(0.0, plan.action.target_velocity);
+            prop_assert_eq!(Mode::EmergencyStop, plan.mode);
+        }
+
+        #[test]
+        fn targets_are_registered_calls(
+            position in -200.0f32..200.0,
+            direction in direction(),
+            calls in proptest::collection::vec(-200i32..200, 0..16),
+            time_step in 0.001f32..10.0,
+        ) {
+            let readings = Readings { position, velocity: 0.0, emergency_stop: false };
+            let plan = plan(&LIMITS, direction, None, readings, calls.as_slice(), time_step);
+
+            match plan.target {
+                Some(target) => {
+                    prop_assert!(calls.contains(&target));
+                    prop_assert_eq!(Mode::Serving, plan.mode);
+                }
+                None => prop_assert_eq!(0.0, plan.action.target_velocity),
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_target_stops_at_next_floor() {
+        let readings = Readings {
+            position: 1.25,
+            velocity: 0.5,
+            emergency_stop: false,
+        };
+        let up = plan(&LIMITS, Direction::Up, Some(5), readings, &[][..], 0.1);
+        assert_eq!(Some(2), up.target);
+        assert_eq!(Mode::Serving, up.mode);
+        assert!(up.action.target_velocity > 0.0);
+
+        let readings = Readings {
+            velocity: -0.5,
+            ..readings
+        };
+        let down = plan(&LIMITS, Direction::Down, Some(-3), readings, &[][..], 0.1);
+        assert_eq!(Some(1), down.target);
+    }
+
+    #[test]
+    fn stays_between_floors_after_emergency_stop_release() {
+        // Calls dropped during the emergency stop leave the target of the stop behind
+        let readings = Readings {
+            position: 1.25,
+            velocity: 0.0,
+            emergency_stop: false,
+        };
+        let released = plan(&LIMITS, Direction::Up, Some(5), readings, &[][..], 0.1);
+        assert_eq!(None, released.target);
+        assert_eq!(Mode::Idle, released.mode);
+        assert_eq!(0.0, released.action.target_velocity);
+    }
+
+    #[test]
+    fn noisy_readings_without_target_stay_idle() {
+        for (position, velocity) in [(2.2, 0.0), (2.2, 0.3), (1.9, -0.3), (-0.4, 1.0)] {
+            let readings = Readings {
+                position,
+                velocity,
+                emergency_stop: false,
+            };
+            let idle = plan(&LIMITS, Direction::Up, None, readings, &[][..], 0.1);
+            assert_eq!(None, idle.target);
+            assert_eq!(Mode::Idle, idle.mode);
+            assert_eq!(0.0, idle.action.target_velocity);
+        }
+    }
+}
+
+#[cfg(kani)]
+mod verification {
+    use super::*;
+
+    #[kani::proof]
+    fn never_overshoots_with_bounded_time_step() {
+        let position: f32 = kani::any();
+        let target: i8 = kani::any();
+        let prefered_velocity: f32 = kani::any();
+        let time_step: f32 = kani::any();
+        kani::assume(position.is_finite() && position.abs() < 128.0);
+        kani::assume(prefered_velocity >= 0.0 && prefered_velocity <= 10.0);
+        kani::assume(time_step > 0.001 && time_step <= 1.0);
+
+        let velocity = target_velocity(position, target as Floor, prefered_velocity, time_step);
+        let distance = target as f32 - position;
+        let travelled = velocity * time_step;
+
+        assert!(travelled * distance >= 0.0);
+        assert!(travelled.abs() <= distance.abs() * (1.0 + 1e-5) + 1e-5);
+    }
+
+    #[kani::proof]
+    #[kani::unwind(5)]
+    fn emergency_stop_yields_zero_velocity() {
+        let calls: [i8; 4] = kani::any();
+        let calls = calls.map(Floor::from);
+        let readings = Readings {
+            position: kani::any(),
+            velocity: kani::any(),
+            emergency_stop: true,
+        };
+        let limits = Limits {
+            prefered_velocity: 1.0,
+            floor_leeway: 0.05,
+            velocity_epsilon: 0.01,
+            max_acceleration: None,
+        };
+
+        let plan = plan(&limits, Direction::Up, None, readings, &calls[..], 0.1);
+
+        assert!(plan.action.target_velocity == 0.0);
+        assert!(plan.mode == Mode::EmergencyStop);
+    }
+}
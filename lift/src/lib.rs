@@ -1,8 +1,64 @@
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(any(feature = "alloc", test))]
+extern crate alloc;
+
 #[allow(unused_imports)]
 use micromath::F32Ext;
 
+pub mod bacnet;
+mod bounded;
+mod calls;
+pub mod canopen;
+mod counters;
+mod crc;
+mod events;
+mod floor_map;
+pub mod fuzz;
+mod group;
+mod health;
+mod invariants;
+mod kernel;
+pub mod modbus;
+#[cfg(feature = "embedded-hal")]
+pub mod motor;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+mod observer;
+mod power;
+#[cfg(feature = "std")]
+pub mod prometheus;
+mod route;
+pub mod run;
+mod sensors;
+#[cfg(feature = "critical-section")]
+pub mod shared;
+mod signals;
+pub mod sim;
+mod state;
+pub mod telemetry;
+#[cfg(feature = "serde")]
+pub mod wire;
+
+pub use bounded::{BoundedLiftController, FloorOutOfRange, FloorSet};
+use calls::Calls;
+pub use calls::{CallQueue, CallQueueFull};
+pub use counters::{Counters, MaintenanceThresholds, ServiceDue};
+pub use events::{Event, EventKind, EventQueue, Timestamp};
+pub use floor_map::{FloorMap, FloorMapError};
+pub use group::GroupDispatcher;
+pub use health::{HealthIndicators, HealthSink};
+pub use invariants::InvariantViolation;
+pub use observer::LiftObserver;
+pub use power::{PowerState, SleepConfig};
+pub use route::Route;
+pub use sensors::{ClosureSensors, EncoderDerivative, FusedVelocity, Keyframe, ScriptedSensors};
+pub use signals::{Announcement, Chime, FloorIndicator, HallLantern};
+pub use state::{ControllerState, StateError, STATE_BLOB_SIZE};
+
 /// Position in normalised units, the distance between two floors in the elevator is '1.0'
 pub type Position = f32;
 
@@ -12,14 +68,32 @@ pub type Velocity = f32;
 /// Signed integer representaiton of actual floor
 pub type Floor = i32;
 
-#[derive(PartialEq)]
-enum Direction {
+/// Direction of travel for the lift
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
     Up,
     Down,
     Neutral,
 }
 
+/// Operating mode of the controller, as determined by the last poll
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// There are no floors to serve, the lift is waiting at the current floor
+    Idle,
+    /// The lift is travelling towards a target floor
+    Serving,
+    /// The emergency stop is activated and the lift is being held still
+    EmergencyStop,
+}
+
 /// The properties associated with a given lift
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LiftController {
     /// Prefered target velocity for the elevator
     prefered_velocity: Velocity,
@@ -31,8 +105,112 @@ pub struct LiftController {
     /// The allowed sensor difference in velocity. If a velocity is below this it is considered zero
     velocity_epsilon: Velocity,
 
+    /// Largest change in velocity per second, in floors/s². Unlimited when `None`
+    max_acceleration: Option<f32>,
+
     /// The current direction of the elevator
     direction: Direction,
+
+    /// The mode the controller was in after the last poll
+    mode: Mode,
+
+    /// The floor the controller was heading for after the last poll
+    target: Option<Floor>,
+
+    /// Operational counters, updated on every poll
+    counters: Counters,
+
+    /// Position reading from the last poll, used to accumulate the distance traveled
+    last_position: Option<Position>,
+
+    /// Counter limits after which the lift is due for service
+    maintenance_thresholds: MaintenanceThresholds,
+
+    /// Measurements behind the health indicators for the current reporting period
+    health: health::HealthMonitor,
+
+    /// The action returned by the last poll, checked by `check_invariants`
+    last_action: Option<Action>,
+
+    /// The arrival chime given by the last poll
+    chime: Option<Chime>,
+
+    /// The floor the arrival chime was given for since the lift last departed
+    chimed_floor: Option<Floor>,
+
+    /// The hall lantern lit at the landing the lift is stopping at
+    hall_lantern: Option<HallLantern>,
+
+    /// The floor shown by the position indicators
+    indicator: FloorIndicator,
+
+    /// Seconds idle without calls before standby, never when `None`
+    standby_delay: Option<f32>,
+
+    /// Seconds the lift has stood idle without calls
+    idle_time: f32,
+
+    /// When to go to sleep, never when `None`
+    sleep: Option<SleepConfig>,
+
+    power: PowerState,
+
+    /// Position reading when the lift went to sleep, moving away from it wakes the lift
+    asleep_at: Position,
+
+    /// Faults detected since creation or the last `clear_faults`
+    faults: Faults,
+}
+
+/// Faults the controller has detected on a poll. They stay latched until `LiftController::clear_faults`,
+/// such that a fault shows up in a snapshot even if it lasted a single poll
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Faults {
+    /// The position or velocity sensor gave a reading that is not a finite number
+    pub sensor_reading: bool,
+    /// The emergency stop was activated
+    pub emergency_stop: bool,
+}
+
+impl Faults {
+    pub const NONE: Faults = Faults {
+        sensor_reading: false,
+        emergency_stop: false,
+    };
+
+    /// If any fault is latched
+    pub fn any(&self) -> bool {
+        self.sensor_reading || self.emergency_stop
+    }
+}
+
+/// A point-in-time view of the controller state, intended for logging and diagnostics
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Snapshot {
+    /// The current direction of the elevator
+    pub direction: Direction,
+
+    /// The mode the controller was in after the last poll
+    pub mode: Mode,
+
+    /// The floor the controller was heading for after the last poll
+    pub target: Option<Floor>,
+
+    /// Configured prefered target velocity
+    pub prefered_velocity: Velocity,
+
+    /// Configured floor leeway
+    pub floor_leeway: Position,
+
+    /// Configured velocity epsilon
+    pub velocity_epsilon: Velocity,
+
+    /// Faults latched since the last `clear_faults`
+    pub faults: Faults,
 }
 
 /// Trait to be implemented by a Lift implementation representing different sensors.
@@ -53,6 +231,9 @@ pub trait LiftSensors {
 }
 
 /// Recommended action from the LiftController
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Action {
     /// Velocity to target
     pub target_velocity: Velocity,
@@ -80,75 +261,498 @@ impl LiftController {
             prefered_velocity,
             floor_leeway,
             velocity_epsilon,
+            max_acceleration: None,
             direction: Direction::Neutral,
+            mode: Mode::Idle,
+            target: None,
+            counters: Counters::new(),
+            last_position: None,
+            maintenance_thresholds: MaintenanceThresholds::none(),
+            health: health::HealthMonitor::new(),
+            last_action: None,
+            chime: None,
+            chimed_floor: None,
+            hall_lantern: None,
+            indicator: FloorIndicator::new(FloorIndicator::DEFAULT_HYSTERESIS),
+            standby_delay: None,
+            idle_time: 0.0,
+            sleep: None,
+            power: PowerState::Active,
+            asleep_at: 0.0,
+            faults: Faults::NONE,
+        }
+    }
+
+    /// Limit the acceleration and deceleration of the commanded velocity to `max_acceleration`
+    /// floors/s², or remove the limit with `None`. The emergency stop is never limited
+    pub fn set_max_acceleration(&mut self, max_acceleration: Option<f32>) {
+        self.max_acceleration = max_acceleration;
+    }
+
+    pub fn max_acceleration(&self) -> Option<f32> {
+        self.max_acceleration
+    }
+
+    /// Operational counters accumulated since creation or the last reset
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    /// Reset all operational counters to zero, typically done after the lift has been serviced
+    pub fn reset_counters(&mut self) {
+        self.counters = Counters::new();
+    }
+
+    /// Count a door cycle, the doors opening and closing again at a stop. The controller does not drive the
+    /// doors, so the host reports each cycle for the door cycle counter and its maintenance threshold
+    pub fn record_door_cycle(&mut self) {
+        self.counters.door_cycles = self.counters.door_cycles.saturating_add(1);
+    }
+
+    /// Set the counter limits after which the lift is due for service
+    pub fn set_maintenance_thresholds(&mut self, thresholds: MaintenanceThresholds) {
+        self.maintenance_thresholds = thresholds;
+    }
+
+    /// Which operational counters have reached their maintenance threshold
+    pub fn service_due(&self) -> ServiceDue {
+        self.counters.service_due(&self.maintenance_thresholds)
+    }
+
+    /// The doors re-opened before closing fully, for instance on an obstructed door edge or the door open button.
+    /// The controller does not drive the doors, so the host reports each re-open for the re-open frequency
+    pub fn record_door_reopen(&mut self) {
+        self.health.record_door_reopen();
+    }
+
+    /// Report the health indicators derived since the last call to the sink, and start a new reporting period.
+    /// This is meant to be called periodically by the host, for instance once per hour
+    pub fn feed_health(&mut self, sink: &mut dyn HealthSink) {
+        sink.report(&self.health.finish_period());
+    }
+
+    /// Take a snapshot of the current controller state
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            direction: self.direction,
+            mode: self.mode,
+            target: self.target,
+            prefered_velocity: self.prefered_velocity,
+            floor_leeway: self.floor_leeway,
+            velocity_epsilon: self.velocity_epsilon,
+            faults: self.faults,
+        }
+    }
+
+    /// Faults latched since creation or the last `clear_faults`
+    pub fn faults(&self) -> Faults {
+        self.faults
+    }
+
+    /// Clear the latched faults, typically once the cause has been dealt with. A fault still present is
+    /// latched again on the next poll
+    pub fn clear_faults(&mut self) {
+        self.faults = Faults::NONE;
+    }
+
+    /// Save the persistable part of the controller state
+    pub fn save_state(&self) -> ControllerState {
+        ControllerState {
+            direction: self.direction,
+            mode: self.mode,
+            faults: self.faults,
+            leveling_error: self.health.leveling_error(),
         }
     }
 
+    /// Restore a previously saved controller state
+    ///
+    /// The target floor is not restored as it is recalculated from the sensors on the next poll
+    pub fn restore_state(&mut self, state: ControllerState) {
+        self.direction = state.direction;
+        self.mode = state.mode;
+        self.target = None;
+        self.faults = state.faults;
+        self.health.set_leveling_error(state.leveling_error);
+    }
+
+    /// Restore the controller state from a blob created by `ControllerState::to_bytes`
+    ///
+    /// If the blob is corrupt or of an unknown version the controller falls back to the default state,
+    /// the error is returned such that the host may report it
+    pub fn restore_state_from_bytes(&mut self, bytes: &[u8]) -> Result<(), StateError> {
+        let result = ControllerState::from_bytes(bytes);
+        self.restore_state(result.unwrap_or_default());
+        result.map(|_| ())
+    }
+
+    /// Check that the state after the last poll is consistent with the sensor readings.
+    /// Meant for debug builds, fuzzing and hardware-in-the-loop rigs, call it right after `poll`
+    pub fn check_invariants(&self, sensors: &dyn LiftSensors) -> Result<(), InvariantViolation> {
+        invariants::check(self, sensors)
+    }
+
     /// From sensor data, poll for the next action to perform
     pub fn poll(&mut self, sensors: &dyn LiftSensors, time_step: f32) -> Action {
-        let is_stopped = sensors.current_velocity().abs() < self.velocity_epsilon;
-        let can_stop_at_floor = self.can_stop_at_floor(sensors);
-        let is_stopped_at_current_floor = is_stopped && can_stop_at_floor.is_some();
+        self.poll_with_observer(sensors, time_step, &mut ())
+    }
 
-        // If the emergency step sensor is active this should take the absolutely highest proprity
-        if sensors.is_emergency_stop_activated() {
-            return Action {
+    /// Same as `poll`, but the observer is notified of any transitions caused by the poll
+    pub fn poll_with_observer(
+        &mut self,
+        sensors: &dyn LiftSensors,
+        time_step: f32,
+        observer: &mut dyn LiftObserver,
+    ) -> Action {
+        self.poll_calls(sensors, sensors.floors_to_stop_at(), time_step, observer)
+    }
+
+    /// Same as `poll`, but plans with the given call queue instead of `LiftSensors::floors_to_stop_at`.
+    /// The queue is sorted, which makes the nearest-floor lookups binary searches rather than linear scans
+    pub fn poll_with_calls<const N: usize>(
+        &mut self,
+        sensors: &dyn LiftSensors,
+        calls: &CallQueue<N>,
+        time_step: f32,
+    ) -> Action {
+        self.poll_calls(sensors, calls, time_step, &mut ())
+    }
+
+    /// Poll using the given calls rather than the floors reported by the sensors
+    pub(crate) fn poll_calls<C: Calls + ?Sized>(
+        &mut self,
+        sensors: &dyn LiftSensors,
+        calls: &C,
+        time_step: f32,
+        observer: &mut dyn LiftObserver,
+    ) -> Action {
+        if self.power == PowerState::Asleep && self.is_woken(sensors, calls) {
+            let rehome = self.sleep.is_some_and(|sleep| sleep.rehome);
+            self.power = if rehome {
+                PowerState::Waking
+            } else {
+                PowerState::Active
+            };
+            self.idle_time = 0.0;
+        }
+        if self.power == PowerState::Waking {
+            // Hold the car where it slept until it has been re-homed
+            let hold = Action {
                 target_velocity: 0.0,
-                is_stopped_at_current_floor,
+                is_stopped_at_current_floor: true,
             };
+            self.last_action = Some(hold);
+            return hold;
         }
 
-        if let (direction, Some(next_target_floor)) = next_target_floor(
-            &self.direction,
-            sensors.current_floor(),
-            self.floor_leeway,
-            sensors.floors_to_stop_at(),
-        ) {
-            // A target floor is set
-
-            /*
-            If a non-neutral direction is given change the direction.
-            In this case we treat Neutral as 'go the same direction'
-            */
-            if direction != Direction::Neutral {
-                self.direction = direction;
+        let previous = self.snapshot();
+        let action = self.next_action(sensors, calls, time_step);
+        self.last_action = Some(action);
+        let current = self.snapshot();
+        let position = sensors.current_floor();
+        let floor = position.round() as Floor;
+
+        // A reading that is not a number is latched as a fault, it would poison the distance for good
+        if position.is_finite() {
+            if let Some(last_position) = self.last_position {
+                self.counters.add_distance(last_position, position);
             }
+            self.last_position = Some(position);
+        }
+        self.health.update(
+            &previous,
+            &current,
+            position,
+            sensors.current_velocity(),
+            time_step,
+        );
+
+        #[cfg(feature = "log")]
+        let mut logger = observer::Logger {
+            floor,
+            direction: current.direction,
+        };
+        observer::notify_transitions(&previous, &current, floor, &mut self.counters);
+        #[cfg(feature = "log")]
+        observer::notify_transitions(&previous, &current, floor, &mut logger);
+        observer::notify_transitions(&previous, &current, floor, observer);
+
+        self.chime = self.arrival_chime(&previous, sensors, &action, calls);
+        if let Some(chime) = self.chime {
+            #[cfg(feature = "log")]
+            logger.on_chime(chime.floor, chime.direction);
+            observer.on_chime(chime.floor, chime.direction);
+        }
+        self.update_hall_lantern(position);
+        #[cfg(feature = "log")]
+        self.announce(&previous, floor, &mut logger);
+        self.announce(&previous, floor, observer);
+        let levelling = sensors.current_velocity().abs() < self.velocity_epsilon;
+        self.indicator.update(position, levelling);
+        let quiet = self.mode == Mode::Idle && levelling && calls.nearest_floor(floor).is_none();
+        self.idle_time = if quiet {
+            self.idle_time + time_step
+        } else {
+            0.0
+        };
+        self.update_power_state(position);
+
+        action
+    }
+
+    fn update_power_state(&mut self, position: Position) {
+        let sleepy = self
+            .sleep
+            .is_some_and(|sleep| self.idle_time >= sleep.delay);
+        self.power = if sleepy {
+            if self.power != PowerState::Asleep {
+                self.asleep_at = position;
+            }
+            PowerState::Asleep
+        } else if self.is_standby() {
+            PowerState::Standby
+        } else {
+            PowerState::Active
+        };
+    }
+
+    /// A call, motion, the emergency stop or a position reading away from where the lift went to sleep
+    fn is_woken<C: Calls + ?Sized>(&self, sensors: &dyn LiftSensors, calls: &C) -> bool {
+        let position = sensors.current_floor();
+        // Compared this way round so readings that are not numbers wake the lift too
+        let still = sensors.current_velocity().abs() < self.velocity_epsilon;
+        let in_place = (position - self.asleep_at).abs() < self.floor_leeway;
+        calls.nearest_floor(position.round() as Floor).is_some()
+            || !still
+            || !in_place
+            || sensors.is_emergency_stop_activated()
+    }
+
+    /// Go to sleep once the lift has stood idle without calls for the configured delay, or never with `None`
+    pub fn set_sleep(&mut self, sleep: Option<SleepConfig>) {
+        self.sleep = sleep;
+    }
+
+    pub fn sleep(&self) -> Option<SleepConfig> {
+        self.sleep
+    }
 
-            /*
-            We take special consideration here when calculating the target velocity.
-            If the time_step is too high we need to make sure we don't overshoot the floor.
-            */
-            let signed_distance = next_target_floor as f32 - sensors.current_floor();
+    /// The power state after the last poll
+    pub fn power_state(&self) -> PowerState {
+        self.power
+    }
+
+    /// Seconds the host may wait between polls, the poll interval while asleep and `None` otherwise,
+    /// meaning the host should poll at its normal rate
+    pub fn poll_interval(&self) -> Option<f32> {
+        match (self.power, self.sleep) {
+            (PowerState::Asleep, Some(sleep)) => Some(sleep.poll_interval),
+            _ => None,
+        }
+    }
+
+    /// The host has re-homed the car after waking up, the controller serves calls again from the next poll
+    pub fn finish_waking(&mut self) {
+        if self.power == PowerState::Waking {
+            self.power = PowerState::Active;
+        }
+    }
+
+    /// Assert standby once the lift has stood idle without calls for `delay` seconds, or never with `None`
+    pub fn set_standby_delay(&mut self, delay: Option<f32>) {
+        self.standby_delay = delay;
+    }
+
+    pub fn standby_delay(&self) -> Option<f32> {
+        self.standby_delay
+    }
+
+    /// The lift has stood idle without calls for the standby delay, and the host may switch off the car
+    /// lighting and ventilation. Clears on the first poll with a call, motion or an emergency stop
+    pub fn is_standby(&self) -> bool {
+        self.standby_delay
+            .is_some_and(|delay| self.idle_time >= delay)
+    }
 
-            let exact_target_velocity = (signed_distance / time_step).abs();
+    /// The floor for the position indicators to show, updated on every poll and nothing before the first.
+    /// See `FloorIndicator` for when it changes, the lift counts as levelling while slower than the velocity epsilon
+    pub fn displayed_floor(&self) -> Option<Floor> {
+        self.indicator.floor()
+    }
+
+    /// Distance past the midpoint between two floors, in floors, before `displayed_floor` switches
+    pub fn set_display_hysteresis(&mut self, hysteresis: Position) {
+        self.indicator.set_hysteresis(hysteresis);
+    }
 
-            let target_velocity =
-                f32::min(self.prefered_velocity, exact_target_velocity).copysign(signed_distance);
+    /// The arrival chime given by the last poll, for hosts driving the gong from the controller outputs.
+    /// It is given once per stop, as the lift starts braking for the floor or at the latest when it arrives
+    pub fn chime(&self) -> Option<Chime> {
+        self.chime
+    }
 
-            Action {
-                target_velocity,
-                is_stopped_at_current_floor: false,
+    /// Tell the observer what a voice annunciator should say after the last poll
+    fn announce(&self, previous: &Snapshot, floor: Floor, observer: &mut dyn LiftObserver) {
+        let departing = previous.mode == Mode::Idle && self.mode == Mode::Serving;
+        if departing {
+            observer.on_announcement(Announcement::DoorsClosing { floor });
+        }
+        if let (Mode::Serving, Some(target)) = (self.mode, self.target) {
+            if departing || previous.target != Some(target) {
+                observer.on_announcement(Announcement::NextStop { floor: target });
             }
-        } else {
-            // No target floor is set, we can simply wait at the current floor
-            Action {
-                target_velocity: 0.0,
-                is_stopped_at_current_floor: true,
+        }
+        if let Some(chime) = self.chime {
+            observer.on_announcement(Announcement::Arriving {
+                floor: chime.floor,
+                direction: chime.direction,
+            });
+        }
+        if previous.mode == Mode::Serving && self.mode == Mode::Idle {
+            let direction = match self.hall_lantern {
+                Some(lantern) if lantern.floor == floor => lantern.direction,
+                _ => previous.direction,
+            };
+            observer.on_announcement(Announcement::DoorsOpening { floor, direction });
+        }
+    }
+
+    /// The hall lantern to light, at the landing the lift has committed to stopping at and pointing the way it
+    /// will leave. It lights together with the chime and goes out once the lift has left the landing
+    pub fn hall_lantern(&self) -> Option<HallLantern> {
+        self.hall_lantern
+    }
+
+    /// Same as `hall_lantern` for every landing from `lowest_floor` and up, one per entry of `lanterns`,
+    /// for hosts with an output per landing. At most one entry is set
+    pub fn hall_lanterns(&self, lowest_floor: Floor, lanterns: &mut [Option<Direction>]) {
+        for (floor, lantern) in (lowest_floor..).zip(lanterns.iter_mut()) {
+            *lantern = self
+                .hall_lantern
+                .filter(|lit| lit.floor == floor)
+                .map(|lit| lit.direction);
+        }
+    }
+
+    fn update_hall_lantern(&mut self, position: Position) {
+        if let Some(chime) = self.chime {
+            self.hall_lantern = Some(HallLantern {
+                floor: chime.floor,
+                direction: chime.direction,
+            });
+        } else if let Some(lantern) = self.hall_lantern {
+            let left = self.target != Some(lantern.floor)
+                && (lantern.floor as Position - position).abs() > self.floor_leeway;
+            if left {
+                self.hall_lantern = None;
             }
         }
     }
 
-    /// Check if it is possible to stop currently
-    /// Returns Some(Floor) if it is possible to stop, and None if it is impossible
-    fn can_stop_at_floor(&self, sensors: &dyn LiftSensors) -> Option<Floor> {
-        let nearest_floor = sensors.current_floor().round();
-        let floor_distance = (sensors.current_floor() - nearest_floor).abs();
+    /// The chime for the stop the lift has committed to with the last poll, unless it was already given
+    fn arrival_chime<C: Calls + ?Sized>(
+        &mut self,
+        previous: &Snapshot,
+        sensors: &dyn LiftSensors,
+        action: &Action,
+        calls: &C,
+    ) -> Option<Chime> {
+        let position = sensors.current_floor();
+        let velocity = sensors.current_velocity();
+        if previous.mode == Mode::Idle && self.mode == Mode::Serving {
+            self.chimed_floor = None;
+        }
 
-        if floor_distance < self.floor_leeway {
-            Some(nearest_floor as Floor)
-        } else {
-            None
+        let (floor, arriving) = match (previous.mode, self.mode, self.target) {
+            // Braking towards the target
+            (_, Mode::Serving, Some(target))
+                if (target as Position - position) * velocity > 0.0
+                    && action.target_velocity.abs() < velocity.abs() - self.velocity_epsilon =>
+            {
+                let arriving = if velocity > 0.0 {
+                    Direction::Up
+                } else {
+                    Direction::Down
+                };
+                (target, arriving)
+            }
+            // Arrived without braking first, such as with long time steps
+            (Mode::Serving, Mode::Idle, _) => (position.round() as Floor, previous.direction),
+            _ => return None,
+        };
+        if self.chimed_floor == Some(floor) {
+            return None;
         }
+
+        self.chimed_floor = Some(floor);
+        Some(Chime {
+            floor,
+            direction: signals::onward_direction(floor, arriving, calls),
+        })
+    }
+
+    /// Same as `poll`, but any transitions caused by the poll are pushed to the queue as events stamped with `timestamp`
+    pub fn poll_with_events<const N: usize>(
+        &mut self,
+        sensors: &dyn LiftSensors,
+        time_step: f32,
+        timestamp: Timestamp,
+        queue: &mut EventQueue<N>,
+    ) -> Action {
+        self.poll_with_observer(
+            sensors,
+            time_step,
+            &mut events::Stamped { timestamp, queue },
+        )
+    }
+
+    /// Decide on the next action and update the controller state accordingly
+    fn next_action<C: Calls + ?Sized>(
+        &mut self,
+        sensors: &dyn LiftSensors,
+        calls: &C,
+        time_step: f32,
+    ) -> Action {
+        let limits = kernel::Limits {
+            prefered_velocity: self.prefered_velocity,
+            floor_leeway: self.floor_leeway,
+            velocity_epsilon: self.velocity_epsilon,
+            max_acceleration: self.max_acceleration,
+        };
+        let readings = kernel::Readings {
+            position: sensors.current_floor(),
+            velocity: sensors.current_velocity(),
+            emergency_stop: sensors.is_emergency_stop_activated(),
+        };
+        if !(readings.position.is_finite() && readings.velocity.is_finite()) {
+            self.faults.sensor_reading = true;
+        }
+        if readings.emergency_stop {
+            self.faults.emergency_stop = true;
+        }
+
+        let plan = kernel::plan(
+            &limits,
+            self.direction,
+            self.target,
+            readings,
+            calls,
+            time_step,
+        );
+        self.direction = plan.direction;
+        self.mode = plan.mode;
+        self.target = plan.target;
+        plan.action
+    }
+
+    /// The floors of `floors_to_stop_at` in the order they will be served, given the current position.
+    /// The returned iterator borrows the slice and does not allocate
+    pub fn planned_route<'a>(
+        &self,
+        current_floor: Position,
+        floors_to_stop_at: &'a [Floor],
+    ) -> Route<'a> {
+        Route::new(floors_to_stop_at, current_floor, self.direction)
     }
 
     pub fn time_to_floor(
@@ -157,15 +761,44 @@ impl LiftController {
         floor: Floor,
         average_stop: f32,
     ) -> Option<f32> {
+        self.eta(
+            sensors.current_floor(),
+            sensors.current_velocity().abs(),
+            sensors.floors_to_stop_at(),
+            floor,
+            average_stop,
+        )
+    }
+
+    /// Same as `time_to_floor` for every floor from `lowest_floor` and up, one per entry of `etas`.
+    /// The sensors are only read once, which makes this cheaper than asking floor by floor
+    pub fn times_to_floors(
+        &self,
+        sensors: &dyn LiftSensors,
+        lowest_floor: Floor,
+        average_stop: f32,
+        etas: &mut [Option<f32>],
+    ) {
         let current_floor = sensors.current_floor();
         let speed = sensors.current_velocity().abs();
+        let floors = sensors.floors_to_stop_at();
+        for (floor, eta) in (lowest_floor..).zip(etas.iter_mut()) {
+            *eta = self.eta(current_floor, speed, floors, floor, average_stop);
+        }
+    }
 
+    fn eta(
+        &self,
+        current_floor: Position,
+        speed: Velocity,
+        floors: &[Floor],
+        floor: Floor,
+        average_stop: f32,
+    ) -> Option<f32> {
         if speed < self.velocity_epsilon {
             return None;
         }
 
-        let floors = sensors.floors_to_stop_at();
-
         let target = floor as f32;
 
         let highest_floor: f32 = floors.iter().max().map(|f| *f as f32).unwrap_or(0f32);
@@ -179,7 +812,8 @@ impl LiftController {
                     .copied()
                     .map(|f| f as f32)
                     .filter(|f| *f > current_floor && *f < target)
-                    .count() as f32 * average_stop;
+                    .count() as f32
+                    * average_stop;
 
                 let distance = target - current_floor;
 
@@ -191,14 +825,16 @@ impl LiftController {
                     .copied()
                     .map(|f| f as f32)
                     .filter(|f| *f > current_floor && *f < highest_floor)
-                    .count() as f32 * average_stop;
+                    .count() as f32
+                    * average_stop;
 
                 let below: f32 = floors
                     .iter()
                     .copied()
                     .map(|f| f as f32)
                     .filter(|f| *f < current_floor && *f > target)
-                    .count() as f32 * average_stop;
+                    .count() as f32
+                    * average_stop;
 
                 let distance = highest_floor - current_floor + highest_floor - target;
 
@@ -210,14 +846,16 @@ impl LiftController {
                     .copied()
                     .map(|f| f as f32)
                     .filter(|f| *f > current_floor && *f < target)
-                    .count() as f32 * average_stop;
+                    .count() as f32
+                    * average_stop;
 
                 let below: f32 = floors
                     .iter()
                     .copied()
                     .map(|f| f as f32)
                     .filter(|f| *f < current_floor && *f > lowest_floor)
-                    .count() as f32 * average_stop;
+                    .count() as f32
+                    * average_stop;
 
                 let distance = current_floor - lowest_floor + target - lowest_floor;
 
@@ -239,108 +877,15 @@ impl LiftController {
     }
 }
 
-/// Find the next target floor and the direction to it
-fn next_target_floor(
-    direction: &Direction,
-    current_floor: Position,
-    floor_leeway: Position,
-    floors: &[Floor],
-) -> (Direction, Option<Floor>) {
-    /*
-    Find the nearest floor. If there is no floor in the current direction, try to look in the other direction.
-    This strategy of priorizing the current direction is important to reduce (acutally make bounds on)
-    the worst case pickup time of for any passengers. With this strategy we can ensure that for a building
-    that is N stories tall the lift will make no more than (N - 1) stops before picking up a passenger,
-    and likewise will make at most (N - 1) stops before dropping them off.
-    */
-    let target_floor = match direction {
-        Direction::Up => nearest_floor_above(current_floor.round() as i32, floors)
-            .or_else(|| nearest_floor_below(current_floor.round() as i32, floors)),
-        Direction::Down => nearest_floor_below(current_floor.round() as i32, floors)
-            .or_else(|| nearest_floor_above(current_floor.round() as i32, floors)),
-        Direction::Neutral => nearest_floor(current_floor.round() as i32, floors),
-    }
-    /*
-    We filter away the current floor from the consideration, this may not be strictly
-    necessary, but since we have made the Lift implementation generic we can't make
-    any assumptions about when the floor-list is cleared
-    */
-    .filter(|floor| (*floor as f32 - current_floor).abs() > floor_leeway);
-
-    match target_floor {
-        Some(target_floor) => {
-            let direction_delta = target_floor - current_floor.round() as i32;
-            let direction = match direction_delta {
-                1..=i32::MAX => Direction::Up,
-                0 => Direction::Neutral,
-                i32::MIN..=-1 => Direction::Down,
-            };
-
-            (direction, Some(target_floor))
-        }
-        None => (Direction::Neutral, None),
-    }
-}
-
-fn nearest_floor_above(current_floor: Floor, floors_to_stop_at: &[Floor]) -> Option<Floor> {
-    floors_to_stop_at
-        .iter()
-        .filter(|floor| **floor >= current_floor)
-        .min_by_key(|floor| **floor - current_floor)
-        .copied()
-}
-
-fn nearest_floor_below(current_floor: Floor, floors_to_stop_at: &[Floor]) -> Option<Floor> {
-    floors_to_stop_at
-        .iter()
-        .filter(|floor| **floor <= current_floor)
-        .min_by_key(|floor| current_floor - **floor)
-        .copied()
-}
-
-fn nearest_floor(current_floor: Floor, floors_to_stop_at: &[Floor]) -> Option<Floor> {
-    floors_to_stop_at
-        .iter()
-        .min_by_key(|floor| (current_floor - **floor).abs())
-        .copied()
-}
-
 #[cfg(test)]
 mod tests {
 
     extern crate std;
 
     use super::*;
-    use std::{fmt, println, vec::Vec};
-
-    #[derive(Debug)]
-    struct TestLift {
-        position: Position,
-        velocity: Velocity,
-        floors_to_stop_at: Vec<Floor>,
-        is_emergency_stop_activated: bool,
-    }
-
-    impl fmt::Debug for LiftController {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            f.debug_struct("LiftController")
-                .field("prefered_velocity", &self.prefered_velocity)
-                .field("floor_leeway", &self.floor_leeway)
-                .field("velocity_epsilon", &self.velocity_epsilon)
-                .field("direction", &self.direction)
-                .finish()
-        }
-    }
-
-    impl fmt::Debug for Direction {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            match &self {
-                Direction::Down => f.write_str("Down"),
-                Direction::Up => f.write_str("Up"),
-                Direction::Neutral => f.write_str("Neutral"),
-            }
-        }
-    }
+    use crate::sim::SimulatedLift;
+    use core::future::Future;
+    use std::{println, vec, vec::Vec};
 
     /// Changes the scale of a decimal number to have the given number of decimals
     /// Useful is small floating point errors lead to failing tests
@@ -349,120 +894,129 @@ mod tests {
         (number * factor).round() / factor
     }
 
-    fn find<T: PartialEq>(elem: &T, slice: &[T]) -> Option<usize> {
-        slice
-            .iter()
-            .enumerate()
-            .find(|(_, e)| *e == elem)
-            .map(|(i, _)| i)
-    }
+    #[test]
+    fn go_to_tenth_floor() {
+        let mut lift = SimulatedLift::new(0.0);
 
-    impl TestLift {
-        fn new() -> Self {
-            TestLift {
-                position: 0.0,
-                velocity: 0.0,
-                floors_to_stop_at: Vec::new(),
-                is_emergency_stop_activated: false,
-            }
-        }
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
 
-        fn stop_at_floor(&mut self, floor: Floor) {
-            if let None = find(&floor, &self.floors_to_stop_at()) {
-                self.floors_to_stop_at.push(floor);
-            }
-        }
+        let time = 20f32;
+        let time_step = 0.1f32;
+        let steps = (time / time_step) as i32;
 
-        fn remove_floor_from_panel(&mut self, floor: Floor) {
-            if let Some(i) = find(&floor, &self.floors_to_stop_at()) {
-                self.floors_to_stop_at.remove(i);
-            }
-        }
+        lift.register_call(10);
 
-        fn accept_action(&mut self, action: Action, time_step: f32) {
-            self.position += action.target_velocity * time_step;
-            self.velocity = action.target_velocity;
-            if action.is_stopped_at_current_floor {
-                self.remove_floor_from_panel(self.position.round() as i32);
-            }
+        for step in 0..steps {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+            println!(
+                "{: >2.1} {: >2.2} {: >2.2}",
+                (step as f32 * time_step),
+                lift.position(),
+                lift.velocity()
+            );
         }
-    }
 
-    impl LiftSensors for TestLift {
-        fn current_floor(&self) -> Position {
-            self.position
-        }
+        assert_eq!(lift.current_floor(), 10f32);
+    }
 
-        fn current_velocity(&self) -> Velocity {
-            self.velocity
-        }
+    #[test]
+    fn acceleration_is_limited() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(1.0, 0.001, 0.001);
+        controller.set_max_acceleration(Some(0.5));
+        let time_step = 0.1f32;
 
-        fn floors_to_stop_at(&self) -> &[Floor] {
-            self.floors_to_stop_at.as_slice()
+        lift.register_call(4);
+        let mut last_velocity = 0.0;
+        for _ in 0..100 {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+            assert!((lift.velocity() - last_velocity).abs() <= 0.05 + 1e-5);
+            last_velocity = lift.velocity();
         }
 
-        fn is_emergency_stop_activated(&self) -> bool {
-            self.is_emergency_stop_activated
-        }
+        // Stopped within the leeway of the floor
+        assert_eq!(0.0, lift.velocity());
+        assert!((4.0 - lift.current_floor()).abs() < 0.001);
     }
 
     #[test]
-    fn go_to_tenth_floor() {
-        let mut lift = TestLift::new();
-
+    fn cancelled_call_stops_at_next_floor() {
+        let mut lift = SimulatedLift::new(0.0);
         let mut controller = LiftController::new(0.5, 0.001, 0.001);
-
-        let time = 20f32;
         let time_step = 0.1f32;
-        let steps = (time / time_step) as i32;
 
-        lift.stop_at_floor(10);
+        lift.register_call(5);
+        for _ in 0..25 {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+        }
+        assert_eq!(1.25, scale(lift.position(), 4));
 
-        for step in 0..steps {
+        lift.cancel_call(5);
+        for _ in 0..20 {
             let action = controller.poll(&lift, time_step);
             lift.accept_action(action, time_step);
-            println!(
-                "{: >2.1} {: >2.2} {: >2.2}",
-                (step as f32 * time_step),
-                lift.position,
-                lift.velocity
-            );
         }
+        assert_eq!(2.0, scale(lift.current_floor(), 4));
+        assert!(
+            controller
+                .poll(&lift, time_step)
+                .is_stopped_at_current_floor
+        );
+    }
 
-        assert_eq!(lift.current_floor(), 10f32);
+    #[test]
+    fn batch_etas_match_single_floor_etas() {
+        let mut lift = SimulatedLift::new(2.0);
+        let mut controller = LiftController::new(1.0, 0.01, 0.01);
+        lift.register_call(6);
+        lift.register_call(0);
+        for _ in 0..5 {
+            let action = controller.poll(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+        }
+
+        let mut etas = [None; 10];
+        controller.times_to_floors(&lift, -1, 5.0, &mut etas);
+        for (floor, eta) in (-1..).zip(etas.iter()) {
+            assert_eq!(controller.time_to_floor(&lift, floor, 5.0), *eta);
+        }
+        assert!(etas.iter().all(Option::is_some));
     }
 
     #[test]
     fn switch_direction() {
-        let mut lift = TestLift::new();
+        let mut lift = SimulatedLift::new(0.0);
 
         let mut controller = LiftController::new(0.5, 0.001, 0.001);
         let time = 20f32;
         let time_step = 0.1f32;
         let steps = (time / time_step) as i32;
 
-        lift.stop_at_floor(5);
+        lift.register_call(5);
         for _ in 0..steps {
             let action = controller.poll(&lift, time_step);
             if action.is_stopped_at_current_floor {
                 println!("Goal is floor 5:\n{:#?}\n{:#?}", lift, controller);
                 assert_eq!(5.0, lift.current_floor());
-                lift.remove_floor_from_panel(5);
+                lift.cancel_call(5);
                 break;
             }
             lift.accept_action(action, time_step);
         }
 
-        lift.stop_at_floor(0);
-        lift.stop_at_floor(1);
-        lift.stop_at_floor(10);
+        lift.register_call(0);
+        lift.register_call(1);
+        lift.register_call(10);
 
         for _ in 0..steps {
             let action = controller.poll(&lift, time_step);
             if action.is_stopped_at_current_floor {
                 println!("Goal is floor 10:\n{:#?}\n{:#?}", lift, controller);
                 assert_eq!(10.0, lift.current_floor());
-                lift.remove_floor_from_panel(10);
+                lift.cancel_call(10);
                 break;
             }
             lift.accept_action(action, time_step);
@@ -473,7 +1027,7 @@ mod tests {
             if action.is_stopped_at_current_floor {
                 println!("Goal is floor 1:\n{:#?}\n{:#?}", lift, controller);
                 assert_eq!(1.0, lift.current_floor());
-                lift.remove_floor_from_panel(1);
+                lift.cancel_call(1);
                 break;
             }
             lift.accept_action(action, time_step);
@@ -484,7 +1038,7 @@ mod tests {
             if action.is_stopped_at_current_floor {
                 println!("Goal is floor 0:\n{:#?}\n{:#?}", lift, controller);
                 assert_eq!(0.0, lift.current_floor());
-                lift.remove_floor_from_panel(0);
+                lift.cancel_call(0);
                 break;
             }
             lift.accept_action(action, time_step);
@@ -493,7 +1047,7 @@ mod tests {
 
     #[test]
     fn emergency_stop() {
-        let mut lift = TestLift::new();
+        let mut lift = SimulatedLift::new(0.0);
 
         let velocity = 0.5f32;
         let mut controller = LiftController::new(velocity, 0.001, 0.001);
@@ -502,10 +1056,10 @@ mod tests {
         let steps = (time / time_step) as i32;
         let time_to_emergency = 20f32;
 
-        lift.stop_at_floor(9000);
+        lift.register_call(9000);
         for i in 0..steps {
             if i as f32 * time_step >= time_to_emergency {
-                lift.is_emergency_stop_activated = true
+                lift.set_emergency_stop(true)
             }
 
             let action = controller.poll(&lift, time_step);
@@ -514,4 +1068,611 @@ mod tests {
 
         assert_eq!(velocity * time_to_emergency, scale(lift.current_floor(), 4));
     }
+
+    #[test]
+    fn snapshot_follows_poll() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+
+        let snapshot = controller.snapshot();
+        assert_eq!(Mode::Idle, snapshot.mode);
+        assert_eq!(Direction::Neutral, snapshot.direction);
+        assert_eq!(None, snapshot.target);
+
+        lift.register_call(-2);
+        controller.poll(&lift, 0.1);
+        let snapshot = controller.snapshot();
+        assert_eq!(Mode::Serving, snapshot.mode);
+        assert_eq!(Direction::Down, snapshot.direction);
+        assert_eq!(Some(-2), snapshot.target);
+
+        assert_eq!(Faults::NONE, snapshot.faults);
+
+        lift.set_emergency_stop(true);
+        controller.poll(&lift, 0.1);
+        assert_eq!(Mode::EmergencyStop, controller.snapshot().mode);
+
+        // The fault stays latched after the emergency stop is released, until it is cleared
+        lift.set_emergency_stop(false);
+        controller.poll(&lift, 0.1);
+        assert!(controller.snapshot().faults.emergency_stop);
+        controller.clear_faults();
+        assert!(!controller.snapshot().faults.any());
+    }
+
+    #[test]
+    fn restored_state_keeps_direction() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        let time_step = 0.1f32;
+
+        // Travel upwards past floor 2 with calls both above and below
+        lift.register_call(5);
+        for _ in 0..30 {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+        }
+        lift.register_call(0);
+        let state = controller.save_state();
+        assert_eq!(Direction::Up, state.direction);
+
+        // A fresh controller would go for the nearest floor, the restored one keeps going up
+        let mut restored = LiftController::new(0.5, 0.001, 0.001);
+        restored.restore_state(state);
+        let action = restored.poll(&lift, time_step);
+        assert!(action.target_velocity > 0.0);
+        assert_eq!(Some(5), restored.snapshot().target);
+    }
+
+    #[test]
+    fn call_queue_picks_the_same_target_as_the_sensors() {
+        let mut lift = SimulatedLift::new(2.0);
+        lift.register_call(3);
+        lift.register_call(1);
+        let mut calls = CallQueue::<4>::new();
+        calls.insert(3).unwrap();
+        calls.insert(1).unwrap();
+
+        let mut from_sensors = LiftController::new(0.5, 0.001, 0.001);
+        let mut from_queue = from_sensors.clone();
+        from_sensors.poll(&lift, 0.1);
+        from_queue.poll_with_calls(&lift, &calls, 0.1);
+        assert_eq!(Some(1), from_sensors.snapshot().target);
+        assert_eq!(from_sensors.snapshot(), from_queue.snapshot());
+    }
+
+    #[test]
+    fn unreadable_state_restores_default() {
+        let saved = ControllerState {
+            direction: Direction::Up,
+            mode: Mode::Serving,
+            faults: Faults {
+                sensor_reading: true,
+                emergency_stop: false,
+            },
+            leveling_error: Some(0.01),
+        };
+        // A blob of the first layout, magic, version, direction, mode, reserved and checksum,
+        // read from a slot sized for the current layout with the rest erased
+        let mut version_1 = [0xFF; STATE_BLOB_SIZE];
+        version_1[..6].copy_from_slice(&[b'L', b'C', 1, 1, 1, 0]);
+        let crc = crc::crc16(&version_1[..6]);
+        version_1[6..8].copy_from_slice(&crc.to_le_bytes());
+        let mut invalid_direction = saved.to_bytes();
+        invalid_direction[3] = 7;
+        let crc = crc::crc16(&invalid_direction[..10]);
+        invalid_direction[10..12].copy_from_slice(&crc.to_le_bytes());
+
+        let blobs: [(&[u8], StateError); 3] = [
+            (
+                &saved.to_bytes()[..STATE_BLOB_SIZE - 1],
+                StateError::TooShort,
+            ),
+            (&version_1, StateError::UnsupportedVersion(1)),
+            (&invalid_direction, StateError::InvalidField),
+        ];
+        for (blob, error) in blobs.iter() {
+            let mut controller = LiftController::new(0.5, 0.001, 0.001);
+            controller.restore_state(saved);
+            assert_eq!(Err(*error), controller.restore_state_from_bytes(blob));
+            assert_eq!(ControllerState::default(), controller.save_state());
+        }
+    }
+
+    #[test]
+    fn event_queue_records_raised_faults() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        let mut events = EventQueue::<4>::new();
+
+        lift.set_emergency_stop(true);
+        controller.poll_with_events(&lift, 0.1, 100, &mut events);
+        controller.poll_with_events(&lift, 0.1, 200, &mut events);
+
+        let kinds: Vec<EventKind> = events.drain().map(|event| event.kind).collect();
+        let faults = Faults {
+            emergency_stop: true,
+            ..Faults::NONE
+        };
+        assert_eq!(
+            vec![
+                EventKind::EmergencyStop { activated: true },
+                EventKind::Fault { faults }
+            ],
+            kinds
+        );
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        departures: Vec<(Floor, Direction)>,
+        arrivals: Vec<Floor>,
+        chimes: Vec<(Floor, Direction)>,
+        announcements: Vec<Announcement>,
+        faults: Vec<Faults>,
+    }
+
+    impl LiftObserver for Recorder {
+        fn on_departed(&mut self, floor: Floor, direction: Direction) {
+            self.departures.push((floor, direction));
+        }
+
+        fn on_arrived(&mut self, floor: Floor) {
+            self.arrivals.push(floor);
+        }
+
+        fn on_chime(&mut self, floor: Floor, direction: Direction) {
+            self.chimes.push((floor, direction));
+        }
+
+        fn on_announcement(&mut self, announcement: Announcement) {
+            self.announcements.push(announcement);
+        }
+
+        fn on_fault(&mut self, faults: Faults) {
+            self.faults.push(faults);
+        }
+    }
+
+    #[test]
+    fn observer_sees_faults_once_until_cleared() {
+        let position = core::cell::Cell::new(0.0);
+        let emergency_stop = core::cell::Cell::new(false);
+        let sensors = ClosureSensors::new(
+            || position.get(),
+            || 0.0,
+            || &[][..],
+            || emergency_stop.get(),
+        );
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        let mut recorder = Recorder::default();
+
+        emergency_stop.set(true);
+        controller.poll_with_observer(&sensors, 0.1, &mut recorder);
+        position.set(f32::NAN);
+        controller.poll_with_observer(&sensors, 0.1, &mut recorder);
+        controller.poll_with_observer(&sensors, 0.1, &mut recorder);
+        controller.clear_faults();
+        controller.poll_with_observer(&sensors, 0.1, &mut recorder);
+
+        let emergency_stop = Faults {
+            emergency_stop: true,
+            ..Faults::NONE
+        };
+        let sensor_reading = Faults {
+            sensor_reading: true,
+            ..Faults::NONE
+        };
+        let both = Faults {
+            sensor_reading: true,
+            emergency_stop: true,
+        };
+        assert_eq!(vec![emergency_stop, sensor_reading, both], recorder.faults);
+    }
+
+    #[test]
+    fn planned_route_is_the_order_calls_are_served() {
+        let time_step = 0.1f32;
+        for (start, first_call) in [(0.0, 5), (8.0, -1)] {
+            let mut lift = SimulatedLift::new(start);
+            let mut controller = LiftController::new(0.5, 0.001, 0.001);
+            lift.register_call(first_call);
+            for step in 0..150 {
+                // Calls registered while the car is anywhere along its first trip
+                if step % 3 == 0 {
+                    let mut lift = lift.clone();
+                    let mut controller = controller.clone();
+                    for floor in [4, 7, 2, -1] {
+                        lift.register_call(floor);
+                    }
+                    let route: Vec<Floor> = controller
+                        .planned_route(lift.current_floor(), lift.floors_to_stop_at())
+                        .collect();
+
+                    let mut recorder = Recorder::default();
+                    for _ in 0..1000 {
+                        let action = controller.poll_with_observer(&lift, time_step, &mut recorder);
+                        lift.accept_action(action, time_step);
+                    }
+                    assert_eq!(
+                        route,
+                        recorder.arrivals,
+                        "from {} going {:?}",
+                        lift.position(),
+                        controller.snapshot().direction
+                    );
+                }
+
+                let action = controller.poll(&lift, time_step);
+                lift.accept_action(action, time_step);
+            }
+        }
+    }
+
+    #[test]
+    fn observer_sees_departures_and_arrivals() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        let mut recorder = Recorder::default();
+        let time_step = 0.1f32;
+
+        lift.register_call(1);
+        lift.register_call(3);
+        for _ in 0..100 {
+            let action = controller.poll_with_observer(&lift, time_step, &mut recorder);
+            lift.accept_action(action, time_step);
+        }
+
+        assert_eq!(
+            vec![(0, Direction::Up), (1, Direction::Up)],
+            recorder.departures
+        );
+        assert_eq!(vec![1, 3], recorder.arrivals);
+    }
+
+    #[test]
+    fn chimes_once_per_stop_for_onward_direction() {
+        for max_acceleration in [None, Some(0.5)] {
+            let mut lift = SimulatedLift::new(2.0);
+            let mut controller = LiftController::new(0.5, 0.001, 0.001);
+            controller.set_max_acceleration(max_acceleration);
+            let mut recorder = Recorder::default();
+            let time_step = 0.1f32;
+
+            lift.register_call(3);
+            lift.register_call(0);
+            let mut chimed_while_moving = false;
+            for _ in 0..300 {
+                let action = controller.poll_with_observer(&lift, time_step, &mut recorder);
+                if controller.chime().map(|chime| chime.floor) == Some(3) {
+                    chimed_while_moving = lift.velocity() > 0.0;
+                }
+                lift.accept_action(action, time_step);
+            }
+
+            assert_eq!(vec![3, 0], recorder.arrivals);
+            assert_eq!(
+                vec![(3, Direction::Down), (0, Direction::Down)],
+                recorder.chimes
+            );
+            assert!(chimed_while_moving);
+        }
+    }
+
+    #[test]
+    fn announces_stops_and_doors() {
+        let mut lift = SimulatedLift::new(2.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        controller.set_max_acceleration(Some(0.5));
+        let mut recorder = Recorder::default();
+        let time_step = 0.1f32;
+
+        lift.register_call(3);
+        lift.register_call(0);
+        for _ in 0..300 {
+            let action = controller.poll_with_observer(&lift, time_step, &mut recorder);
+            lift.accept_action(action, time_step);
+        }
+
+        let down = Direction::Down;
+        assert_eq!(
+            vec![
+                Announcement::DoorsClosing { floor: 2 },
+                Announcement::NextStop { floor: 3 },
+                Announcement::Arriving {
+                    floor: 3,
+                    direction: down
+                },
+                Announcement::DoorsOpening {
+                    floor: 3,
+                    direction: down
+                },
+                Announcement::DoorsClosing { floor: 3 },
+                Announcement::NextStop { floor: 0 },
+                Announcement::Arriving {
+                    floor: 0,
+                    direction: down
+                },
+                Announcement::DoorsOpening {
+                    floor: 0,
+                    direction: down
+                },
+            ],
+            recorder.announcements
+        );
+    }
+
+    #[test]
+    fn hall_lantern_lit_from_chime_until_leaving() {
+        let mut lift = SimulatedLift::new(2.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        let time_step = 0.1f32;
+
+        lift.register_call(3);
+        lift.register_call(0);
+        let mut lit = Vec::new();
+        for _ in 0..300 {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+            let mut lanterns = [None; 5];
+            controller.hall_lanterns(-1, &mut lanterns);
+            assert_eq!(
+                controller.hall_lantern().map(|lantern| lantern.direction),
+                lanterns.iter().copied().flatten().next()
+            );
+            if let Some(lantern) = controller.hall_lantern() {
+                assert!((lantern.floor as Position - lift.position()).abs() <= 1.0);
+                if lit.last() != Some(&lantern) {
+                    lit.push(lantern);
+                }
+            }
+        }
+
+        assert_eq!(
+            vec![
+                HallLantern {
+                    floor: 3,
+                    direction: Direction::Down
+                },
+                HallLantern {
+                    floor: 0,
+                    direction: Direction::Down
+                }
+            ],
+            lit
+        );
+        assert_eq!(
+            Some(0),
+            controller.hall_lantern().map(|lantern| lantern.floor)
+        );
+    }
+
+    #[test]
+    fn standby_after_idle_delay_until_call() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        let time_step = 0.1f32;
+        let poll = |lift: &mut SimulatedLift, controller: &mut LiftController, steps| {
+            for _ in 0..steps {
+                let action = controller.poll(&*lift, time_step);
+                lift.accept_action(action, time_step);
+            }
+        };
+
+        poll(&mut lift, &mut controller, 1000);
+        assert!(!controller.is_standby());
+
+        controller.set_standby_delay(Some(60.0));
+        lift.register_call(1);
+        poll(&mut lift, &mut controller, 20);
+        assert_eq!(1.0, lift.current_floor());
+        poll(&mut lift, &mut controller, 595);
+        assert!(!controller.is_standby());
+        poll(&mut lift, &mut controller, 10);
+        assert!(controller.is_standby());
+
+        lift.register_call(0);
+        poll(&mut lift, &mut controller, 1);
+        assert!(!controller.is_standby());
+    }
+
+    #[test]
+    fn sleeps_and_wakes_on_call_after_rehoming() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        controller.set_standby_delay(Some(10.0));
+        controller.set_sleep(Some(SleepConfig {
+            delay: 60.0,
+            poll_interval: 5.0,
+            rehome: true,
+        }));
+
+        for _ in 0..650 {
+            let action = controller.poll(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+        }
+        assert_eq!(PowerState::Asleep, controller.power_state());
+        assert_eq!(Some(5.0), controller.poll_interval());
+
+        // Slow polls while asleep
+        for _ in 0..10 {
+            let action = controller.poll(&lift, 5.0);
+            lift.accept_action(action, 5.0);
+        }
+        assert_eq!(PowerState::Asleep, controller.power_state());
+
+        lift.register_call(2);
+        for _ in 0..10 {
+            let action = controller.poll(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+            assert_eq!(0.0, action.target_velocity);
+            assert_eq!(Ok(()), controller.check_invariants(&lift));
+        }
+        assert_eq!(PowerState::Waking, controller.power_state());
+        assert_eq!(None, controller.poll_interval());
+
+        controller.finish_waking();
+        let action = controller.poll(&lift, 0.1);
+        assert!(action.target_velocity > 0.0);
+        assert_eq!(PowerState::Active, controller.power_state());
+    }
+
+    #[test]
+    fn counters_accumulate() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        let time_step = 0.1f32;
+
+        lift.register_call(2);
+        lift.register_call(4);
+        for _ in 0..200 {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+        }
+        lift.register_call(1);
+        for _ in 0..200 {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+        }
+
+        let counters = *controller.counters();
+        assert_eq!(3, counters.motor_starts);
+        assert_eq!(3, counters.stops_served);
+        assert_eq!(1, counters.reversals);
+        assert_eq!(7.0, scale(counters.distance as f32, 3));
+
+        controller.record_door_cycle();
+        assert_eq!(1, controller.counters().door_cycles);
+        controller.reset_counters();
+        assert_eq!(Counters::new(), *controller.counters());
+    }
+
+    #[test]
+    fn distance_skips_readings_that_are_not_numbers() {
+        let position = core::cell::Cell::new(1.5);
+        let sensors = ClosureSensors::new(|| position.get(), || 0.0, || &[][..], || false);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        controller.set_maintenance_thresholds(MaintenanceThresholds {
+            distance: Some(1.0),
+            ..MaintenanceThresholds::none()
+        });
+
+        for reading in [1.5, f32::NAN, f32::INFINITY, 0.5].iter() {
+            position.set(*reading);
+            controller.poll(&sensors, 0.1);
+        }
+
+        assert_eq!(1.0, controller.counters().distance);
+        assert!(controller.service_due().distance);
+        assert!(controller.faults().sensor_reading);
+    }
+
+    impl HealthSink for Option<HealthIndicators> {
+        fn report(&mut self, indicators: &HealthIndicators) {
+            *self = Some(*indicators);
+        }
+    }
+
+    #[test]
+    fn health_indicators_cover_period() {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.01, 0.001);
+        let time_step = 0.1f32;
+
+        lift.register_call(2);
+        for _ in 0..100 {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+        }
+        controller.record_door_reopen();
+        controller.record_door_reopen();
+
+        let mut report = None;
+        controller.feed_health(&mut report);
+        let indicators = report.unwrap();
+        assert_eq!(1, indicators.arrivals);
+        assert_eq!(1, indicators.departures);
+        assert_eq!(Some(0.0), indicators.leveling_error.map(|e| scale(e, 4)));
+        assert_eq!(Some(0.1), indicators.start_latency.map(|l| scale(l, 4)));
+        assert_eq!(2, indicators.door_reopens);
+        assert_eq!(Some(2.0), indicators.reopen_frequency);
+
+        controller.record_door_reopen();
+        controller.feed_health(&mut report);
+        assert_eq!(0, report.unwrap().arrivals);
+        assert_eq!(None, report.unwrap().leveling_error);
+        assert_eq!(1, report.unwrap().door_reopens);
+        assert_eq!(None, report.unwrap().reopen_frequency);
+    }
+
+    struct Ready;
+
+    impl run::Tick for Ready {
+        async fn tick(&mut self) {}
+    }
+
+    /// Sensors updated through interior mutability, as they would be by another task
+    struct SharedLift {
+        position: core::cell::Cell<Position>,
+        velocity: core::cell::Cell<Velocity>,
+        floors_to_stop_at: Vec<Floor>,
+    }
+
+    impl LiftSensors for SharedLift {
+        fn current_floor(&self) -> Position {
+            self.position.get()
+        }
+
+        fn current_velocity(&self) -> Velocity {
+            self.velocity.get()
+        }
+
+        fn floors_to_stop_at(&self) -> &[Floor] {
+            &self.floors_to_stop_at
+        }
+
+        fn is_emergency_stop_activated(&self) -> bool {
+            false
+        }
+    }
+
+    struct Drive<'a>(&'a SharedLift, f32);
+
+    impl run::ActionSink for Drive<'_> {
+        fn apply(&mut self, action: Action) -> core::ops::ControlFlow<()> {
+            let lift = self.0;
+            lift.position
+                .set(lift.position.get() + action.target_velocity * self.1);
+            lift.velocity.set(action.target_velocity);
+            if action.is_stopped_at_current_floor {
+                core::ops::ControlFlow::Break(())
+            } else {
+                core::ops::ControlFlow::Continue(())
+            }
+        }
+    }
+
+    #[test]
+    fn async_loop_runs_until_stopped() {
+        let lift = SharedLift {
+            position: Default::default(),
+            velocity: Default::default(),
+            floors_to_stop_at: vec![3],
+        };
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        let time_step = 0.1f32;
+        let mut drive = Drive(&lift, time_step);
+        let mut ticker = Ready;
+
+        let mut future = core::pin::pin!(run::run(
+            &mut controller,
+            &lift,
+            &mut drive,
+            &mut ticker,
+            time_step
+        ));
+        let mut context = core::task::Context::from_waker(core::task::Waker::noop());
+        assert!(future.as_mut().poll(&mut context).is_ready());
+        assert_eq!(3.0, scale(lift.current_floor(), 4));
+    }
 }
@@ -0,0 +1,153 @@
+//! Modbus register map for supervising a lift from a PLC.
+//!
+//! **Input registers** (function code 4) expose the state of the lift, 32 bit values occupy two
+//! registers with the high word first:
+//!
+//! | address | content                                                        |
+//! |---------|----------------------------------------------------------------|
+//! | 0-1     | position in thousandths of a floor (i32)                       |
+//! | 2-3     | velocity in thousandths of a floor per second (i32)            |
+//! | 4       | mode, 0 = idle, 1 = serving, 2 = emergency stop                |
+//! | 5       | direction, 0 = neutral, 1 = up, 2 = down                       |
+//! | 6       | target floor (i16), `NO_TARGET` when there is none             |
+//! | 7       | status bits, see `STATUS_EMERGENCY_STOP` and `STATUS_SERVICE_DUE` |
+//! | 8-9     | motor starts (u32)                                             |
+//! | 10-11   | stops served (u32)                                             |
+//!
+//! **Coils** (function codes 1, 5 and 15) control the lift: coil 0 is the emergency stop,
+//! coil `1 + n` is the call for the `n`-th floor counted from the lowest floor of the map.
+
+use crate::state::{direction_to_byte, mode_to_byte};
+use crate::{Floor, LiftController, LiftSensors, Mode};
+
+/// Number of input registers in the map
+pub const INPUT_REGISTER_COUNT: usize = 12;
+
+/// Value of the target floor register when the lift has no target
+pub const NO_TARGET: u16 = 0x8000;
+
+/// Status bit set while the emergency stop is active
+pub const STATUS_EMERGENCY_STOP: u16 = 0x0001;
+
+/// Status bit set when an operational counter reached its maintenance threshold
+pub const STATUS_SERVICE_DUE: u16 = 0x0002;
+
+/// Coil address of the emergency stop
+pub const EMERGENCY_STOP_COIL: u16 = 0;
+
+/// A write to a coil, decoded into what it means for the lift
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoilWrite {
+    /// Activate or release the emergency stop
+    EmergencyStop(bool),
+    /// Register (`true`) or cancel (`false`) a call to the floor
+    Call(Floor, bool),
+}
+
+/// The floors covered by the coil map of a building
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterMap {
+    pub lowest_floor: Floor,
+    pub floors: u16,
+}
+
+impl RegisterMap {
+    /// Read all input registers
+    pub fn input_registers(
+        &self,
+        controller: &LiftController,
+        sensors: &dyn LiftSensors,
+    ) -> [u16; INPUT_REGISTER_COUNT] {
+        let snapshot = controller.snapshot();
+        let counters = controller.counters();
+        let mut registers = [0u16; INPUT_REGISTER_COUNT];
+
+        let position = (sensors.current_floor() * 1000.0) as i32;
+        let velocity = (sensors.current_velocity() * 1000.0) as i32;
+        registers[0..2].copy_from_slice(&split(position as u32));
+        registers[2..4].copy_from_slice(&split(velocity as u32));
+        registers[4] = mode_to_byte(snapshot.mode) as u16;
+        registers[5] = direction_to_byte(snapshot.direction) as u16;
+        registers[6] = snapshot
+            .target
+            .map(|target| target as i16 as u16)
+            .unwrap_or(NO_TARGET);
+
+        if snapshot.mode == Mode::EmergencyStop {
+            registers[7] |= STATUS_EMERGENCY_STOP;
+        }
+        if controller.service_due().any() {
+            registers[7] |= STATUS_SERVICE_DUE;
+        }
+
+        registers[8..10].copy_from_slice(&split(counters.motor_starts));
+        registers[10..12].copy_from_slice(&split(counters.stops_served));
+        registers
+    }
+
+    /// Read a coil, `None` if the address is outside of the map
+    pub fn read_coil(&self, sensors: &dyn LiftSensors, address: u16) -> Option<bool> {
+        match self.coil_to_floor(address)? {
+            None => Some(sensors.is_emergency_stop_activated()),
+            Some(floor) => Some(sensors.floors_to_stop_at().contains(&floor)),
+        }
+    }
+
+    /// Decode a write to a coil, `None` if the address is outside of the map
+    pub fn decode_coil_write(&self, address: u16, value: bool) -> Option<CoilWrite> {
+        match self.coil_to_floor(address)? {
+            None => Some(CoilWrite::EmergencyStop(value)),
+            Some(floor) => Some(CoilWrite::Call(floor, value)),
+        }
+    }
+
+    /// Coil address of the call for a floor, `None` if the floor is outside of the map
+    pub fn floor_to_coil(&self, floor: Floor) -> Option<u16> {
+        let offset = floor - self.lowest_floor;
+        if offset >= 0 && offset < self.floors as i32 {
+            Some(offset as u16 + 1)
+        } else {
+            None
+        }
+    }
+
+    /// `Some(None)` for the emergency stop coil, `Some(Some(floor))` for call coils
+    fn coil_to_floor(&self, address: u16) -> Option<Option<Floor>> {
+        match address {
+            EMERGENCY_STOP_COIL => Some(None),
+            address if address <= self.floors => Some(Some(self.lowest_floor + address as i32 - 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Split a 32 bit value into two registers, high word first
+fn split(value: u32) -> [u16; 2] {
+    [(value >> 16) as u16, value as u16]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coil_addresses() {
+        let map = RegisterMap {
+            lowest_floor: -2,
+            floors: 13,
+        };
+
+        assert_eq!(Some(1), map.floor_to_coil(-2));
+        assert_eq!(Some(13), map.floor_to_coil(10));
+        assert_eq!(None, map.floor_to_coil(11));
+        assert_eq!(
+            Some(CoilWrite::EmergencyStop(true)),
+            map.decode_coil_write(0, true)
+        );
+        assert_eq!(
+            Some(CoilWrite::Call(0, true)),
+            map.decode_coil_write(3, true)
+        );
+        assert_eq!(None, map.decode_coil_write(14, true));
+    }
+}
@@ -0,0 +1,162 @@
+//! Adapter driving a DC motor through a PWM channel and a direction pin, using the `embedded-hal` traits.
+//!
+//! The commanded velocity is scaled into a duty cycle, with velocities inside the deadband switching
+//! the motor off, such that small models can be driven directly from the controller.
+
+use core::ops::ControlFlow;
+
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::run::ActionSink;
+use crate::{Action, Velocity};
+
+/// Scaling of velocities to motor outputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotorConfig {
+    /// Velocity resulting in a duty cycle of 100%, higher velocities are clamped
+    pub max_velocity: Velocity,
+
+    /// Velocities with a magnitude below this turn the motor off
+    pub deadband: Velocity,
+
+    /// Set the direction pin high when moving down rather than up
+    pub invert_direction: bool,
+}
+
+/// Errors from the underlying PWM channel or direction pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotorError<P, D> {
+    Pwm(P),
+    Direction(D),
+}
+
+/// A motor driven by a PWM channel for speed and an output pin for direction
+pub struct MotorOutput<P, D> {
+    pwm: P,
+    direction: D,
+    config: MotorConfig,
+}
+
+impl<P: SetDutyCycle, D: OutputPin> MotorOutput<P, D> {
+    pub fn new(pwm: P, direction: D, config: MotorConfig) -> Self {
+        MotorOutput {
+            pwm,
+            direction,
+            config,
+        }
+    }
+
+    /// Set the outputs according to the target velocity of the action
+    pub fn apply(&mut self, action: &Action) -> Result<(), MotorError<P::Error, D::Error>> {
+        let velocity = action.target_velocity;
+        let speed = velocity.abs();
+
+        if speed < self.config.deadband {
+            return self.pwm.set_duty_cycle_fully_off().map_err(MotorError::Pwm);
+        }
+
+        let up = velocity > 0.0;
+        if up != self.config.invert_direction {
+            self.direction.set_low()
+        } else {
+            self.direction.set_high()
+        }
+        .map_err(MotorError::Direction)?;
+
+        let fraction = f32::min(speed / self.config.max_velocity, 1.0);
+        let duty = (fraction * self.pwm.max_duty_cycle() as f32) as u16;
+        self.pwm.set_duty_cycle(duty).map_err(MotorError::Pwm)
+    }
+
+    /// Release the PWM channel and direction pin
+    pub fn release(self) -> (P, D) {
+        (self.pwm, self.direction)
+    }
+}
+
+/// Drives the motor from the async control loop, stopping the loop if an output fails
+impl<P: SetDutyCycle, D: OutputPin> ActionSink for MotorOutput<P, D> {
+    fn apply(&mut self, action: Action) -> ControlFlow<()> {
+        match MotorOutput::apply(self, &action) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use embedded_hal::digital::ErrorType as PinErrorType;
+    use embedded_hal::pwm::ErrorType as PwmErrorType;
+
+    #[derive(Default)]
+    struct Pwm(u16);
+
+    impl PwmErrorType for Pwm {
+        type Error = Infallible;
+    }
+
+    impl SetDutyCycle for Pwm {
+        fn max_duty_cycle(&self) -> u16 {
+            1000
+        }
+
+        fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+            self.0 = duty;
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Pin(bool);
+
+    impl PinErrorType for Pin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Pin {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0 = false;
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0 = true;
+            Ok(())
+        }
+    }
+
+    fn action(target_velocity: Velocity) -> Action {
+        Action {
+            target_velocity,
+            is_stopped_at_current_floor: false,
+        }
+    }
+
+    #[test]
+    fn scales_and_applies_deadband() {
+        let config = MotorConfig {
+            max_velocity: 2.0,
+            deadband: 0.05,
+            invert_direction: false,
+        };
+        let mut motor = MotorOutput::new(Pwm::default(), Pin::default(), config);
+
+        motor.apply(&action(-1.0)).unwrap();
+        assert_eq!(500, motor.pwm.0);
+        assert!(motor.direction.0);
+
+        motor.apply(&action(5.0)).unwrap();
+        assert_eq!(1000, motor.pwm.0);
+        assert!(!motor.direction.0);
+
+        motor.apply(&action(0.01)).unwrap();
+        assert_eq!(0, motor.pwm.0);
+    }
+}
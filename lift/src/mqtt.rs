@@ -0,0 +1,149 @@
+//! Publishing of controller telemetry to MQTT, and reception of commands from it.
+//!
+//! The module does not depend on any MQTT client library. The host implements `MqttClient` for the
+//! client of its choice, and forwards received messages to `MqttPublisher::handle_message`.
+//!
+//! Topics are derived from a configurable prefix:
+//!
+//! * `<prefix>/snapshot` - the controller snapshot as JSON, published retained
+//! * `<prefix>/event` - every event as JSON
+//! * `<prefix>/command` - subscribed to, accepts `wire::Command` values as JSON
+
+use std::format;
+use std::string::String;
+
+use crate::wire::Command;
+use crate::{Event, EventQueue, Snapshot};
+
+/// Minimal interface of an MQTT client, to be implemented by the host
+pub trait MqttClient {
+    type Error;
+
+    /// Publish a payload to a topic
+    fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> Result<(), Self::Error>;
+
+    /// Subscribe to a topic, received messages are to be passed to `MqttPublisher::handle_message`
+    fn subscribe(&mut self, topic: &str) -> Result<(), Self::Error>;
+}
+
+/// Errors when publishing or receiving
+#[derive(Debug)]
+pub enum MqttError<E> {
+    /// The client failed
+    Client(E),
+    /// A payload could not be serialized or deserialized
+    Json(serde_json::Error),
+}
+
+/// Publishes telemetry of one lift under a topic prefix
+pub struct MqttPublisher<C> {
+    client: C,
+    snapshot_topic: String,
+    event_topic: String,
+    command_topic: String,
+}
+
+impl<C: MqttClient> MqttPublisher<C> {
+    /// Create a publisher for the topics under `prefix`, subscribing to the command topic
+    pub fn new(mut client: C, prefix: &str) -> Result<Self, MqttError<C::Error>> {
+        let command_topic = format!("{}/command", prefix);
+        client
+            .subscribe(&command_topic)
+            .map_err(MqttError::Client)?;
+
+        Ok(MqttPublisher {
+            client,
+            snapshot_topic: format!("{}/snapshot", prefix),
+            event_topic: format!("{}/event", prefix),
+            command_topic,
+        })
+    }
+
+    /// Publish a snapshot of the controller state
+    pub fn publish_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), MqttError<C::Error>> {
+        let payload = serde_json::to_vec(snapshot).map_err(MqttError::Json)?;
+        self.client
+            .publish(&self.snapshot_topic, &payload, true)
+            .map_err(MqttError::Client)
+    }
+
+    /// Publish a single event
+    pub fn publish_event(&mut self, event: &Event) -> Result<(), MqttError<C::Error>> {
+        let payload = serde_json::to_vec(event).map_err(MqttError::Json)?;
+        self.client
+            .publish(&self.event_topic, &payload, false)
+            .map_err(MqttError::Client)
+    }
+
+    /// Drain the queue, publishing the events oldest first.
+    /// On failure the remaining events are left in the queue
+    pub fn publish_events<const N: usize>(
+        &mut self,
+        queue: &mut EventQueue<N>,
+    ) -> Result<(), MqttError<C::Error>> {
+        while let Some(event) = queue.pop() {
+            self.publish_event(&event)?;
+        }
+        Ok(())
+    }
+
+    /// Decode a received message, `None` if it was not sent to the command topic
+    pub fn handle_message(
+        &self,
+        topic: &str,
+        payload: &[u8],
+    ) -> Option<Result<Command, MqttError<C::Error>>> {
+        if topic != self.command_topic {
+            return None;
+        }
+        Some(serde_json::from_slice(payload).map_err(MqttError::Json))
+    }
+
+    /// The underlying client
+    pub fn client(&mut self) -> &mut C {
+        &mut self.client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiftController;
+    use std::{vec, vec::Vec};
+
+    #[derive(Default)]
+    struct Client {
+        subscriptions: Vec<String>,
+        published: Vec<(String, Vec<u8>)>,
+    }
+
+    impl MqttClient for Client {
+        type Error = ();
+
+        fn publish(&mut self, topic: &str, payload: &[u8], _retain: bool) -> Result<(), ()> {
+            self.published.push((topic.into(), payload.into()));
+            Ok(())
+        }
+
+        fn subscribe(&mut self, topic: &str) -> Result<(), ()> {
+            self.subscriptions.push(topic.into());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn topics_and_commands() {
+        let mut publisher = MqttPublisher::new(Client::default(), "building/lift-1").unwrap();
+        publisher
+            .publish_snapshot(&LiftController::new(1.0, 0.01, 0.01).snapshot())
+            .unwrap();
+
+        let client = publisher.client();
+        assert_eq!(vec!["building/lift-1/command"], client.subscriptions);
+        assert_eq!("building/lift-1/snapshot", client.published[0].0);
+
+        let command = publisher.handle_message("building/lift-1/command", br#"{"StopAtFloor":3}"#);
+        assert_eq!(Some(Command::StopAtFloor(3)), command.and_then(|c| c.ok()));
+        assert!(publisher.handle_message("other", b"").is_none());
+    }
+}
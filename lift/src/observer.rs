@@ -0,0 +1,190 @@
+use crate::{Announcement, Direction, Faults, Floor, Mode, Snapshot};
+
+/// Trait to be implemented by integrations that want to react to transitions in the controller,
+/// rather than comparing successive `Action`s themselves.
+///
+/// All callbacks have empty default implementations, so only the relevant ones need to be implemented.
+/// Floors reported are the floor nearest to the lift when the transition happened.
+pub trait LiftObserver {
+    /// The lift has started moving from a standstill at `floor`, travelling in `direction`
+    fn on_departed(&mut self, _floor: Floor, _direction: Direction) {}
+
+    /// The lift has reached a floor it was to stop at
+    fn on_arrived(&mut self, _floor: Floor) {}
+
+    /// The lift changed the direction it is serving floors in
+    fn on_direction_changed(&mut self, _from: Direction, _to: Direction) {}
+
+    /// The controller selected a new target floor
+    fn on_target_selected(&mut self, _floor: Floor) {}
+
+    /// The emergency stop was activated (`true`) or released (`false`)
+    fn on_emergency_stop(&mut self, _activated: bool) {}
+
+    /// The lift committed to stopping at `floor`, and will leave it in `direction`. See `Chime`
+    fn on_chime(&mut self, _floor: Floor, _direction: Direction) {}
+
+    /// Something for a voice annunciator to say, possibly several times in one poll
+    fn on_announcement(&mut self, _announcement: Announcement) {}
+
+    /// The controller latched one or more faults. Only the faults raised by this poll are set in `faults`,
+    /// faults that were already latched are not reported again until they have been cleared
+    fn on_fault(&mut self, _faults: Faults) {}
+}
+
+/// The unit type is used as the observer when nobody is interested in the transitions
+impl LiftObserver for () {}
+
+/// Compare the controller state from before and after a poll and notify the observer of the differences
+pub(crate) fn notify_transitions(
+    previous: &Snapshot,
+    current: &Snapshot,
+    floor: Floor,
+    observer: &mut dyn LiftObserver,
+) {
+    match (previous.mode, current.mode) {
+        (Mode::EmergencyStop, Mode::EmergencyStop) => {}
+        (_, Mode::EmergencyStop) => observer.on_emergency_stop(true),
+        (Mode::EmergencyStop, _) => observer.on_emergency_stop(false),
+        (Mode::Serving, Mode::Idle) => observer.on_arrived(floor),
+        _ => {}
+    }
+
+    if current.direction != previous.direction {
+        observer.on_direction_changed(previous.direction, current.direction);
+    }
+
+    if current.target != previous.target {
+        if let Some(target) = current.target {
+            observer.on_target_selected(target);
+        }
+    }
+
+    if previous.mode == Mode::Idle && current.mode == Mode::Serving {
+        observer.on_departed(floor, current.direction);
+    }
+
+    let raised = Faults {
+        sensor_reading: current.faults.sensor_reading && !previous.faults.sensor_reading,
+        emergency_stop: current.faults.emergency_stop && !previous.faults.emergency_stop,
+    };
+    if raised.any() {
+        observer.on_fault(raised);
+    }
+}
+
+/// Observer forwarding all transitions as records to the `log` crate.
+///
+/// Every record is a list of `key=value` fields, starting with `event=` and the floor nearest to the lift
+/// and the direction it is serving after the poll, such that the records can be filtered and parsed
+#[cfg(feature = "log")]
+pub(crate) struct Logger {
+    pub floor: Floor,
+    pub direction: Direction,
+}
+
+#[cfg(feature = "log")]
+impl LiftObserver for Logger {
+    fn on_departed(&mut self, floor: Floor, direction: Direction) {
+        log::debug!(target: "lift", "event=departed floor={} direction={:?}", floor, direction);
+    }
+
+    fn on_arrived(&mut self, floor: Floor) {
+        log::debug!(target: "lift", "event=stop_completed floor={} direction={:?}", floor, self.direction);
+    }
+
+    fn on_direction_changed(&mut self, from: Direction, to: Direction) {
+        log::debug!(target: "lift", "event=direction_changed floor={} from={:?} to={:?}", self.floor, from, to);
+    }
+
+    fn on_target_selected(&mut self, floor: Floor) {
+        log::debug!(target: "lift", "event=target_selected floor={} direction={:?} target={}", self.floor, self.direction, floor);
+    }
+
+    fn on_emergency_stop(&mut self, activated: bool) {
+        if activated {
+            log::warn!(target: "lift", "event=emergency_stop floor={} direction={:?} activated=true", self.floor, self.direction);
+        } else {
+            log::info!(target: "lift", "event=emergency_stop floor={} direction={:?} activated=false", self.floor, self.direction);
+        }
+    }
+
+    fn on_chime(&mut self, floor: Floor, direction: Direction) {
+        log::debug!(target: "lift", "event=chime floor={} direction={:?}", floor, direction);
+    }
+
+    fn on_announcement(&mut self, announcement: Announcement) {
+        log::debug!(target: "lift", "event=announcement floor={} announcement={:?}", self.floor, announcement);
+    }
+
+    fn on_fault(&mut self, faults: Faults) {
+        let raised = [
+            (faults.sensor_reading, "sensor_reading"),
+            (faults.emergency_stop, "emergency_stop"),
+        ];
+        for (_, fault) in raised.iter().filter(|(raised, _)| *raised) {
+            log::warn!(target: "lift", "event=fault_raised floor={} direction={:?} fault={}", self.floor, self.direction, fault);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "log"))]
+mod tests {
+    extern crate std;
+
+    use crate::sim::SimulatedLift;
+    use crate::LiftController;
+    use std::string::{String, ToString};
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    impl log::Log for Capture {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            if record.target() == "lift" {
+                RECORDS.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn logs_key_value_records() {
+        log::set_logger(&Capture).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(0.5, 0.001, 0.001);
+        lift.register_call(1);
+        for _ in 0..40 {
+            let action = controller.poll(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+        }
+        lift.set_emergency_stop(true);
+        controller.poll(&lift, 0.1);
+
+        let records = RECORDS.lock().unwrap();
+        let expected = [
+            "event=target_selected floor=0 direction=Up target=1",
+            "event=departed floor=0 direction=Up",
+            "event=stop_completed floor=1 direction=Up",
+            "event=emergency_stop floor=1 direction=Up activated=true",
+            "event=fault_raised floor=1 direction=Up fault=emergency_stop",
+        ];
+        for record in expected.iter() {
+            assert!(
+                records.iter().any(|logged| logged == record),
+                "missing {}",
+                record
+            );
+        }
+        assert!(records.iter().all(|record| record.starts_with("event=")));
+    }
+}
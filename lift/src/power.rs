@@ -0,0 +1,29 @@
+/// Power state of the controller, for hosts managing their own power domains
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerState {
+    /// Serving calls, or idle for less than the standby delay
+    Active,
+    /// Idle without calls for the standby delay, the car lighting and ventilation may be switched off
+    Standby,
+    /// Idle without calls for the sleep delay. The host may poll at `SleepConfig::poll_interval`
+    /// and power down the drive
+    Asleep,
+    /// Woken by a call or sensor activity and waiting for the host to re-home the car, see
+    /// `LiftController::finish_waking`. The car is held at the floor until then
+    Waking,
+}
+
+/// When the controller goes to sleep and how it wakes up
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SleepConfig {
+    /// Seconds idle without calls before going to sleep
+    pub delay: f32,
+    /// Seconds between polls the host may use while asleep
+    pub poll_interval: f32,
+    /// Hold the car after waking up until the host has re-homed it and calls `LiftController::finish_waking`
+    pub rehome: bool,
+}
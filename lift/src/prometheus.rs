@@ -0,0 +1,165 @@
+//! Formatting of controller metrics in the Prometheus text exposition format.
+//!
+//! The output is plain text ready to be served from the `/metrics` endpoint of any HTTP stack.
+//! Every car is identified by a `car` label, such that the metrics of a whole group of lifts
+//! can be exposed from one endpoint.
+
+use std::fmt::Write;
+use std::string::String;
+
+use crate::{Floor, LiftController, Mode};
+
+/// Content type to serve the exposition with
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Format the operational counters, state and maintenance status of the given cars
+pub fn encode_controllers(cars: &[(&str, &LiftController)]) -> String {
+    let mut out = String::new();
+
+    family(
+        &mut out,
+        "lift_motor_starts_total",
+        "Number of times the lift started moving from a standstill",
+        "counter",
+        cars.iter()
+            .map(|(car, c)| (label(car), c.counters().motor_starts as f64)),
+    );
+    family(
+        &mut out,
+        "lift_distance_floors_total",
+        "Total distance traveled in floors",
+        "counter",
+        cars.iter()
+            .map(|(car, c)| (label(car), c.counters().distance)),
+    );
+    family(
+        &mut out,
+        "lift_reversals_total",
+        "Number of direction reversals",
+        "counter",
+        cars.iter()
+            .map(|(car, c)| (label(car), c.counters().reversals as f64)),
+    );
+    family(
+        &mut out,
+        "lift_stops_served_total",
+        "Number of stops made at requested floors",
+        "counter",
+        cars.iter()
+            .map(|(car, c)| (label(car), c.counters().stops_served as f64)),
+    );
+    family(
+        &mut out,
+        "lift_door_cycles_total",
+        "Number of door cycles reported by the host",
+        "counter",
+        cars.iter()
+            .map(|(car, c)| (label(car), c.counters().door_cycles as f64)),
+    );
+    family(
+        &mut out,
+        "lift_emergency_stop",
+        "1 if the emergency stop is active",
+        "gauge",
+        cars.iter().map(|(car, c)| {
+            let active = c.snapshot().mode == Mode::EmergencyStop;
+            (label(car), if active { 1.0 } else { 0.0 })
+        }),
+    );
+    family(
+        &mut out,
+        "lift_target_floor",
+        "Floor the lift is heading for, absent when idle",
+        "gauge",
+        cars.iter().filter_map(|(car, c)| {
+            c.snapshot()
+                .target
+                .map(|target| (label(car), target as f64))
+        }),
+    );
+    family(
+        &mut out,
+        "lift_service_due",
+        "1 if any operational counter reached its maintenance threshold",
+        "gauge",
+        cars.iter().map(|(car, c)| {
+            let due = c.service_due().any();
+            (label(car), if due { 1.0 } else { 0.0 })
+        }),
+    );
+
+    out
+}
+
+/// Format estimated times of arrival as given by `(car, floor, seconds)`
+pub fn encode_etas(etas: &[(&str, Floor, f32)]) -> String {
+    let mut out = String::new();
+    family(
+        &mut out,
+        "lift_eta_seconds",
+        "Estimated time until the car arrives at the floor",
+        "gauge",
+        etas.iter().map(|(car, floor, eta)| {
+            let mut labels = label(car);
+            labels.pop();
+            let _ = write!(labels, ",floor=\"{}\"}}", floor);
+            (labels, *eta as f64)
+        }),
+    );
+    out
+}
+
+/// Write one metric family, the header is skipped if there are no samples
+fn family<I: Iterator<Item = (String, f64)>>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    kind: &str,
+    samples: I,
+) {
+    let mut samples = samples.peekable();
+    if samples.peek().is_none() {
+        return;
+    }
+
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+}
+
+/// Label set identifying a car, with the value escaped according to the exposition format
+fn label(car: &str) -> String {
+    let mut labels = String::from("{car=\"");
+    for c in car.chars() {
+        match c {
+            '\\' => labels.push_str("\\\\"),
+            '"' => labels.push_str("\\\""),
+            '\n' => labels.push_str("\\n"),
+            c => labels.push(c),
+        }
+    }
+    labels.push_str("\"}");
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn families_are_grouped() {
+        let a = LiftController::new(1.0, 0.01, 0.01);
+        let b = LiftController::new(1.0, 0.01, 0.01);
+        let text = encode_controllers(&[("a", &a), ("b\"", &b)]);
+
+        assert_eq!(1, text.matches("# TYPE lift_motor_starts_total").count());
+        assert!(text.contains("lift_motor_starts_total{car=\"a\"} 0\n"));
+        assert!(text.contains("lift_motor_starts_total{car=\"b\\\"\"} 0\n"));
+        assert!(!text.contains("lift_target_floor"));
+
+        let text = encode_etas(&[("a", -1, 2.5)]);
+        assert!(text.contains("lift_eta_seconds{car=\"a\",floor=\"-1\"} 2.5\n"));
+    }
+}
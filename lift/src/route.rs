@@ -0,0 +1,138 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::calls::Calls;
+use crate::{Direction, Floor, Position};
+
+/// Lazy iterator over the floors of a stop slice in the order the lift will serve them.
+///
+/// The lift first sweeps in its direction of travel, then reverses and serves the remaining
+/// floors. No allocation takes place, each step scans the borrowed slice, and duplicate
+/// floors are only yielded once.
+///
+/// Like the planner the position is rounded to the nearest floor, and a sweep starts with the calls at
+/// that floor, so a lift slightly past a floor it is about to stop at returns to it first.
+#[derive(Debug, Clone)]
+pub struct Route<'a> {
+    floors: &'a [Floor],
+    /// The floor the position rounds to
+    start: Floor,
+    /// Floors are yielded beyond the cursor in the direction of the sweep, wide enough to sit past any floor
+    cursor: i64,
+    direction: Direction,
+    reversed: bool,
+}
+
+impl<'a> Route<'a> {
+    pub(crate) fn new(floors: &'a [Floor], current_floor: Position, direction: Direction) -> Self {
+        let start = current_floor.round() as Floor;
+        let direction = match direction {
+            Direction::Neutral => neutral_direction(floors, start),
+            direction => direction,
+        };
+
+        // Start just behind the rounded floor so a call there is served first
+        let cursor = match direction {
+            Direction::Down => start as i64 + 1,
+            _ => start as i64 - 1,
+        };
+
+        Route {
+            floors,
+            start,
+            cursor,
+            direction,
+            reversed: false,
+        }
+    }
+
+    fn next_in_sweep(&self) -> Option<Floor> {
+        let cursor = self.cursor;
+        let floors = self.floors.iter().copied();
+        match self.direction {
+            Direction::Down => floors.filter(|f| (*f as i64) < cursor).max(),
+            _ => floors.filter(|f| (*f as i64) > cursor).min(),
+        }
+    }
+}
+
+/// The direction a lift without one takes. The planner heads for the nearest call, and keeps no direction
+/// while serving a call at the floor it is at, after which it heads for the nearest of the other calls
+fn neutral_direction(floors: &[Floor], floor: Floor) -> Direction {
+    let nearest = match floors.nearest_floor(floor) {
+        Some(nearest) if nearest == floor => {
+            let below = floor
+                .checked_sub(1)
+                .and_then(|below| floors.nearest_floor_below(below));
+            let above = floor
+                .checked_add(1)
+                .and_then(|above| floors.nearest_floor_above(above));
+            match (below, above) {
+                (Some(below), Some(above)) if above.abs_diff(floor) < below.abs_diff(floor) => {
+                    Some(above)
+                }
+                (Some(below), _) => Some(below),
+                (None, above) => above,
+            }
+        }
+        nearest => nearest,
+    };
+    match nearest {
+        Some(nearest) if nearest < floor => Direction::Down,
+        _ => Direction::Up,
+    }
+}
+
+impl<'a> Iterator for Route<'a> {
+    type Item = Floor;
+
+    fn next(&mut self) -> Option<Floor> {
+        loop {
+            if let Some(floor) = self.next_in_sweep() {
+                self.cursor = floor as i64;
+                return Some(floor);
+            }
+            if self.reversed {
+                return None;
+            }
+            // The first sweep served the calls at the rounded floor, so the second one starts past it
+            self.reversed = true;
+            self.cursor = self.start as i64;
+            self.direction = match self.direction {
+                Direction::Down => Direction::Up,
+                _ => Direction::Down,
+            };
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.floors.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    #[test]
+    fn serves_direction_of_travel_first() {
+        let floors = [2, 7, -1, 5, 2, 4];
+        // Slightly past floor 4, the planner returns to it before continuing up
+        let route = Route::new(&floors, 4.3, Direction::Up);
+        assert_eq!(vec![4, 5, 7, 2, -1], route.collect::<Vec<_>>());
+        let route = Route::new(&floors, 4.6, Direction::Up);
+        assert_eq!(vec![5, 7, 4, 2, -1], route.collect::<Vec<_>>());
+        let route = Route::new(&floors, 4.3, Direction::Down);
+        assert_eq!(vec![4, 2, -1, 5, 7], route.collect::<Vec<_>>());
+
+        let mut route = Route::new(&floors, 2.0, Direction::Neutral);
+        assert_eq!(Some(2), route.next());
+        assert_eq!(Some(4), route.next());
+        // Ties go to the floor below, like the planner
+        let route = Route::new(&[3, 1], 2.0, Direction::Neutral);
+        assert_eq!(vec![1, 3], route.collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,39 @@
+//! Asynchronous control loop, for running the controller as a task on an async executor such as Embassy.
+
+use core::future::Future;
+use core::ops::ControlFlow;
+
+use crate::{Action, LiftController, LiftSensors};
+
+/// A fixed-rate tick source, typically a thin wrapper around `embassy_time::Ticker`
+pub trait Tick {
+    /// Wait for the next tick
+    fn tick(&mut self) -> impl Future<Output = ()>;
+}
+
+/// Receiver of the actions decided by the controller, typically the motor drive
+pub trait ActionSink {
+    /// Apply an action, returning `ControlFlow::Break` stops the control loop
+    fn apply(&mut self, action: Action) -> ControlFlow<()>;
+}
+
+/// Run the control loop, polling the controller on every tick and passing the actions to `commands`.
+///
+/// `time_step` must match the period of the ticker. The sensors are borrowed for the lifetime of the loop,
+/// so readings updated by other tasks must be shared through interior mutability.
+/// The future completes when `commands` breaks the loop.
+pub async fn run<S: LiftSensors, A: ActionSink, T: Tick>(
+    controller: &mut LiftController,
+    sensors: &S,
+    commands: &mut A,
+    ticker: &mut T,
+    time_step: f32,
+) {
+    loop {
+        ticker.tick().await;
+        let action = controller.poll(sensors, time_step);
+        if commands.apply(action).is_break() {
+            return;
+        }
+    }
+}
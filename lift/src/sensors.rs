@@ -0,0 +1,408 @@
+use core::marker::PhantomData;
+
+use crate::{Floor, LiftSensors, Position, Velocity};
+
+/// `LiftSensors` read through four closures or function pointers, for prototypes, benchmarks and FFI shims
+/// that would rather not define a type of their own.
+///
+/// The floors to stop at are borrowed for `'a`, typically from a call queue owned by the caller
+pub struct ClosureSensors<'a, P, V, C, E> {
+    position: P,
+    velocity: V,
+    calls: C,
+    emergency_stop: E,
+    floors: PhantomData<&'a [Floor]>,
+}
+
+impl<'a, P, V, C, E> ClosureSensors<'a, P, V, C, E>
+where
+    P: Fn() -> Position,
+    V: Fn() -> Velocity,
+    C: Fn() -> &'a [Floor],
+    E: Fn() -> bool,
+{
+    /// Sensors reading the position, the velocity, the floors to stop at and the emergency stop from the closures
+    pub const fn new(position: P, velocity: V, calls: C, emergency_stop: E) -> Self {
+        ClosureSensors {
+            position,
+            velocity,
+            calls,
+            emergency_stop,
+            floors: PhantomData,
+        }
+    }
+}
+
+impl<'a, P, V, C, E> LiftSensors for ClosureSensors<'a, P, V, C, E>
+where
+    P: Fn() -> Position,
+    V: Fn() -> Velocity,
+    C: Fn() -> &'a [Floor],
+    E: Fn() -> bool,
+{
+    fn current_floor(&self) -> Position {
+        (self.position)()
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        (self.velocity)()
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        (self.calls)()
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        (self.emergency_stop)()
+    }
+}
+
+/// Position and velocity of the lift at a point in time of a `ScriptedSensors` script
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Keyframe {
+    /// Seconds from the start of the script
+    pub time: f32,
+    pub position: Position,
+    pub velocity: Velocity,
+}
+
+/// `LiftSensors` playing back a script over time, for building exact readings in tests of edge cases.
+///
+/// The position and velocity are interpolated linearly between keyframes, and held before the first and
+/// after the last. They are scripted independently, so readings that do not agree can be played as well.
+/// The floors to stop at and the emergency stop change in steps, each entry holding from its time until the
+/// next. All entries must be in order of time
+#[derive(Debug, Clone)]
+pub struct ScriptedSensors<'a> {
+    motion: &'a [Keyframe],
+    calls: &'a [(f32, &'a [Floor])],
+    emergency_stop: &'a [(f32, bool)],
+    time: f32,
+}
+
+impl<'a> ScriptedSensors<'a> {
+    /// Sensors at the start of the script. Before the first entry of `calls` and `emergency_stop` there are
+    /// no floors to stop at and the emergency stop is released
+    pub const fn new(
+        motion: &'a [Keyframe],
+        calls: &'a [(f32, &'a [Floor])],
+        emergency_stop: &'a [(f32, bool)],
+    ) -> Self {
+        ScriptedSensors {
+            motion,
+            calls,
+            emergency_stop,
+            time: 0.0,
+        }
+    }
+
+    /// Seconds from the start of the script
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Move forward in the script, typically by the time step passed to the controller
+    pub fn advance(&mut self, time_step: f32) {
+        self.time += time_step;
+    }
+
+    /// A value of the keyframes, interpolated at the current time
+    fn interpolate(&self, value: fn(&Keyframe) -> f32) -> f32 {
+        let next = self.motion.iter().position(|key| key.time > self.time);
+        match next {
+            Some(0) => value(&self.motion[0]),
+            Some(next) => {
+                let (from, to) = (&self.motion[next - 1], &self.motion[next]);
+                let fraction = (self.time - from.time) / (to.time - from.time);
+                value(from) + (value(to) - value(from)) * fraction
+            }
+            None => self.motion.last().map(value).unwrap_or(0.0),
+        }
+    }
+
+    /// The value of the last entry at or before the current time
+    fn held<T: Copy>(&self, entries: &[(f32, T)]) -> Option<T> {
+        entries
+            .iter()
+            .take_while(|(time, _)| *time <= self.time)
+            .last()
+            .map(|(_, value)| *value)
+    }
+}
+
+impl<'a> LiftSensors for ScriptedSensors<'a> {
+    fn current_floor(&self) -> Position {
+        self.interpolate(|key| key.position)
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        self.interpolate(|key| key.velocity)
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        self.held(self.calls).unwrap_or(&[])
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        self.held(self.emergency_stop).unwrap_or(false)
+    }
+}
+
+/// Velocity from the change in a position reading between polls, such as from a shaft encoder
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncoderDerivative {
+    last_position: Option<Position>,
+    velocity: Velocity,
+}
+
+impl EncoderDerivative {
+    pub const fn new() -> Self {
+        EncoderDerivative {
+            last_position: None,
+            velocity: 0.0,
+        }
+    }
+
+    /// Take a position reading `time_step` seconds after the previous one and return the velocity.
+    /// The first reading gives zero, as does a time step that is not positive
+    pub fn update(&mut self, position: Position, time_step: f32) -> Velocity {
+        self.velocity = match self.last_position {
+            Some(last) if time_step > 0.0 => (position - last) / time_step,
+            _ => 0.0,
+        };
+        self.last_position = Some(position);
+        self.velocity
+    }
+
+    pub fn velocity(&self) -> Velocity {
+        self.velocity
+    }
+}
+
+/// `LiftSensors` reading the velocity from several sources, such as a tachometer and an encoder derivative,
+/// and everything else from the wrapped sensors.
+///
+/// The readings are combined as a mean weighted by the configured weights, so a source can be selected by
+/// giving the others a weight of zero. The sources are cross-checked on every update: when any two differ by
+/// more than the tolerance a fault is flagged, and the reading furthest from standstill is used instead, so
+/// the controller never takes a moving lift for a stopped one
+#[derive(Debug, Clone)]
+pub struct FusedVelocity<S, const N: usize> {
+    sensors: S,
+    weights: [f32; N],
+    tolerance: Velocity,
+    velocity: Velocity,
+    spread: Velocity,
+}
+
+impl<S: LiftSensors, const N: usize> FusedVelocity<S, N> {
+    /// Fuse `N` velocity sources with `weights`, which must not all be zero, flagging a fault when they
+    /// differ by more than `tolerance` floors/s
+    pub const fn new(sensors: S, weights: [f32; N], tolerance: Velocity) -> Self {
+        FusedVelocity {
+            sensors,
+            weights,
+            tolerance,
+            velocity: 0.0,
+            spread: 0.0,
+        }
+    }
+
+    /// Take a reading from every source, in the order of the weights, and return the fused velocity
+    pub fn update(&mut self, readings: [Velocity; N]) -> Velocity {
+        let (mut lowest, mut highest) = (Velocity::INFINITY, Velocity::NEG_INFINITY);
+        let (mut sum, mut total_weight) = (0.0, 0.0);
+        for (reading, weight) in readings.iter().zip(self.weights.iter()) {
+            lowest = lowest.min(*reading);
+            highest = highest.max(*reading);
+            sum += reading * weight;
+            total_weight += weight;
+        }
+        self.spread = if readings.iter().any(|reading| reading.is_nan()) {
+            Velocity::NAN
+        } else if N > 0 {
+            highest - lowest
+        } else {
+            0.0
+        };
+
+        self.velocity = if self.is_fault() {
+            if highest.abs() > lowest.abs() {
+                highest
+            } else {
+                lowest
+            }
+        } else if total_weight > 0.0 {
+            sum / total_weight
+        } else {
+            0.0
+        };
+        self.velocity
+    }
+
+    /// The sources disagreed beyond the tolerance on the last update
+    pub fn is_fault(&self) -> bool {
+        // Also flags readings that are not numbers
+        self.spread.is_nan() || self.spread > self.tolerance
+    }
+
+    /// Difference between the highest and the lowest reading of the last update
+    pub fn spread(&self) -> Velocity {
+        self.spread
+    }
+
+    pub fn sensors(&self) -> &S {
+        &self.sensors
+    }
+
+    pub fn sensors_mut(&mut self) -> &mut S {
+        &mut self.sensors
+    }
+}
+
+impl<S: LiftSensors, const N: usize> LiftSensors for FusedVelocity<S, N> {
+    fn current_floor(&self) -> Position {
+        self.sensors.current_floor()
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        self.velocity
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        self.sensors.floors_to_stop_at()
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        self.sensors.is_emergency_stop_activated()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiftController;
+    use core::cell::Cell;
+
+    #[test]
+    fn closures_drive_the_controller() {
+        let position = Cell::new(0.0);
+        let velocity = Cell::new(0.0);
+        let calls = [2];
+        fn released() -> bool {
+            false
+        }
+        let sensors = ClosureSensors::new(
+            || position.get(),
+            || velocity.get(),
+            || &calls[..],
+            released,
+        );
+
+        let mut controller = LiftController::new(0.5, 0.01, 0.01);
+        for _ in 0..39 {
+            let action = controller.poll(&sensors, 0.1);
+            position.set(position.get() + action.target_velocity * 0.1);
+            velocity.set(action.target_velocity);
+        }
+
+        assert!((sensors.current_floor() - 1.95).abs() < 1e-4);
+        assert_eq!(&[2], sensors.floors_to_stop_at());
+        assert!(!sensors.is_emergency_stop_activated());
+    }
+
+    #[test]
+    fn script_plays_back_interpolated_and_stepped_signals() {
+        let motion = [
+            Keyframe {
+                time: 1.0,
+                position: 0.0,
+                velocity: 0.0,
+            },
+            Keyframe {
+                time: 3.0,
+                position: 1.0,
+                velocity: 1.0,
+            },
+        ];
+        let calls: [(f32, &[Floor]); 2] = [(0.5, &[1, 4]), (2.0, &[4])];
+        let emergency_stop = [(2.5, true)];
+        let mut sensors = ScriptedSensors::new(&motion, &calls, &emergency_stop);
+
+        assert_eq!(0.0, sensors.current_floor());
+        assert!(sensors.floors_to_stop_at().is_empty());
+        sensors.set_time(2.0);
+        assert_eq!(0.5, sensors.current_floor());
+        assert_eq!(0.5, sensors.current_velocity());
+        assert_eq!(&[4], sensors.floors_to_stop_at());
+        assert!(!sensors.is_emergency_stop_activated());
+        sensors.advance(0.5);
+        assert!(sensors.is_emergency_stop_activated());
+        sensors.advance(10.0);
+        assert_eq!(1.0, sensors.current_floor());
+        assert_eq!(12.5, sensors.time());
+    }
+
+    #[test]
+    fn emergency_stop_at_leveling_zone_boundary() {
+        let leeway = 0.01;
+        let motion = [
+            Keyframe {
+                time: 0.0,
+                position: 1.0 - 2.0 * leeway,
+                velocity: 0.0,
+            },
+            Keyframe {
+                time: 1.0,
+                position: 1.0,
+                velocity: 0.0,
+            },
+        ];
+        let emergency_stop = [(0.0, true)];
+        let mut sensors = ScriptedSensors::new(&motion, &[], &emergency_stop);
+        let mut controller = LiftController::new(0.5, leeway, 0.01);
+
+        // Just outside the leveling zone the lift is not at the floor, just inside it is
+        sensors.set_time(0.49);
+        assert!(!controller.poll(&sensors, 0.1).is_stopped_at_current_floor);
+        sensors.set_time(0.51);
+        assert!(controller.poll(&sensors, 0.1).is_stopped_at_current_floor);
+    }
+
+    #[test]
+    fn fuses_velocity_sources_and_flags_disagreement() {
+        let motion = [Keyframe {
+            time: 0.0,
+            position: 2.0,
+            velocity: 9.0,
+        }];
+        let mut fused =
+            FusedVelocity::new(ScriptedSensors::new(&motion, &[], &[]), [3.0, 1.0], 0.1);
+        let mut encoder = EncoderDerivative::new();
+        assert_eq!(0.0, encoder.update(2.0, 0.1));
+
+        let tachometer = 0.5;
+        let derivative = encoder.update(2.045, 0.1);
+        assert!((fused.update([tachometer, derivative]) - 0.4875).abs() < 1e-4);
+        assert!(!fused.is_fault());
+        assert_eq!(2.0, fused.current_floor());
+
+        // Selecting the tachometer alone
+        let mut selected = FusedVelocity::new(fused.sensors().clone(), [1.0, 0.0], 0.1);
+        assert_eq!(0.5, selected.update([0.5, 0.45]));
+
+        // A slipping encoder reads standstill, the moving reading wins
+        assert_eq!(-0.5, fused.update([-0.5, 0.0]));
+        assert!(fused.is_fault());
+        assert_eq!(0.5, fused.spread());
+        assert_eq!(0.2, fused.update([0.2, f32::NAN]));
+        assert!(fused.is_fault());
+    }
+}
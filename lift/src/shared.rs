@@ -0,0 +1,100 @@
+//! Sharing of a controller and its sensors between interrupt handlers and the main loop.
+//!
+//! Interrupt handlers feeding sensor samples and the main loop polling the controller must not access the
+//! same data concurrently. `SharedController` guards both with a critical section, using the
+//! `critical-section` crate, so it can be placed in a `static` on any target that provides an implementation.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+
+use crate::{Action, LiftController, LiftSensors};
+
+/// A controller and its sensors, accessible from both interrupt handlers and the main loop
+pub struct SharedController<S> {
+    inner: Mutex<RefCell<Shared<S>>>,
+}
+
+struct Shared<S> {
+    controller: LiftController,
+    sensors: S,
+}
+
+impl<S: LiftSensors> SharedController<S> {
+    pub const fn new(controller: LiftController, sensors: S) -> Self {
+        SharedController {
+            inner: Mutex::new(RefCell::new(Shared {
+                controller,
+                sensors,
+            })),
+        }
+    }
+
+    /// Update the sensor readings, typically called from the interrupt handler receiving samples
+    pub fn update_sensors<R>(&self, update: impl FnOnce(&mut S) -> R) -> R {
+        critical_section::with(|cs| update(&mut self.inner.borrow_ref_mut(cs).sensors))
+    }
+
+    /// Poll the controller with the current sensor readings.
+    ///
+    /// Interrupts are held off for the duration of the poll, which keeps the readings consistent
+    pub fn poll(&self, time_step: f32) -> Action {
+        self.with(|controller, sensors| controller.poll(sensors, time_step))
+    }
+
+    /// Access both the controller and the sensors inside a critical section
+    pub fn with<R>(&self, f: impl FnOnce(&mut LiftController, &mut S) -> R) -> R {
+        critical_section::with(|cs| {
+            let mut shared = self.inner.borrow_ref_mut(cs);
+            let Shared {
+                controller,
+                sensors,
+            } = &mut *shared;
+            f(controller, sensors)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Floor, Position, Velocity};
+
+    struct Sample {
+        position: Position,
+        floors_to_stop_at: [Floor; 1],
+    }
+
+    impl LiftSensors for Sample {
+        fn current_floor(&self) -> Position {
+            self.position
+        }
+
+        fn current_velocity(&self) -> Velocity {
+            0.0
+        }
+
+        fn floors_to_stop_at(&self) -> &[Floor] {
+            &self.floors_to_stop_at
+        }
+
+        fn is_emergency_stop_activated(&self) -> bool {
+            false
+        }
+    }
+
+    static SHARED: SharedController<Sample> = SharedController::new(
+        LiftController::new(1.0, 0.01, 0.01),
+        Sample {
+            position: 0.0,
+            floors_to_stop_at: [2],
+        },
+    );
+
+    #[test]
+    fn samples_reach_the_controller() {
+        assert!(SHARED.poll(0.1).target_velocity > 0.0);
+        SHARED.update_sensors(|sample| sample.position = 3.0);
+        assert!(SHARED.poll(0.1).target_velocity < 0.0);
+    }
+}
@@ -0,0 +1,169 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::calls::Calls;
+use crate::{Direction, Floor, Position};
+
+/// Signal for the arrival gong at a landing, given once for every stop as the lift commits to it.
+/// The gong strikes once when the lift will leave the floor upwards and twice when it will leave downwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Chime {
+    pub floor: Floor,
+    /// The direction the lift leaves the floor in, never `Neutral`
+    pub direction: Direction,
+}
+
+impl Chime {
+    /// Number of strikes of the gong
+    pub const fn strikes(&self) -> u8 {
+        match self.direction {
+            Direction::Down => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Direction lantern at a landing, lit as the lift approaches a floor it will stop at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HallLantern {
+    pub floor: Floor,
+    /// The direction the lift leaves the floor in, never `Neutral`
+    pub direction: Direction,
+}
+
+/// What a voice annunciator should say, in the order the moments come up over a trip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Announcement {
+    /// The lift is leaving `floor`, the doors close first
+    DoorsClosing { floor: Floor },
+    /// The lift is heading for `floor` as its next stop, on departure or when the stop changes on the way
+    NextStop { floor: Floor },
+    /// The lift has committed to stopping at `floor`, and will leave it in `direction`
+    Arriving { floor: Floor, direction: Direction },
+    /// The lift has stopped at `floor` and the doors open, it will leave in `direction`
+    DoorsOpening { floor: Floor, direction: Direction },
+}
+
+/// The floor shown by the position indicators in the car and at the landings.
+///
+/// The display switches to the next floor once the lift is past the midpoint between two floors by
+/// more than the hysteresis, so a lift standing or creeping close to a midpoint does not make it flicker.
+/// While the lift is levelling the last floor shown is held
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FloorIndicator {
+    floor: Option<Floor>,
+    /// Distance past the midpoint, in floors, before the display switches
+    hysteresis: Position,
+}
+
+impl FloorIndicator {
+    pub const DEFAULT_HYSTERESIS: Position = 0.1;
+
+    /// An indicator showing nothing until the first update
+    pub const fn new(hysteresis: Position) -> Self {
+        FloorIndicator {
+            floor: None,
+            hysteresis,
+        }
+    }
+
+    /// Update the display from the position of the lift and return the floor to show.
+    /// The first update shows the nearest floor
+    pub fn update(&mut self, position: Position, levelling: bool) -> Floor {
+        let nearest = position.round() as Floor;
+        let floor = match self.floor {
+            Some(shown) if levelling => shown,
+            Some(shown) if (position - shown as Position).abs() < 0.5 + self.hysteresis => shown,
+            _ => nearest,
+        };
+        self.floor = Some(floor);
+        floor
+    }
+
+    /// The floor shown, nothing before the first update
+    pub fn floor(&self) -> Option<Floor> {
+        self.floor
+    }
+
+    pub fn hysteresis(&self) -> Position {
+        self.hysteresis
+    }
+
+    /// Change the hysteresis, keeping the floor shown
+    pub fn set_hysteresis(&mut self, hysteresis: Position) {
+        self.hysteresis = hysteresis;
+    }
+}
+
+impl Default for FloorIndicator {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_HYSTERESIS)
+    }
+}
+
+/// The direction the lift leaves `floor` in after stopping there, having arrived travelling in `arriving`.
+/// It continues if there are calls further on, turns if there are only calls behind it, and otherwise
+/// keeps the direction it arrived in
+pub(crate) fn onward_direction<C: Calls + ?Sized>(
+    floor: Floor,
+    arriving: Direction,
+    calls: &C,
+) -> Direction {
+    let above = calls.nearest_floor_above(floor.saturating_add(1)).is_some();
+    let below = calls.nearest_floor_below(floor.saturating_sub(1)).is_some();
+    match (arriving, above, below) {
+        (Direction::Up, false, true) => Direction::Down,
+        (Direction::Down, true, false) => Direction::Up,
+        (Direction::Neutral, false, true) => Direction::Down,
+        (Direction::Neutral, _, _) => Direction::Up,
+        (arriving, _, _) => arriving,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indicator_switches_past_midpoint_and_holds_while_levelling() {
+        let mut indicator = FloorIndicator::default();
+        assert_eq!(None, indicator.floor());
+        assert_eq!(2, indicator.update(2.45, false));
+        assert_eq!(2, indicator.update(2.55, false));
+        assert_eq!(2, indicator.update(2.45, false));
+        assert_eq!(3, indicator.update(2.65, false));
+        assert_eq!(3, indicator.update(2.45, false));
+        assert_eq!(3, indicator.update(2.3, true));
+        assert_eq!(2, indicator.update(2.3, false));
+        // Several floors at once, such as after a long time step
+        assert_eq!(6, indicator.update(6.2, false));
+    }
+
+    #[test]
+    fn continues_then_turns() {
+        let calls: &[Floor] = &[1, 6];
+        assert_eq!(Direction::Up, onward_direction(4, Direction::Up, calls));
+        assert_eq!(Direction::Down, onward_direction(4, Direction::Down, calls));
+        assert_eq!(Direction::Down, onward_direction(6, Direction::Up, calls));
+        assert_eq!(Direction::Up, onward_direction(1, Direction::Down, calls));
+        assert_eq!(
+            Direction::Down,
+            onward_direction(1, Direction::Down, &[1][..])
+        );
+        assert_eq!(
+            2,
+            Chime {
+                floor: 1,
+                direction: Direction::Down
+            }
+            .strikes()
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Simulation of a lift, for testing controllers and the planners built on them.
+//!
+//! `SimulatedLift` is a model of a car that every consumer of the crate can share, with optional
+//! physics, imperfect sensors, faults and passengers, which `TrafficGenerator` can spawn following
+//! standard traffic patterns. `check_properties` is a property-based harness that runs a `Planner`
+//! in random buildings. Demand can also be replayed from a building's `CallLog`, and a `Recorder`
+//! captures what a planner saw so the run can be replayed later. A `Timeline` of the car's state
+//! exports to CSV or JSON for plotting. A `Scenario` describes a whole run as data, including the
+//! expectations it must meet. `DispatchEnv` wraps several cars as a reinforcement learning
+//! environment for training dispatch policies.
+//!
+//! Everything random draws from a seeded `Rng`, so a simulation is reproducible from its seeds.
+
+#[cfg(any(feature = "alloc", test))]
+mod call_log;
+#[cfg(any(feature = "alloc", test))]
+mod comfort;
+#[cfg(any(feature = "alloc", test))]
+mod compare;
+#[cfg(any(feature = "alloc", test))]
+mod dispatch_env;
+#[cfg(any(feature = "alloc", test))]
+mod doors;
+#[cfg(any(feature = "alloc", test))]
+mod dwell;
+#[cfg(any(feature = "alloc", test))]
+mod energy;
+#[cfg(any(feature = "alloc", test))]
+mod faults;
+#[cfg(any(feature = "alloc", test))]
+mod metrics;
+#[cfg(any(feature = "alloc", test))]
+mod passengers;
+#[cfg(any(feature = "alloc", test))]
+mod physics;
+#[cfg(any(feature = "alloc", test))]
+mod plant;
+mod property;
+mod rng;
+#[cfg(any(feature = "alloc", test))]
+mod scenario;
+#[cfg(any(feature = "alloc", test))]
+mod sensors;
+#[cfg(any(feature = "alloc", test))]
+mod timeline;
+#[cfg(any(feature = "alloc", test))]
+mod trace;
+#[cfg(any(feature = "alloc", test))]
+mod traffic;
+
+#[cfg(any(feature = "alloc", test))]
+pub use call_log::{CallLog, CallLogError, CallLogReplay, LoggedCall};
+#[cfg(any(feature = "alloc", test))]
+pub use comfort::{ComfortMeter, ComfortReport};
+#[cfg(any(feature = "alloc", test))]
+pub use compare::{compare_policies, run_policy, Comparison, ComparisonScenario, PolicyResult};
+#[cfg(any(feature = "alloc", test))]
+pub use dispatch_env::{
+    CarObservation, DispatchConfig, DispatchEnv, InvalidCar, Observation, StepResult,
+};
+#[cfg(any(feature = "alloc", test))]
+pub use doors::DoorState;
+#[cfg(any(feature = "alloc", test))]
+pub use dwell::{
+    hall_call_notification_time, DwellModel, ACCESSIBLE_WALKING_SPEED, MIN_DOOR_OPEN_TIME,
+    MIN_NOTIFICATION_TIME,
+};
+#[cfg(any(feature = "alloc", test))]
+pub use energy::{EnergyMeter, EnergyModel, EnergyReport};
+#[cfg(any(feature = "alloc", test))]
+pub use faults::Fault;
+#[cfg(any(feature = "alloc", test))]
+pub use metrics::{Metrics, MetricsReport, Summary};
+#[cfg(any(feature = "alloc", test))]
+pub use passengers::{Passenger, PassengerId, Trip};
+#[cfg(any(feature = "alloc", test))]
+pub use physics::PhysicsConfig;
+#[cfg(any(feature = "alloc", test))]
+pub use plant::SimulatedLift;
+pub use property::{check_properties, Planner, PropertyConfig, PropertyFailure, MAX_CALLS};
+pub use rng::Rng;
+#[cfg(any(feature = "alloc", test))]
+pub use scenario::{
+    BuildingConfig, CarConfig, ControllerConfig, Expectation, ExpectationFailure, Scenario,
+    ScenarioError, ScenarioOutcome, ScriptPlayer, ScriptedAction, ScriptedEvent,
+};
+#[cfg(any(feature = "alloc", test))]
+pub use sensors::{LatencyModel, NoiseModel};
+#[cfg(any(feature = "alloc", test))]
+pub use timeline::{Timeline, TimelinePoint};
+#[cfg(any(feature = "alloc", test))]
+pub use trace::{Divergence, Recorder, SensorReadings, Trace, TraceSample};
+#[cfg(any(feature = "alloc", test))]
+pub use traffic::{TrafficConfig, TrafficGenerator, TrafficPattern};
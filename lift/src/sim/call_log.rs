@@ -0,0 +1,246 @@
+//! Call logs exported from real buildings, replayed as simulated demand.
+//!
+//! The CSV form has one call per line with the columns `timestamp,floor,direction,destination`.
+//! Timestamps are seconds, direction is `up`, `down` or empty, and destination may be empty
+//! for buildings without destination dispatch. A header line, empty lines and lines starting
+//! with `#` are skipped. The JSON form, behind the `json` feature, is an array of `LoggedCall`.
+
+use alloc::vec::Vec;
+
+use super::plant::SimulatedLift;
+use crate::{Direction, Floor};
+
+/// One call from a building's log
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoggedCall {
+    /// Seconds, only the differences between calls matter
+    pub timestamp: f32,
+    pub floor: Floor,
+    pub direction: Option<Direction>,
+    pub destination: Option<Floor>,
+}
+
+/// Error while loading a call log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallLogError {
+    /// A field was missing or could not be parsed, lines count from 1
+    InvalidField { line: usize, field: &'static str },
+    /// The JSON was malformed or did not match `LoggedCall`
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// Calls from a log, sorted by timestamp
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallLog {
+    calls: Vec<LoggedCall>,
+}
+
+impl CallLog {
+    pub fn new(mut calls: Vec<LoggedCall>) -> Self {
+        calls.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        CallLog { calls }
+    }
+
+    pub fn from_csv(csv: &str) -> Result<Self, CallLogError> {
+        let mut calls = Vec::new();
+        for (index, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split(',').map(str::trim);
+            let timestamp = fields.next().unwrap_or_default();
+            if index == 0 && timestamp.parse::<f32>().is_err() {
+                continue;
+            }
+            let invalid = |field| CallLogError::InvalidField {
+                line: index + 1,
+                field,
+            };
+
+            let timestamp = timestamp
+                .parse::<f32>()
+                .ok()
+                .filter(|t| t.is_finite())
+                .ok_or(invalid("timestamp"))?;
+            let floor = fields
+                .next()
+                .and_then(|f| f.parse().ok())
+                .ok_or(invalid("floor"))?;
+            let direction = match fields.next().unwrap_or_default() {
+                "" => None,
+                d if d.eq_ignore_ascii_case("up") => Some(Direction::Up),
+                d if d.eq_ignore_ascii_case("down") => Some(Direction::Down),
+                _ => return Err(invalid("direction")),
+            };
+            let destination = match fields.next().unwrap_or_default() {
+                "" => None,
+                d => Some(d.parse().map_err(|_| invalid("destination"))?),
+            };
+
+            calls.push(LoggedCall {
+                timestamp,
+                floor,
+                direction,
+                destination,
+            });
+        }
+        Ok(Self::new(calls))
+    }
+
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, CallLogError> {
+        serde_json::from_str(json)
+            .map(Self::new)
+            .map_err(|_| CallLogError::Json)
+    }
+
+    pub fn calls(&self) -> &[LoggedCall] {
+        &self.calls
+    }
+
+    /// Seconds between the first and last call
+    pub fn duration(&self) -> f32 {
+        match (self.calls.first(), self.calls.last()) {
+            (Some(first), Some(last)) => last.timestamp - first.timestamp,
+            _ => 0.0,
+        }
+    }
+
+    /// Replay the log from its first call
+    pub fn replay(&self) -> CallLogReplay<'_> {
+        CallLogReplay {
+            log: self,
+            next: 0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+/// Feeds the calls of a log into a simulated lift as simulated time passes
+#[derive(Debug, Clone)]
+pub struct CallLogReplay<'a> {
+    log: &'a CallLog,
+    next: usize,
+    elapsed: f32,
+}
+
+impl CallLogReplay<'_> {
+    /// True once every call has been fed
+    pub fn is_finished(&self) -> bool {
+        self.next == self.log.calls.len()
+    }
+
+    /// Advance by `time_step` and feed the calls that happened in it, returns how many were fed.
+    /// Calls with a destination spawn a passenger, the others only register a call at their floor.
+    pub fn feed(&mut self, lift: &mut SimulatedLift, time_step: f32) -> usize {
+        let start = match self.log.calls.first() {
+            Some(first) => first.timestamp,
+            None => return 0,
+        };
+        self.elapsed += time_step;
+
+        let due = self.log.calls[self.next..]
+            .iter()
+            .take_while(|call| call.timestamp - start < self.elapsed)
+            .count();
+        for call in &self.log.calls[self.next..self.next + due] {
+            match call.destination {
+                Some(destination) => {
+                    lift.spawn_passenger(call.floor, destination);
+                }
+                None => {
+                    lift.register_call(call.floor);
+                }
+            }
+        }
+        self.next += due;
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "timestamp,floor,direction,destination
+# morning
+30.5, 0, up, 4
+
+12,3,Down,
+40,2,,";
+
+    #[test]
+    fn parses_csv() {
+        let log = CallLog::from_csv(CSV).unwrap();
+        assert_eq!(
+            &[
+                LoggedCall {
+                    timestamp: 12.0,
+                    floor: 3,
+                    direction: Some(Direction::Down),
+                    destination: None
+                },
+                LoggedCall {
+                    timestamp: 30.5,
+                    floor: 0,
+                    direction: Some(Direction::Up),
+                    destination: Some(4)
+                },
+                LoggedCall {
+                    timestamp: 40.0,
+                    floor: 2,
+                    direction: None,
+                    destination: None
+                },
+            ][..],
+            log.calls()
+        );
+        assert_eq!(28.0, log.duration());
+
+        assert_eq!(
+            Err(CallLogError::InvalidField {
+                line: 2,
+                field: "direction"
+            }),
+            CallLog::from_csv("0,1,up,2\n5,1,sideways,2")
+        );
+        assert_eq!(
+            Err(CallLogError::InvalidField {
+                line: 1,
+                field: "floor"
+            }),
+            CallLog::from_csv("0")
+        );
+    }
+
+    #[test]
+    fn replays_into_simulation() {
+        let log = CallLog::from_csv(CSV).unwrap();
+        let mut replay = log.replay();
+        let mut lift = SimulatedLift::new(0.0);
+
+        assert_eq!(1, replay.feed(&mut lift, 1.0));
+        assert_eq!(&[3][..], lift.calls());
+        assert_eq!(0, replay.feed(&mut lift, 10.0));
+        assert_eq!(1, replay.feed(&mut lift, 10.0));
+        assert_eq!(1, lift.waiting().len());
+        assert_eq!(1, replay.feed(&mut lift, 10.0));
+        assert!(replay.is_finished());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn parses_json() {
+        let log = CallLog::from_json(
+            r#"[{"timestamp": 5.0, "floor": 1, "direction": "Up", "destination": 3},
+                {"timestamp": 1.0, "floor": 2, "direction": null, "destination": null}]"#,
+        )
+        .unwrap();
+        assert_eq!(2, log.calls()[0].floor);
+        assert_eq!(Some(3), log.calls()[1].destination);
+        assert_eq!(Err(CallLogError::Json), CallLog::from_json("{"));
+    }
+}
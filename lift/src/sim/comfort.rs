@@ -0,0 +1,150 @@
+use alloc::vec::Vec;
+
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use super::passengers::Trip;
+use super::plant::SimulatedLift;
+use crate::Velocity;
+
+/// Ride quality over a stretch of a trajectory, in SI units
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComfortReport {
+    /// Highest magnitude of acceleration in m/s²
+    pub peak_acceleration: f32,
+    /// Highest magnitude of jerk in m/s³
+    pub peak_jerk: f32,
+    /// Root mean square of the acceleration in m/s², a proxy for the vibration felt in the car
+    pub rms_acceleration: f32,
+}
+
+impl ComfortReport {
+    /// Ride quality of a trajectory given as `(time, velocity)` samples in seconds and floors per
+    /// second, such as those of a `Timeline` or a recorded trace. Samples must be in time order,
+    /// samples at the same time are skipped
+    pub fn of(samples: &[(f32, Velocity)], floor_height: f32) -> Self {
+        let mut report = ComfortReport::default();
+        let mut squares = 0.0;
+        let mut duration = 0.0;
+        let mut last_acceleration = None;
+
+        for pair in samples.windows(2) {
+            let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+            let dt = t1 - t0;
+            if dt <= 0.0 {
+                continue;
+            }
+
+            let acceleration = (v1 - v0) * floor_height / dt;
+            report.peak_acceleration = report.peak_acceleration.max(acceleration.abs());
+            squares += acceleration * acceleration * dt;
+            duration += dt;
+
+            if let Some(last) = last_acceleration {
+                let jerk: f32 = (acceleration - last) / dt;
+                report.peak_jerk = report.peak_jerk.max(jerk.abs());
+            }
+            last_acceleration = Some(acceleration);
+        }
+
+        if duration > 0.0 {
+            report.rms_acceleration = (squares / duration).sqrt();
+        }
+        report
+    }
+}
+
+/// Records the velocity of a `SimulatedLift`, call `record` after every step
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComfortMeter {
+    floor_height: f32,
+    samples: Vec<(f32, Velocity)>,
+}
+
+impl ComfortMeter {
+    /// Floor height in meters, to convert from floors to SI units
+    pub fn new(floor_height: f32) -> Self {
+        ComfortMeter {
+            floor_height,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, lift: &SimulatedLift) {
+        self.samples.push((lift.time(), lift.velocity()));
+    }
+
+    /// Ride quality over the whole run
+    pub fn report(&self) -> ComfortReport {
+        ComfortReport::of(&self.samples, self.floor_height)
+    }
+
+    /// Ride quality between boarding and alighting, as felt by the passenger of a trip
+    pub fn trip(&self, trip: &Trip) -> ComfortReport {
+        let start = self.samples.partition_point(|(t, _)| *t < trip.boarded_at);
+        let end = self
+            .samples
+            .partition_point(|(t, _)| *t <= trip.alighted_at);
+        ComfortReport::of(&self.samples[start..end], self.floor_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::PhysicsConfig;
+    use crate::LiftController;
+
+    #[test]
+    fn trapezoid_profile() {
+        // Accelerate at 1 m/s² for a second, cruise, then brake at 2 m/s² for half a second
+        let samples: Vec<(f32, Velocity)> = (0..=40)
+            .map(|i| {
+                let t = i as f32 * 0.1;
+                let v = if t <= 1.0 {
+                    t
+                } else if t <= 3.5 {
+                    1.0
+                } else {
+                    1.0 - 2.0 * (t - 3.5)
+                };
+                (t, v)
+            })
+            .collect();
+
+        let report = ComfortReport::of(&samples, 1.0);
+        assert!((report.peak_acceleration - 2.0).abs() < 1e-3);
+        assert!((report.peak_jerk - 20.0).abs() < 1e-2);
+        // sqrt((1² · 1 + 2² · 0.5) / 4)
+        assert!((report.rms_acceleration - 0.75f32.sqrt()).abs() < 1e-3);
+        assert_eq!(
+            ComfortReport::default(),
+            ComfortReport::of(&samples[..1], 1.0)
+        );
+    }
+
+    #[test]
+    fn physics_rides_smoother_than_kinematics() {
+        let ride = |physics: Option<PhysicsConfig>| {
+            let mut lift = SimulatedLift::new(0.0);
+            lift.set_physics(physics);
+            lift.spawn_passenger(0, 3);
+            let mut controller = LiftController::new(0.5, 0.01, 0.01);
+            let mut meter = ComfortMeter::new(3.0);
+            for _ in 0..300 {
+                let action = controller.poll(&lift, 0.1);
+                lift.accept_action(action, 0.1);
+                meter.record(&lift);
+            }
+            let trip = lift.completed_trips()[0];
+            meter.trip(&trip)
+        };
+
+        let kinematic = ride(None);
+        let physical = ride(Some(PhysicsConfig::default()));
+        assert!(kinematic.peak_acceleration > 10.0);
+        assert!(physical.peak_acceleration < kinematic.peak_acceleration);
+        assert!(physical.rms_acceleration < kinematic.rms_acceleration);
+    }
+}
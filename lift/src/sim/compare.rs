@@ -0,0 +1,167 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::metrics::{Metrics, MetricsReport};
+use super::plant::SimulatedLift;
+use super::property::Planner;
+use super::traffic::{TrafficConfig, TrafficGenerator};
+use crate::Floor;
+
+/// Seeded traffic scenario that every policy in a comparison is run against
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComparisonScenario {
+    pub traffic: TrafficConfig,
+    pub seed: u64,
+    /// Seconds during which passengers arrive
+    pub duration: f32,
+    /// Seconds after `duration` to let the car serve those still waiting or riding
+    pub drain: f32,
+    pub time_step: f32,
+    pub capacity: usize,
+    pub initial_floor: Floor,
+}
+
+/// Result of running one policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyResult<'a> {
+    pub name: &'a str,
+    pub metrics: MetricsReport,
+    /// Passengers still waiting or riding when the run ended
+    pub unserved: usize,
+}
+
+/// Results of running several policies against the same scenario, displayed as a table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison<'a> {
+    pub results: Vec<PolicyResult<'a>>,
+}
+
+/// Run a policy against the scenario and collect its metrics
+pub fn run_policy(
+    scenario: &ComparisonScenario,
+    planner: &mut dyn Planner,
+) -> (MetricsReport, usize) {
+    let mut lift = SimulatedLift::new(scenario.initial_floor as f32);
+    lift.set_capacity(scenario.capacity);
+    let mut traffic = TrafficGenerator::new(scenario.traffic.clone(), scenario.seed);
+    let mut metrics = Metrics::new();
+
+    let steps = ((scenario.duration + scenario.drain) / scenario.time_step) as u32;
+    for step in 0..steps {
+        if step as f32 * scenario.time_step < scenario.duration {
+            traffic.feed(&mut lift, scenario.time_step);
+        }
+        let action = planner.plan(&lift, scenario.time_step);
+        lift.accept_action(action, scenario.time_step);
+        metrics.record(&lift, scenario.time_step);
+    }
+
+    (metrics.report(), lift.waiting().len() + lift.riders().len())
+}
+
+/// Run every policy against the same seeded scenario, so differences come from the policies alone
+pub fn compare_policies<'a, I>(scenario: &ComparisonScenario, policies: I) -> Comparison<'a>
+where
+    I: IntoIterator<Item = (&'a str, &'a mut dyn Planner)>,
+{
+    let results = policies
+        .into_iter()
+        .map(|(name, planner)| {
+            let (metrics, unserved) = run_policy(scenario, planner);
+            PolicyResult {
+                name,
+                metrics,
+                unserved,
+            }
+        })
+        .collect();
+    Comparison { results }
+}
+
+impl fmt::Display for Comparison<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:>6} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>6} {:>8} {:>5} {:>7} {:>8}",
+            "policy",
+            "trips",
+            "wait avg",
+            "wait p95",
+            "wait max",
+            "jrny avg",
+            "jrny p95",
+            "jrny max",
+            "stops",
+            "distance",
+            "load",
+            "net Wh",
+            "unserved"
+        )?;
+        for result in &self.results {
+            let m = &result.metrics;
+            writeln!(
+                f,
+                "{:<16} {:>6} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>6} {:>8.1} {:>5.2} {:>7.1} {:>8}",
+                result.name,
+                m.journey_time.count,
+                m.wait_time.mean,
+                m.wait_time.p95,
+                m.wait_time.max,
+                m.journey_time.mean,
+                m.journey_time.p95,
+                m.journey_time.max,
+                m.stops,
+                m.distance,
+                m.load_factor,
+                m.energy.net(),
+                result.unserved
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::TrafficPattern;
+    use crate::LiftController;
+    use alloc::string::ToString;
+
+    #[test]
+    fn faster_car_wins() {
+        let scenario = ComparisonScenario {
+            traffic: TrafficConfig {
+                pattern: TrafficPattern::UpPeak,
+                arrival_rate: 4.0,
+                lowest_floor: 0,
+                lobby: 0,
+                populations: alloc::vec![0, 20, 20, 20, 20, 20],
+            },
+            seed: 11,
+            duration: 600.0,
+            drain: 300.0,
+            time_step: 0.1,
+            capacity: 8,
+            initial_floor: 0,
+        };
+        let mut fast = LiftController::new(1.0, 0.01, 0.01);
+        let mut slow = LiftController::new(0.5, 0.01, 0.01);
+        let policies: [(&str, &mut dyn Planner); 2] = [("fast", &mut fast), ("slow", &mut slow)];
+
+        let comparison = compare_policies(&scenario, policies);
+        let (fast, slow) = (&comparison.results[0], &comparison.results[1]);
+        assert_eq!(
+            fast.metrics.journey_time.count,
+            slow.metrics.journey_time.count
+        );
+        assert!(fast.metrics.journey_time.count > 0);
+        assert_eq!(0, fast.unserved);
+        assert!(fast.metrics.journey_time.mean < slow.metrics.journey_time.mean);
+
+        let table = comparison.to_string();
+        assert_eq!(3, table.lines().count());
+        assert!(table.lines().nth(2).unwrap().starts_with("slow"));
+    }
+}
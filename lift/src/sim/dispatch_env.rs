@@ -0,0 +1,248 @@
+//! Reinforcement learning environment for dispatching calls between cars, following the gym
+//! convention of `reset(seed)` and `step(action)`.
+//!
+//! Passengers arrive following a `TrafficConfig`. Whenever one is waiting for an assignment the
+//! environment stops and the agent picks the car that serves it. Between assignments every car
+//! runs its own `LiftController`, and the reward is minus the seconds waited by all passengers,
+//! so maximising the return minimises the total wait time.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use super::plant::SimulatedLift;
+use super::scenario::ControllerConfig;
+use super::traffic::{TrafficConfig, TrafficGenerator};
+use crate::{Floor, LiftController, Position, Velocity};
+
+/// Configuration of a dispatch environment
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DispatchConfig {
+    /// Number of cars, all starting at the lobby
+    pub cars: usize,
+    pub capacity: usize,
+    pub controller: ControllerConfig,
+    pub traffic: TrafficConfig,
+    pub time_step: f32,
+    /// Seconds of simulated time in an episode
+    pub duration: f32,
+}
+
+/// What the agent sees of a car
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CarObservation {
+    pub position: Position,
+    pub velocity: Velocity,
+    /// Floors the car has calls for
+    pub calls: usize,
+    /// Passengers assigned to the car and still waiting for it
+    pub waiting: usize,
+    pub riders: usize,
+}
+
+/// What the agent sees after a reset or a step
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Observation {
+    pub time: f32,
+    pub cars: Vec<CarObservation>,
+    /// Origin and destination of the passenger to assign, `None` once the episode is done
+    pub call: Option<(Floor, Floor)>,
+}
+
+/// Result of a step
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepResult {
+    pub observation: Observation,
+    /// Minus the seconds waited by all passengers since the previous step
+    pub reward: f32,
+    pub done: bool,
+}
+
+/// The action named a car that does not exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidCar(pub usize);
+
+/// Environment assigning each arriving passenger to a car
+#[derive(Debug)]
+pub struct DispatchEnv {
+    config: DispatchConfig,
+    cars: Vec<(SimulatedLift, LiftController)>,
+    traffic: TrafficGenerator,
+    unassigned: VecDeque<(Floor, Floor)>,
+    time: f32,
+}
+
+impl DispatchEnv {
+    /// Create an environment, ready for its first episode with seed 0
+    pub fn new(config: DispatchConfig) -> Self {
+        let traffic = TrafficGenerator::new(config.traffic.clone(), 0);
+        let mut env = DispatchEnv {
+            config,
+            cars: Vec::new(),
+            traffic,
+            unassigned: VecDeque::new(),
+            time: 0.0,
+        };
+        env.reset(0);
+        env
+    }
+
+    pub fn config(&self) -> &DispatchConfig {
+        &self.config
+    }
+
+    /// Number of possible actions, one per car
+    pub fn action_space(&self) -> usize {
+        self.config.cars
+    }
+
+    /// Start a new episode, the traffic of an episode is determined by its seed
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let lobby = self.config.traffic.lobby as Position;
+        self.cars = (0..self.config.cars)
+            .map(|_| {
+                let mut lift = SimulatedLift::new(lobby);
+                lift.set_capacity(self.config.capacity);
+                (lift, self.config.controller.build())
+            })
+            .collect();
+        self.traffic = TrafficGenerator::new(self.config.traffic.clone(), seed);
+        self.unassigned.clear();
+        self.time = 0.0;
+        self.advance();
+        self.observation()
+    }
+
+    /// Assign the current call to `car` and run until the next call needs an assignment or the
+    /// episode ends. Once done, steps only return the final observation.
+    pub fn step(&mut self, car: usize) -> Result<StepResult, InvalidCar> {
+        if car >= self.cars.len() {
+            return Err(InvalidCar(car));
+        }
+        if let Some((origin, destination)) = self.unassigned.pop_front() {
+            self.cars[car].0.spawn_passenger(origin, destination);
+        }
+
+        let reward = -self.advance();
+        Ok(StepResult {
+            observation: self.observation(),
+            reward,
+            done: self.is_done(),
+        })
+    }
+
+    fn is_done(&self) -> bool {
+        self.unassigned.is_empty() && self.time >= self.config.duration
+    }
+
+    /// Simulate until a call is unassigned or the episode ends, returns the seconds waited
+    fn advance(&mut self) -> f32 {
+        let time_step = self.config.time_step;
+        let mut waited = 0.0;
+        while self.unassigned.is_empty() && self.time < self.config.duration {
+            for (lift, controller) in &mut self.cars {
+                let action = controller.poll(lift, time_step);
+                lift.accept_action(action, time_step);
+                waited += lift.waiting().len() as f32 * time_step;
+            }
+            self.unassigned.extend(self.traffic.arrivals(time_step));
+            self.time += time_step;
+        }
+        waited
+    }
+
+    fn observation(&self) -> Observation {
+        Observation {
+            time: self.time,
+            cars: self
+                .cars
+                .iter()
+                .map(|(lift, _)| CarObservation {
+                    position: lift.position(),
+                    velocity: lift.velocity(),
+                    calls: lift.calls().len(),
+                    waiting: lift.waiting().len(),
+                    riders: lift.riders().len(),
+                })
+                .collect(),
+            call: self.unassigned.front().copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::TrafficPattern;
+
+    fn env() -> DispatchEnv {
+        DispatchEnv::new(DispatchConfig {
+            cars: 3,
+            capacity: 8,
+            controller: ControllerConfig {
+                prefered_velocity: 1.0,
+                floor_leeway: 0.01,
+                velocity_epsilon: 0.01,
+            },
+            traffic: TrafficConfig {
+                pattern: TrafficPattern::Uniform,
+                arrival_rate: 6.0,
+                lowest_floor: 0,
+                lobby: 0,
+                populations: alloc::vec![20; 10],
+            },
+            time_step: 0.1,
+            duration: 300.0,
+        })
+    }
+
+    /// Run an episode and return its total reward
+    fn episode(env: &mut DispatchEnv, seed: u64, policy: impl Fn(&Observation) -> usize) -> f32 {
+        let mut observation = env.reset(seed);
+        let mut total = 0.0;
+        loop {
+            let result = env.step(policy(&observation)).unwrap();
+            total += result.reward;
+            if result.done {
+                return total;
+            }
+            observation = result.observation;
+        }
+    }
+
+    fn nearest(observation: &Observation) -> usize {
+        let (origin, _) = observation.call.unwrap();
+        (0..observation.cars.len())
+            .min_by(|a, b| {
+                let distance = |car: &usize| {
+                    let car = &observation.cars[*car];
+                    (car.position - origin as Position).abs() + car.calls as f32
+                };
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn episodes_are_reproducible() {
+        let mut env = env();
+        assert_eq!(3, env.action_space());
+        let first = env.reset(5);
+        assert!(first.call.is_some());
+        assert_eq!(Err(InvalidCar(3)), env.step(3));
+        assert_eq!(first, env.reset(5));
+        assert_eq!(episode(&mut env, 5, |_| 1), episode(&mut env, 5, |_| 1));
+    }
+
+    #[test]
+    fn spreading_calls_waits_less() {
+        let mut env = env();
+        let single = episode(&mut env, 2, |_| 0);
+        let spread = episode(&mut env, 2, nearest);
+        assert!(single < 0.0);
+        assert!(spread > single);
+    }
+}
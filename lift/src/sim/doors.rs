@@ -0,0 +1,114 @@
+/// Motion of the car doors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+/// The doors over one dwell at a landing. They open over half of the door time, stay open while
+/// passengers board and alight, and close over the other half
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct DoorCycle {
+    /// Seconds to open, and to close, the doors
+    travel: f32,
+    /// Seconds of the whole cycle
+    duration: f32,
+    elapsed: f32,
+}
+
+impl DoorCycle {
+    pub const CLOSED: DoorCycle = DoorCycle {
+        travel: 0.0,
+        duration: 0.0,
+        elapsed: 0.0,
+    };
+
+    /// A cycle lasting `dwell` seconds. In a dwell shorter than the door time the doors move
+    /// faster, and start closing as soon as they are open
+    pub fn new(door_time: f32, dwell: f32) -> Self {
+        DoorCycle {
+            travel: (door_time / 2.0).min(dwell / 2.0).max(0.0),
+            duration: dwell.max(0.0),
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn step(&mut self, time_step: f32) {
+        self.elapsed += time_step;
+        // Round away what is left after summing up time steps, so the cycle ends on time
+        if self.remaining() < 1e-4 {
+            self.elapsed = self.duration;
+        }
+    }
+
+    /// Seconds until the doors are closed
+    pub fn remaining(&self) -> f32 {
+        (self.duration - self.elapsed).max(0.0)
+    }
+
+    pub fn state(&self) -> DoorState {
+        let remaining = self.remaining();
+        if remaining == 0.0 {
+            DoorState::Closed
+        } else if self.elapsed < self.travel {
+            DoorState::Opening
+        } else if remaining < self.travel {
+            DoorState::Closing
+        } else {
+            DoorState::Open
+        }
+    }
+
+    /// How far the doors are open, from 0 when closed to 1 when fully open
+    pub fn open_fraction(&self) -> f32 {
+        match self.state() {
+            DoorState::Closed => 0.0,
+            DoorState::Open => 1.0,
+            DoorState::Opening => self.elapsed / self.travel,
+            DoorState::Closing => self.remaining() / self.travel,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_holds_and_closes() {
+        let mut doors = DoorCycle::new(4.0, 6.0);
+        let mut states = [DoorState::Closed; 60];
+        let mut fractions = [0.0; 60];
+        for i in 0..60 {
+            states[i] = doors.state();
+            fractions[i] = doors.open_fraction();
+            doors.step(0.1);
+        }
+
+        assert_eq!(DoorState::Opening, states[0]);
+        assert!((fractions[10] - 0.5).abs() < 1e-4);
+        assert_eq!(DoorState::Open, states[25]);
+        assert_eq!(1.0, fractions[25]);
+        assert_eq!(DoorState::Closing, states[45]);
+        assert!((fractions[50] - 0.5).abs() < 1e-4);
+        assert_eq!(DoorState::Closed, doors.state());
+        assert_eq!(0.0, doors.remaining());
+    }
+
+    #[test]
+    fn short_dwell_shortens_door_travel() {
+        let mut doors = DoorCycle::new(4.0, 1.0);
+        doors.step(0.5);
+        assert_eq!(DoorState::Open, doors.state());
+        assert_eq!(1.0, doors.open_fraction());
+        doors.step(0.25);
+        assert_eq!(DoorState::Closing, doors.state());
+        assert!((doors.open_fraction() - 0.5).abs() < 1e-4);
+        assert_eq!(DoorState::Closed, DoorCycle::CLOSED.state());
+    }
+}
@@ -0,0 +1,92 @@
+/// Walking speed the accessibility rules assume for reaching the car from a hall call button, in m/s (1.5 ft/s)
+pub const ACCESSIBLE_WALKING_SPEED: f32 = 0.457;
+
+/// Shortest time from the notification that a car answers a hall call until its doors start to close, in seconds
+pub const MIN_NOTIFICATION_TIME: f32 = 5.0;
+
+/// Shortest time the doors stay fully open at a stop, in seconds
+pub const MIN_DOOR_OPEN_TIME: f32 = 3.0;
+
+/// Seconds from the notification that a car answers a hall call, by its lantern and chime, until its doors may
+/// start to close, for a hall call button `distance` metres from the car entrance. This is the time the
+/// accessibility rules for lifts (ADA) give a passenger to walk over, and never less than `MIN_NOTIFICATION_TIME`
+pub fn hall_call_notification_time(distance: f32) -> f32 {
+    (distance / ACCESSIBLE_WALKING_SPEED).max(MIN_NOTIFICATION_TIME)
+}
+
+/// How long the car stands at a landing, depending on how many passengers board and alight
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DwellModel {
+    /// Seconds to open and close the doors, spent on every stop
+    pub door_time: f32,
+    /// Seconds per passenger boarding the car
+    pub per_boarding: f32,
+    /// Seconds per passenger alighting from the car
+    pub per_alighting: f32,
+    /// Metres from the farthest hall call button to the car entrance. When set, every dwell is long enough
+    /// for the accessible door timing, see `minimum_dwell`
+    pub hall_call_distance: Option<f32>,
+}
+
+impl DwellModel {
+    /// Typical figures for a passenger lift with centre opening doors
+    pub const TYPICAL: DwellModel = DwellModel {
+        door_time: 4.0,
+        per_boarding: 1.5,
+        per_alighting: 1.2,
+        hall_call_distance: None,
+    };
+
+    /// Seconds the car stands at the landing for the given passenger exchange
+    pub fn dwell_time(&self, boarding: u32, alighting: u32) -> f32 {
+        let exchange = self.door_time
+            + boarding as f32 * self.per_boarding
+            + alighting as f32 * self.per_alighting;
+        exchange.max(self.minimum_dwell())
+    }
+
+    /// Shortest dwell the accessible door timing allows, zero without `hall_call_distance`.
+    /// The doors must not start closing before the hall call notification time has passed since they started
+    /// opening, and must stay fully open for at least `MIN_DOOR_OPEN_TIME`
+    pub fn minimum_dwell(&self) -> f32 {
+        match self.hall_call_distance {
+            Some(distance) => {
+                let travel = self.door_time / 2.0;
+                (hall_call_notification_time(distance) + travel)
+                    .max(MIN_DOOR_OPEN_TIME + 2.0 * travel)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+impl Default for DwellModel {
+    fn default() -> Self {
+        Self::TYPICAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accessible_timing_lengthens_short_dwells() {
+        assert_eq!(5.0, hall_call_notification_time(1.0));
+        assert!((hall_call_notification_time(4.57) - 10.0).abs() < 1e-4);
+
+        let mut dwell = DwellModel::TYPICAL;
+        assert_eq!(4.0, dwell.dwell_time(0, 0));
+        dwell.hall_call_distance = Some(4.57);
+        assert!((dwell.minimum_dwell() - 12.0).abs() < 1e-4);
+        assert_eq!(dwell.minimum_dwell(), dwell.dwell_time(1, 1));
+        assert_eq!(25.0, dwell.dwell_time(10, 5));
+
+        // Doors slow enough that holding them open decides
+        dwell.door_time = 12.0;
+        dwell.hall_call_distance = Some(0.0);
+        assert_eq!(15.0, dwell.minimum_dwell());
+    }
+}
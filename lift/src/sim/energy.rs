@@ -0,0 +1,180 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use super::physics::GRAVITY;
+use super::plant::SimulatedLift;
+use crate::{Position, Velocity};
+
+/// Joules in a watt-hour
+const JOULES_PER_WH: f32 = 3600.0;
+
+/// Electrical efficiency of the drive. The masses and friction of the car are taken from the
+/// physics of the lift, or from `PhysicsConfig::default()` when it runs kinematically
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EnergyModel {
+    /// Share of the electrical energy drawn that reaches the sheave, 0 to 1
+    pub motor_efficiency: f32,
+    /// Share of the energy returned by an overhauling car that is fed back to the grid, 0 to 1.
+    /// Zero for drives without regeneration, where it is burned in a braking resistor
+    pub regeneration_efficiency: f32,
+    /// Power drawn at all times by the controller, lighting and ventilation, in W
+    pub standby_power: f32,
+}
+
+impl EnergyModel {
+    /// A geared traction drive with a regenerative inverter
+    pub const REGENERATIVE: Self = EnergyModel {
+        motor_efficiency: 0.85,
+        regeneration_efficiency: 0.65,
+        standby_power: 150.0,
+    };
+}
+
+impl Default for EnergyModel {
+    fn default() -> Self {
+        Self::REGENERATIVE
+    }
+}
+
+/// Energy totals of a run in Wh
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnergyReport {
+    /// Drawn from the grid, including standby
+    pub consumed: f32,
+    /// Fed back to the grid
+    pub regenerated: f32,
+}
+
+impl EnergyReport {
+    pub fn net(&self) -> f32 {
+        self.consumed - self.regenerated
+    }
+}
+
+/// Integrates the energy of the drive from the motion of the car, call `record` after every step.
+///
+/// The work at the sheave over a step is the change in potential energy of the rope imbalance,
+/// the change in kinetic energy of the car and counterweight, and the friction losses.
+/// Positive work is drawn through the motor, negative work is regenerated unless the brake holds.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnergyMeter {
+    model: EnergyModel,
+    last: Option<(Position, Velocity)>,
+    report: EnergyReport,
+}
+
+impl EnergyMeter {
+    pub fn new(model: EnergyModel) -> Self {
+        EnergyMeter {
+            model,
+            last: None,
+            report: EnergyReport::default(),
+        }
+    }
+
+    pub fn model(&self) -> &EnergyModel {
+        &self.model
+    }
+
+    pub fn record(&mut self, lift: &SimulatedLift, time_step: f32) {
+        let physics = lift.physics().unwrap_or_default();
+        let load = if lift.physics().is_some() {
+            lift.load()
+        } else {
+            lift.riders().len() as f32 * lift.passenger_mass()
+        };
+
+        let (position, velocity) = (lift.position(), lift.velocity());
+        let (last_position, last_velocity) = self.last.unwrap_or((position, velocity));
+        self.last = Some((position, velocity));
+
+        let meters = |floors: f32| floors * physics.floor_height;
+        let height = meters(position - last_position);
+        let speed = meters(velocity.abs());
+        let last_speed = meters(last_velocity.abs());
+
+        let car = physics.car_mass + load;
+        let potential = (car - physics.counterweight_mass) * GRAVITY * height;
+        let kinetic =
+            0.5 * (car + physics.counterweight_mass) * (speed * speed - last_speed * last_speed);
+        let friction = (physics.friction + physics.viscous_friction * speed) * height.abs();
+        let work = potential + kinetic + friction;
+
+        let mut consumed = self.model.standby_power * time_step;
+        if work > 0.0 {
+            consumed += work / self.model.motor_efficiency;
+        } else if !lift.is_brake_engaged() {
+            self.report.regenerated -= work * self.model.regeneration_efficiency / JOULES_PER_WH;
+        }
+        self.report.consumed += consumed / JOULES_PER_WH;
+    }
+
+    pub fn report(&self) -> EnergyReport {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, Floor};
+
+    fn travel(lift: &mut SimulatedLift, meter: &mut EnergyMeter, to: Floor) {
+        let direction = (to as Position - lift.position()).signum();
+        while (to as Position - lift.position()) * direction > 1e-3 {
+            let action = Action {
+                target_velocity: direction,
+                is_stopped_at_current_floor: false,
+            };
+            lift.accept_action(action, 0.1);
+            meter.record(lift, 0.1);
+        }
+        let stop = Action {
+            target_velocity: 0.0,
+            is_stopped_at_current_floor: false,
+        };
+        lift.accept_action(stop, 0.1);
+        meter.record(lift, 0.1);
+    }
+
+    #[test]
+    fn light_car_regenerates_going_up() {
+        let model = EnergyModel {
+            standby_power: 0.0,
+            ..EnergyModel::REGENERATIVE
+        };
+        let mut lift = SimulatedLift::new(0.0);
+        let mut meter = EnergyMeter::new(model);
+        meter.record(&lift, 0.0);
+
+        // The counterweight is heavier than the empty car, so it pulls the car up
+        travel(&mut lift, &mut meter, 10);
+        let up = meter.report();
+        assert!(up.regenerated > 0.0);
+
+        travel(&mut lift, &mut meter, 0);
+        let down = meter.report();
+        assert!(down.consumed - up.consumed > up.consumed);
+
+        // 400 kg over 30 m, with friction and acceleration on top
+        let imbalance = 400.0 * GRAVITY * 30.0 / JOULES_PER_WH;
+        assert!(down.consumed > imbalance / model.motor_efficiency);
+        assert!(up.regenerated < imbalance * model.regeneration_efficiency);
+        assert!(down.net() > 0.0);
+    }
+
+    #[test]
+    fn standby_power_accumulates_while_idle() {
+        let lift = SimulatedLift::new(0.0);
+        let mut meter = EnergyMeter::default();
+        for _ in 0..36_000 {
+            meter.record(&lift, 0.1);
+        }
+        assert!((meter.report().consumed - 150.0).abs() < 0.5);
+        assert_eq!(0.0, meter.report().regenerated);
+    }
+}
@@ -0,0 +1,29 @@
+/// A fault that can be injected into the simulated car
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Fault {
+    /// Position and velocity readings stop updating
+    SensorFreeze,
+    /// The velocity reading has the wrong sign
+    VelocitySignFlip,
+    /// The emergency stop reads as active, whatever its real state
+    StuckEmergencyStop,
+    /// The door closed signal reads as open
+    DoorClosedDropped,
+}
+
+/// A fault active from `at` for `duration` seconds of simulated time
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ScheduledFault {
+    pub at: f32,
+    pub duration: f32,
+    pub fault: Fault,
+}
+
+impl ScheduledFault {
+    pub fn is_active(&self, time: f32) -> bool {
+        time >= self.at && time - self.at < self.duration
+    }
+}
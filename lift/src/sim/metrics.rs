@@ -0,0 +1,177 @@
+use alloc::vec::Vec;
+
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use super::energy::{EnergyMeter, EnergyModel, EnergyReport};
+use super::plant::SimulatedLift;
+use crate::Position;
+
+/// Summary statistics of a series of durations in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f32,
+    /// 95th percentile, by the nearest rank
+    pub p95: f32,
+    pub max: f32,
+}
+
+impl Summary {
+    /// Summarise the values, all zero if there are none
+    pub fn of(values: &[f32]) -> Self {
+        if values.is_empty() {
+            return Summary::default();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f32::total_cmp);
+
+        let count = sorted.len();
+        let rank = (0.95 * count as f32).ceil() as usize;
+        Summary {
+            count,
+            mean: sorted.iter().sum::<f32>() / count as f32,
+            p95: sorted[rank.clamp(1, count) - 1],
+            max: sorted[count - 1],
+        }
+    }
+}
+
+/// Passenger and car statistics over a simulation run
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetricsReport {
+    /// Seconds from arriving at the landing to boarding
+    pub wait_time: Summary,
+    /// Seconds from arriving at the landing to reaching the destination
+    pub journey_time: Summary,
+    /// Number of times the car stopped at a floor
+    pub stops: u32,
+    /// Floors travelled
+    pub distance: f32,
+    /// Average share of the capacity in use, 0 to 1
+    pub load_factor: f32,
+    /// Seconds of simulated time covered
+    pub duration: f32,
+    pub energy: EnergyReport,
+}
+
+/// Collects metrics from a `SimulatedLift`, call `record` after every step
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metrics {
+    wait_times: Vec<f32>,
+    journey_times: Vec<f32>,
+    trips_seen: usize,
+    stops: u32,
+    was_stopped: bool,
+    distance: f32,
+    last_position: Option<Position>,
+    /// Time integral of the load factor
+    load_time: f32,
+    duration: f32,
+    energy: EnergyMeter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collect metrics with another energy model than `EnergyModel::REGENERATIVE`
+    pub fn with_energy_model(model: EnergyModel) -> Self {
+        Metrics {
+            energy: EnergyMeter::new(model),
+            ..Self::default()
+        }
+    }
+
+    /// Record the state of the lift after a step of `time_step` seconds
+    pub fn record(&mut self, lift: &SimulatedLift, time_step: f32) {
+        for trip in &lift.completed_trips()[self.trips_seen..] {
+            self.wait_times.push(trip.wait_time());
+            self.journey_times.push(trip.journey_time());
+        }
+        self.trips_seen = lift.completed_trips().len();
+
+        if lift.is_stopped() && !self.was_stopped {
+            self.stops += 1;
+        }
+        self.was_stopped = lift.is_stopped();
+
+        if let Some(last_position) = self.last_position {
+            self.distance += (lift.position() - last_position).abs();
+        }
+        self.last_position = Some(lift.position());
+
+        if lift.capacity() > 0 {
+            self.load_time += lift.riders().len() as f32 / lift.capacity() as f32 * time_step;
+        }
+        self.duration += time_step;
+        self.energy.record(lift, time_step);
+    }
+
+    /// Energy used so far, cheaper than the full report when only the energy is needed
+    pub fn energy(&self) -> EnergyReport {
+        self.energy.report()
+    }
+
+    pub fn report(&self) -> MetricsReport {
+        MetricsReport {
+            wait_time: Summary::of(&self.wait_times),
+            journey_time: Summary::of(&self.journey_times),
+            stops: self.stops,
+            distance: self.distance,
+            load_factor: if self.duration > 0.0 {
+                self.load_time / self.duration
+            } else {
+                0.0
+            },
+            duration: self.duration,
+            energy: self.energy.report(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiftController;
+
+    #[test]
+    fn summary_statistics() {
+        let values: Vec<f32> = (1..=20).map(|v| v as f32).collect();
+        let summary = Summary::of(&values);
+        assert_eq!(20, summary.count);
+        assert_eq!(10.5, summary.mean);
+        assert_eq!(19.0, summary.p95);
+        assert_eq!(20.0, summary.max);
+        assert_eq!(Summary::default(), Summary::of(&[]));
+    }
+
+    #[test]
+    fn collects_from_a_run() {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.set_capacity(2);
+        lift.spawn_passenger(0, 3);
+        lift.spawn_passenger(2, 0);
+        let mut controller = LiftController::new(1.0, 0.01, 0.01);
+        let mut metrics = Metrics::new();
+
+        for _ in 0..600 {
+            let action = controller.poll(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+            metrics.record(&lift, 0.1);
+        }
+
+        let report = metrics.report();
+        assert_eq!(2, report.journey_time.count);
+        assert!(report.wait_time.max > 0.0);
+        assert!(report.journey_time.mean > report.wait_time.mean);
+        assert!((report.distance - 6.0).abs() < 0.01);
+        assert!(report.stops >= 3);
+        assert!(report.load_factor > 0.0 && report.load_factor < 1.0);
+        assert!(report.energy.consumed > 0.0);
+    }
+}
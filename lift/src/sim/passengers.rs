@@ -0,0 +1,135 @@
+use alloc::vec::Vec;
+
+use crate::Floor;
+
+/// Identifies a simulated passenger
+pub type PassengerId = u32;
+
+/// A simulated passenger, waiting at a landing or riding the car
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Passenger {
+    pub id: PassengerId,
+    pub origin: Floor,
+    pub destination: Floor,
+    /// Simulated time the passenger arrived at the origin landing
+    pub arrived_at: f32,
+    /// Simulated time the passenger boarded the car, if they have
+    pub boarded_at: Option<f32>,
+}
+
+/// A passenger who has reached their destination
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trip {
+    pub id: PassengerId,
+    pub origin: Floor,
+    pub destination: Floor,
+    pub arrived_at: f32,
+    pub boarded_at: f32,
+    pub alighted_at: f32,
+}
+
+impl Trip {
+    /// Seconds from arriving at the landing to boarding the car
+    pub fn wait_time(&self) -> f32 {
+        self.boarded_at - self.arrived_at
+    }
+
+    /// Seconds spent in the car
+    pub fn ride_time(&self) -> f32 {
+        self.alighted_at - self.boarded_at
+    }
+
+    /// Seconds from arriving at the landing to reaching the destination
+    pub fn journey_time(&self) -> f32 {
+        self.alighted_at - self.arrived_at
+    }
+}
+
+/// Passengers waiting at the landings, riding the car and done travelling
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Passengers {
+    pub capacity: usize,
+    /// Mass of a passenger in kg, for the load in the car
+    pub passenger_mass: f32,
+    next_id: PassengerId,
+    pub waiting: Vec<Passenger>,
+    pub riders: Vec<Passenger>,
+    pub completed: Vec<Trip>,
+}
+
+/// The passengers who boarded and alighted at a stop
+pub(crate) struct Exchange {
+    pub boarded: Vec<Floor>,
+    pub alighted: u32,
+}
+
+impl Passengers {
+    pub const fn new() -> Self {
+        Passengers {
+            capacity: 13,
+            passenger_mass: 75.0,
+            next_id: 0,
+            waiting: Vec::new(),
+            riders: Vec::new(),
+            completed: Vec::new(),
+        }
+    }
+
+    pub fn spawn(&mut self, origin: Floor, destination: Floor, time: f32) -> PassengerId {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.waiting.push(Passenger {
+            id,
+            origin,
+            destination,
+            arrived_at: time,
+            boarded_at: None,
+        });
+        id
+    }
+
+    /// Let riders off and waiting passengers on at a floor, as long as there is room in the car.
+    /// Returns the destinations of those who boarded and the number who alighted
+    pub fn exchange(&mut self, floor: Floor, time: f32) -> Exchange {
+        let before = self.riders.len();
+        let completed = &mut self.completed;
+        self.riders.retain(|rider| {
+            if rider.destination != floor {
+                return true;
+            }
+            completed.push(Trip {
+                id: rider.id,
+                origin: rider.origin,
+                destination: rider.destination,
+                arrived_at: rider.arrived_at,
+                boarded_at: rider.boarded_at.unwrap_or(rider.arrived_at),
+                alighted_at: time,
+            });
+            false
+        });
+        let alighted = (before - self.riders.len()) as u32;
+
+        // First come, first served
+        let mut boarded = Vec::new();
+        let mut index = 0;
+        while index < self.waiting.len() && self.riders.len() < self.capacity {
+            if self.waiting[index].origin == floor {
+                let mut passenger = self.waiting.remove(index);
+                passenger.boarded_at = Some(time);
+                boarded.push(passenger.destination);
+                self.riders.push(passenger);
+            } else {
+                index += 1;
+            }
+        }
+
+        Exchange { boarded, alighted }
+    }
+
+    pub fn load(&self) -> f32 {
+        self.riders.len() as f32 * self.passenger_mass
+    }
+}
@@ -0,0 +1,147 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use crate::{Position, Velocity};
+
+/// Standard gravity in m/s²
+pub(crate) const GRAVITY: f32 = 9.81;
+
+/// Parameters of the physical plant, in SI units unless stated otherwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PhysicsConfig {
+    /// Distance between two floors in meters
+    pub floor_height: f32,
+    /// Mass of the empty car in kg
+    pub car_mass: f32,
+    /// Mass of the counterweight in kg, usually the car plus 40-50% of the rated load
+    pub counterweight_mass: f32,
+    /// Highest force the motor can apply at the sheave in N
+    pub max_motor_force: f32,
+    /// Highest speed the motor can reach, in floors per second
+    pub max_speed: Velocity,
+    /// Force applied by the drive per m/s of velocity error, in N·s/m
+    pub velocity_gain: f32,
+    /// Share of the rope imbalance the drive compensates for from load weighing, 0 to 1
+    pub load_compensation: f32,
+    /// Coulomb friction in the guide rails and sheave in N
+    pub friction: f32,
+    /// Viscous friction in N·s/m
+    pub viscous_friction: f32,
+    /// The brake engages when the car is commanded to stand still below this speed, in m/s
+    pub brake_engage_speed: f32,
+    /// Deceleration of the car while braking on an emergency stop, in m/s²
+    pub emergency_deceleration: f32,
+    /// Spring constant of the suspension ropes in N/m, the car sags by `load * g / rope_stiffness`
+    pub rope_stiffness: f32,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        PhysicsConfig {
+            floor_height: 3.0,
+            car_mass: 1000.0,
+            counterweight_mass: 1400.0,
+            max_motor_force: 20_000.0,
+            max_speed: 2.0,
+            velocity_gain: 20_000.0,
+            load_compensation: 0.9,
+            friction: 300.0,
+            viscous_friction: 100.0,
+            brake_engage_speed: 0.05,
+            emergency_deceleration: 3.0,
+            rope_stiffness: 400_000.0,
+        }
+    }
+}
+
+/// State of the physical plant beyond position and velocity
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Physics {
+    pub config: PhysicsConfig,
+    /// Mass of the passengers and goods in the car in kg
+    pub load: f32,
+    pub is_brake_engaged: bool,
+}
+
+impl Physics {
+    pub fn new(config: PhysicsConfig) -> Self {
+        Physics {
+            config,
+            load: 0.0,
+            is_brake_engaged: true,
+        }
+    }
+
+    /// Change the load in the car, returns how far the car sags in floors as the ropes stretch.
+    /// A negative sag means the car rises as load is removed
+    pub fn set_load(&mut self, load: f32) -> Position {
+        let sag = (load - self.load) * GRAVITY / self.config.rope_stiffness;
+        self.load = load;
+        sag / self.config.floor_height
+    }
+
+    /// Integrate the car one time step towards the commanded velocity, returns the new position and velocity
+    pub fn step(
+        &mut self,
+        position: Position,
+        velocity: Velocity,
+        commanded: Velocity,
+        emergency_stop: bool,
+        time_step: f32,
+    ) -> (Position, Velocity) {
+        let config = &self.config;
+        let height = config.floor_height;
+        let speed = velocity * height;
+
+        if emergency_stop {
+            // The safety brake decelerates the car regardless of the drive
+            let braked = (speed.abs() - config.emergency_deceleration * time_step).max(0.0);
+            let velocity = braked.copysign(speed) / height;
+            self.is_brake_engaged = velocity == 0.0;
+            return (position + velocity * time_step, velocity);
+        }
+
+        if commanded == 0.0 && speed.abs() < config.brake_engage_speed {
+            self.is_brake_engaged = true;
+        } else if commanded != 0.0 {
+            self.is_brake_engaged = false;
+        }
+        if self.is_brake_engaged {
+            return (position, 0.0);
+        }
+
+        let commanded = commanded.max(-config.max_speed).min(config.max_speed) * height;
+
+        // Positive imbalance pulls the car down
+        let imbalance = (config.car_mass + self.load - config.counterweight_mass) * GRAVITY;
+        let total_mass = config.car_mass + self.load + config.counterweight_mass;
+
+        let motor = (config.velocity_gain * (commanded - speed)
+            + config.load_compensation * imbalance)
+            .max(-config.max_motor_force)
+            .min(config.max_motor_force);
+        let driving = motor - imbalance;
+
+        let friction = config.friction + config.viscous_friction * speed.abs();
+        let net = if speed == 0.0 {
+            // Static friction holds the car until the driving force overcomes it
+            (driving.abs() - friction).max(0.0).copysign(driving)
+        } else {
+            driving - friction.copysign(speed)
+        };
+
+        let mut next_speed = speed + net / total_mass * time_step;
+        if speed != 0.0 && next_speed.signum() != speed.signum() && driving.abs() < friction {
+            // Friction can stop the car, but never reverse it
+            next_speed = 0.0;
+        }
+        let max_speed = config.max_speed * height;
+        let next_speed = next_speed.max(-max_speed).min(max_speed);
+
+        let velocity = next_speed / height;
+        (position + velocity * time_step, velocity)
+    }
+}
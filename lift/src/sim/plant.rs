@@ -0,0 +1,649 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use alloc::vec::Vec;
+
+use super::doors::{DoorCycle, DoorState};
+use super::dwell::DwellModel;
+use super::faults::{Fault, ScheduledFault};
+use super::passengers::{Passenger, PassengerId, Passengers, Trip};
+use super::physics::{Physics, PhysicsConfig};
+use super::rng::Rng;
+use super::sensors::{LatencyModel, NoiseModel, SensorModel};
+use crate::{Action, Floor, LiftSensors, Position, Velocity};
+
+/// Model of a lift car, for driving a controller without hardware.
+///
+/// By default the car is kinematic and reaches the commanded velocity instantly. With physics
+/// enabled through `set_physics` it is driven by a motor with limited force against gravity,
+/// friction and a brake, and no longer tracks the commanded velocity perfectly. The call for the
+/// current floor is cleared whenever the controller reports it has stopped there.
+///
+/// The controller sees the car through simulated sensors, which can add bias and noise to the
+/// readings, quantize the position to encoder counts and delay the readings, and faults can be
+/// scripted to strike at given times. `position` and `velocity` always return the true state
+/// of the car.
+///
+/// Passengers can be spawned at the landings. They call the car, board it when it stops with
+/// room to spare, press the button for their destination and leave when they get there, and
+/// the car dwells at each stop for as long as the exchange takes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SimulatedLift {
+    position: Position,
+    velocity: Velocity,
+    floors_to_stop_at: Vec<Floor>,
+    is_emergency_stop_activated: bool,
+    is_stopped: bool,
+    physics: Option<Physics>,
+    sensors: SensorModel,
+    measured_position: Position,
+    measured_velocity: Velocity,
+    /// Seconds of simulated time, advanced by `accept_action`
+    time: f32,
+    faults: Vec<ScheduledFault>,
+    dwell: DwellModel,
+    /// The doors at the current stop, the car may only move once they are closed
+    doors: DoorCycle,
+    passengers: Passengers,
+}
+
+impl SimulatedLift {
+    /// A car standing still at the given position, with no calls
+    pub const fn new(position: Position) -> Self {
+        SimulatedLift {
+            position,
+            velocity: 0.0,
+            floors_to_stop_at: Vec::new(),
+            is_emergency_stop_activated: false,
+            is_stopped: false,
+            physics: None,
+            sensors: SensorModel::new(),
+            measured_position: position,
+            measured_velocity: 0.0,
+            time: 0.0,
+            faults: Vec::new(),
+            dwell: DwellModel::TYPICAL,
+            doors: DoorCycle::CLOSED,
+            passengers: Passengers::new(),
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn velocity(&self) -> Velocity {
+        self.velocity
+    }
+
+    /// Seconds of simulated time since the car was created
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// If the controller reported the car stopped at a floor on the last step
+    pub fn is_stopped(&self) -> bool {
+        self.is_stopped
+    }
+
+    /// Move the car, for instance to start a scenario elsewhere than the ground floor
+    pub fn set_position(&mut self, position: Position) {
+        self.position = position;
+        self.measure(0.0);
+    }
+
+    pub fn set_velocity(&mut self, velocity: Velocity) {
+        self.velocity = velocity;
+        self.measure(0.0);
+    }
+
+    /// Add bias and Gaussian noise to the position readings, in floors
+    pub fn set_position_noise(&mut self, noise: NoiseModel) {
+        self.sensors.position_noise = noise;
+        self.measure(0.0);
+    }
+
+    /// Add bias and Gaussian noise to the velocity readings, in floors per second
+    pub fn set_velocity_noise(&mut self, noise: NoiseModel) {
+        self.sensors.velocity_noise = noise;
+        self.measure(0.0);
+    }
+
+    /// Read the position from an encoder with the given counts per floor, `None` for an ideal sensor.
+    /// The velocity is then derived by differencing counts between steps, giving the staircase
+    /// signals of a real encoder, and velocity noise no longer applies
+    pub fn set_encoder(&mut self, counts_per_floor: Option<u32>) {
+        self.sensors.encoder_resolution = counts_per_floor;
+        self.measure(0.0);
+    }
+
+    /// Delay the readings, and optionally take them less often than the controller polls
+    pub fn set_latency(&mut self, latency: LatencyModel) {
+        self.sensors.latency = latency;
+    }
+
+    /// Seed the sensor noise and latency jitter, runs with the same seed see the same readings
+    pub fn set_seed(&mut self, seed: u64) {
+        self.sensors.rng = Rng::new(seed);
+        self.measure(0.0);
+    }
+
+    /// Drive the car with a physical model rather than kinematically, `None` disables physics again
+    pub fn set_physics(&mut self, config: Option<PhysicsConfig>) {
+        self.physics = config.map(Physics::new);
+    }
+
+    /// The physical model driving the car, `None` when it runs kinematically
+    pub fn physics(&self) -> Option<PhysicsConfig> {
+        self.physics.as_ref().map(|p| p.config)
+    }
+
+    /// If the brake holds the car, always false without physics
+    pub fn is_brake_engaged(&self) -> bool {
+        self.physics.as_ref().is_some_and(|p| p.is_brake_engaged)
+    }
+
+    /// Mass of passengers and goods in the car in kg, always zero without physics
+    pub fn load(&self) -> f32 {
+        self.physics.as_ref().map_or(0.0, |p| p.load)
+    }
+
+    /// Change the load in the car. The ropes stretch with the load, so the car sags below the
+    /// landing as passengers board and rises as they leave. Has no effect without physics
+    pub fn set_load(&mut self, load: f32) {
+        if let Some(physics) = &mut self.physics {
+            self.position -= physics.set_load(load);
+            self.measure(0.0);
+        }
+    }
+
+    pub fn set_dwell_model(&mut self, dwell: DwellModel) {
+        self.dwell = dwell;
+    }
+
+    pub fn dwell_model(&self) -> DwellModel {
+        self.dwell
+    }
+
+    /// Stand at the landing while passengers board and alight, for as long as the dwell model
+    /// says. The car ignores the commanded velocity until the dwell is over
+    pub fn start_dwell(&mut self, boarding: u32, alighting: u32) {
+        let dwell = self.dwell.dwell_time(boarding, alighting);
+        self.doors = DoorCycle::new(self.dwell.door_time, dwell);
+        self.velocity = 0.0;
+    }
+
+    /// Seconds left of the current dwell, zero when the car is free to move
+    pub fn dwell_remaining(&self) -> f32 {
+        self.doors.remaining()
+    }
+
+    /// Where the doors are in their cycle at the current stop
+    pub fn door_state(&self) -> DoorState {
+        self.doors.state()
+    }
+
+    /// How far the doors are open, from 0 when closed to 1 when fully open
+    pub fn door_open_fraction(&self) -> f32 {
+        self.doors.open_fraction()
+    }
+
+    /// Number of passengers that fit in the car
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.passengers.capacity = capacity;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.passengers.capacity
+    }
+
+    /// Mass of a passenger in kg, adding to the load in the car when physics is enabled
+    pub fn set_passenger_mass(&mut self, mass: f32) {
+        self.passengers.passenger_mass = mass;
+    }
+
+    pub fn passenger_mass(&self) -> f32 {
+        self.passengers.passenger_mass
+    }
+
+    /// A passenger arrives at `origin` wanting to go to `destination`, and calls the car.
+    /// Returns `None` if the passenger is already where they want to be
+    pub fn spawn_passenger(&mut self, origin: Floor, destination: Floor) -> Option<PassengerId> {
+        if origin == destination {
+            return None;
+        }
+        self.register_call(origin);
+        Some(self.passengers.spawn(origin, destination, self.time))
+    }
+
+    /// Passengers waiting at any landing, in the order they arrived
+    pub fn waiting(&self) -> &[Passenger] {
+        &self.passengers.waiting
+    }
+
+    /// Passengers waiting at a landing, in the order they arrived
+    pub fn waiting_at(&self, floor: Floor) -> impl Iterator<Item = &Passenger> + '_ {
+        self.passengers
+            .waiting
+            .iter()
+            .filter(move |passenger| passenger.origin == floor)
+    }
+
+    /// Passengers in the car
+    pub fn riders(&self) -> &[Passenger] {
+        &self.passengers.riders
+    }
+
+    /// Passengers who have reached their destination, in the order they did
+    pub fn completed_trips(&self) -> &[Trip] {
+        &self.passengers.completed
+    }
+
+    /// Inject a fault `at` seconds into the simulation, lasting for `duration` seconds.
+    /// Use `f32::INFINITY` for a fault that never clears
+    pub fn schedule_fault(&mut self, at: f32, duration: f32, fault: Fault) {
+        self.faults.push(ScheduledFault {
+            at,
+            duration,
+            fault,
+        });
+    }
+
+    /// Remove all scheduled faults, including active ones
+    pub fn clear_faults(&mut self) {
+        self.faults.clear();
+    }
+
+    /// If the fault is currently active
+    pub fn is_fault_active(&self, fault: Fault) -> bool {
+        self.faults
+            .iter()
+            .any(|f| f.fault == fault && f.is_active(self.time))
+    }
+
+    /// The door closed signal as the controller sees it, which a fault can drop
+    pub fn is_door_closed(&self) -> bool {
+        self.door_state() == DoorState::Closed && !self.is_fault_active(Fault::DoorClosedDropped)
+    }
+
+    pub fn set_emergency_stop(&mut self, activated: bool) {
+        self.is_emergency_stop_activated = activated;
+    }
+
+    /// If the emergency stop has been activated with `set_emergency_stop`, whatever the faults
+    pub fn is_emergency_stop_latched(&self) -> bool {
+        self.is_emergency_stop_activated
+    }
+
+    /// The registered calls, in the order they were registered
+    pub fn calls(&self) -> &[Floor] {
+        &self.floors_to_stop_at
+    }
+
+    /// Register a call to a floor, returns false if it was already registered
+    pub fn register_call(&mut self, floor: Floor) -> bool {
+        if self.floors_to_stop_at.contains(&floor) {
+            return false;
+        }
+        self.floors_to_stop_at.push(floor);
+        true
+    }
+
+    /// Cancel the call to a floor, returns true if it was registered
+    pub fn cancel_call(&mut self, floor: Floor) -> bool {
+        let before = self.floors_to_stop_at.len();
+        self.floors_to_stop_at.retain(|f| *f != floor);
+        self.floors_to_stop_at.len() != before
+    }
+
+    /// Move the car according to an action from the controller for one time step
+    pub fn accept_action(&mut self, action: Action, time_step: f32) {
+        match &mut self.physics {
+            _ if self.doors.state() != DoorState::Closed => {
+                // The doors are open, the drive is interlocked
+                self.doors.step(time_step);
+                self.velocity = 0.0;
+            }
+            Some(physics) => {
+                let (position, velocity) = physics.step(
+                    self.position,
+                    self.velocity,
+                    action.target_velocity,
+                    self.is_emergency_stop_activated,
+                    time_step,
+                );
+                self.position = position;
+                self.velocity = velocity;
+            }
+            None => {
+                self.position += action.target_velocity * time_step;
+                self.velocity = action.target_velocity;
+            }
+        }
+        self.is_stopped = action.is_stopped_at_current_floor;
+        if action.is_stopped_at_current_floor {
+            let floor = self.position.round() as Floor;
+            self.cancel_call(floor);
+            self.exchange_passengers(floor);
+        } else {
+            self.recall_left_behind();
+        }
+        self.time += time_step;
+        self.measure(time_step);
+    }
+
+    fn exchange_passengers(&mut self, floor: Floor) {
+        let exchange = self.passengers.exchange(floor, self.time);
+        let boarded = exchange.boarded.len() as u32;
+        if boarded + exchange.alighted == 0 {
+            return;
+        }
+
+        for destination in exchange.boarded {
+            self.register_call(destination);
+        }
+        self.set_load(self.passengers.load());
+        self.start_dwell(boarded, exchange.alighted);
+    }
+
+    /// Passengers left behind for lack of room call the car again once it has left their floor
+    fn recall_left_behind(&mut self) {
+        let current_floor = self.position.round() as Floor;
+        for index in 0..self.passengers.waiting.len() {
+            let origin = self.passengers.waiting[index].origin;
+            if origin != current_floor {
+                self.register_call(origin);
+            }
+        }
+    }
+
+    fn measure(&mut self, time_step: f32) {
+        if let Some((position, velocity)) =
+            self.sensors
+                .measure(self.position, self.velocity, time_step)
+        {
+            if self.is_fault_active(Fault::SensorFreeze) {
+                return;
+            }
+            self.measured_position = position;
+            self.measured_velocity = velocity;
+        }
+    }
+}
+
+impl Default for SimulatedLift {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+impl LiftSensors for SimulatedLift {
+    fn current_floor(&self) -> Position {
+        self.measured_position
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        if self.is_fault_active(Fault::VelocitySignFlip) {
+            -self.measured_velocity
+        } else {
+            self.measured_velocity
+        }
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        &self.floors_to_stop_at
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        self.is_emergency_stop_activated || self.is_fault_active(Fault::StuckEmergencyStop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiftController;
+
+    #[test]
+    fn physical_car_lags_and_still_arrives() {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.set_physics(Some(PhysicsConfig::default()));
+        lift.register_call(3);
+        // Slow enough for the car to brake within the leeway, the controller does not plan deceleration
+        let mut controller = LiftController::new(0.25, 0.02, 0.01);
+
+        let time_step = 0.05;
+        let action = controller.poll(&lift, time_step);
+        lift.accept_action(action, time_step);
+        assert!(lift.velocity() > 0.0);
+        assert!(lift.velocity() < action.target_velocity);
+
+        for _ in 0..600 {
+            let action = controller.poll(&lift, time_step);
+            lift.accept_action(action, time_step);
+        }
+        assert!(lift.calls().is_empty());
+        assert!(lift.is_brake_engaged());
+        assert!((lift.position() - 3.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn car_sags_with_load() {
+        let mut lift = SimulatedLift::new(2.0);
+        lift.set_physics(Some(PhysicsConfig::default()));
+
+        // 800 kg on 400 kN/m ropes stretches them by about 20 mm, or 0.0065 floors
+        lift.set_load(800.0);
+        assert!((lift.position() - (2.0 - 0.00654)).abs() < 0.0001);
+
+        let mut controller = LiftController::new(0.25, 0.005, 0.01);
+        lift.register_call(2);
+        for _ in 0..20 {
+            let action = controller.poll(&lift, 0.05);
+            lift.accept_action(action, 0.05);
+        }
+        assert!((lift.position() - 2.0).abs() < 0.005);
+
+        lift.set_load(0.0);
+        assert!(lift.position() > 2.0);
+    }
+
+    #[test]
+    fn noise_has_configured_bias_and_spread() {
+        let mut lift = SimulatedLift::new(4.0);
+        lift.set_seed(7);
+        lift.set_position_noise(NoiseModel {
+            bias: 0.1,
+            std_dev: 0.02,
+        });
+
+        let samples = 2000;
+        let (mut sum, mut sum_squares) = (0.0, 0.0);
+        for _ in 0..samples {
+            lift.set_position(4.0);
+            let error = lift.current_floor() - 4.0;
+            sum += error;
+            sum_squares += error * error;
+        }
+        let mean = sum / samples as f32;
+        let std_dev = (sum_squares / samples as f32 - mean * mean).sqrt();
+
+        assert!((mean - 0.1).abs() < 0.002);
+        assert!((std_dev - 0.02).abs() < 0.002);
+        assert_eq!(0.0, lift.current_velocity());
+    }
+
+    #[test]
+    fn encoder_quantizes_position_and_differences_velocity() {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.set_encoder(Some(100));
+
+        let action = Action {
+            target_velocity: 0.37,
+            is_stopped_at_current_floor: false,
+        };
+        let mut velocities = 0.0;
+        for _ in 0..100 {
+            lift.accept_action(action, 0.01);
+            let counts = lift.current_floor() * 100.0;
+            assert!((counts - counts.round()).abs() < 1e-3);
+            assert!([0.0, 1.0]
+                .iter()
+                .any(|v| (lift.current_velocity() - v).abs() < 1e-3));
+            velocities += lift.current_velocity();
+        }
+        assert!((velocities / 100.0 - 0.37).abs() < 0.011);
+    }
+
+    #[test]
+    fn scripted_faults_strike_and_clear() {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.schedule_fault(0.5, 0.5, Fault::SensorFreeze);
+        lift.schedule_fault(1.5, 0.5, Fault::VelocitySignFlip);
+        lift.schedule_fault(2.5, f32::INFINITY, Fault::StuckEmergencyStop);
+
+        let action = Action {
+            target_velocity: 1.0,
+            is_stopped_at_current_floor: false,
+        };
+        let step = |lift: &mut SimulatedLift, steps| {
+            for _ in 0..steps {
+                lift.accept_action(action, 0.1);
+            }
+        };
+
+        step(&mut lift, 6);
+        let frozen = lift.current_floor();
+        step(&mut lift, 3);
+        assert_eq!(frozen, lift.current_floor());
+        assert!(lift.position() > frozen);
+        step(&mut lift, 2);
+        assert!(lift.current_floor() > frozen);
+
+        step(&mut lift, 5);
+        assert_eq!(-1.0, lift.current_velocity());
+        step(&mut lift, 5);
+        assert_eq!(1.0, lift.current_velocity());
+
+        assert!(!lift.is_emergency_stop_activated());
+        step(&mut lift, 5);
+        let mut controller = crate::LiftController::new(1.0, 0.01, 0.01);
+        assert!(lift.is_emergency_stop_activated());
+        assert_eq!(0.0, controller.poll(&lift, 0.1).target_velocity);
+        assert!(lift.is_door_closed());
+    }
+
+    #[test]
+    fn dwell_depends_on_passenger_exchange() {
+        let mut lift = SimulatedLift::new(1.0);
+        lift.set_dwell_model(DwellModel {
+            door_time: 2.0,
+            per_boarding: 1.0,
+            per_alighting: 0.5,
+            hall_call_distance: None,
+        });
+        lift.start_dwell(2, 2);
+        assert_eq!(5.0, lift.dwell_remaining());
+        assert_eq!(DoorState::Opening, lift.door_state());
+        assert!(!lift.is_door_closed());
+
+        let action = Action {
+            target_velocity: 1.0,
+            is_stopped_at_current_floor: false,
+        };
+        for _ in 0..48 {
+            lift.accept_action(action, 0.1);
+        }
+        assert_eq!(1.0, lift.position());
+        assert_eq!(DoorState::Closing, lift.door_state());
+        for _ in 0..3 {
+            lift.accept_action(action, 0.1);
+        }
+        assert!(lift.position() > 1.0);
+    }
+
+    #[test]
+    fn passengers_board_ride_and_alight() {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.set_capacity(1);
+        let mut controller = crate::LiftController::new(1.0, 0.01, 0.01);
+
+        lift.spawn_passenger(2, 5).unwrap();
+        lift.spawn_passenger(2, 0).unwrap();
+        assert_eq!(None, lift.spawn_passenger(3, 3));
+        assert_eq!(2, lift.waiting_at(2).count());
+
+        for _ in 0..1000 {
+            let action = controller.poll(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+            assert!(lift.riders().len() <= 1);
+        }
+
+        let trips = lift.completed_trips();
+        assert_eq!(2, trips.len());
+        assert_eq!((2, 5), (trips[0].origin, trips[0].destination));
+        assert!(trips[1].wait_time() > trips[0].journey_time());
+        assert!(lift.waiting().is_empty() && lift.riders().is_empty());
+    }
+
+    #[test]
+    fn readings_are_delayed_and_held_between_samples() {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.set_latency(LatencyModel {
+            latency: 0.1,
+            jitter: 0.0,
+            sample_period: 0.05,
+        });
+
+        let action = Action {
+            target_velocity: 1.0,
+            is_stopped_at_current_floor: false,
+        };
+        let mut readings = alloc::vec::Vec::new();
+        for _ in 0..40 {
+            lift.accept_action(action, 0.01);
+            readings.push(lift.current_floor());
+        }
+
+        // Nothing arrives until the first sample has been in flight for the latency
+        assert!(readings[..14].iter().all(|p| *p == 0.0));
+        // From then on each reading is held for a sample period and lags by the latency
+        assert!((readings[14] - 0.05).abs() < 1e-4);
+        assert_eq!(readings[14], readings[18]);
+        assert!((readings[39] - 0.3).abs() < 1e-4);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn state_round_trips_through_json() {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.set_physics(Some(PhysicsConfig::default()));
+        lift.set_position_noise(NoiseModel {
+            bias: 0.0,
+            std_dev: 0.01,
+        });
+        lift.spawn_passenger(0, 4);
+        let mut controller = LiftController::new(1.0, 0.01, 0.01);
+        for _ in 0..80 {
+            let action = controller.poll(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+        }
+
+        let json = serde_json::to_string(&(&lift, &controller)).unwrap();
+        let (mut restored_lift, mut restored_controller): (SimulatedLift, LiftController) =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(lift, restored_lift);
+        assert_eq!(controller.snapshot(), restored_controller.snapshot());
+
+        // The restored simulation carries on exactly like the original
+        for _ in 0..80 {
+            let action = controller.poll(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+            let action = restored_controller.poll(&restored_lift, 0.1);
+            restored_lift.accept_action(action, 0.1);
+        }
+        assert_eq!(lift, restored_lift);
+        assert_eq!(1, lift.completed_trips().len());
+    }
+}
@@ -0,0 +1,207 @@
+//! Drives a planner against a simple kinematic car in randomly generated buildings with random
+//! call sequences, and checks that every registered call is eventually served and that the car
+//! never exceeds the configured speed. The harness is generic over `Planner`, so the same
+//! properties can be run against other strategies than `LiftController`.
+
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use super::rng::Rng;
+use crate::{Action, CallQueue, Floor, LiftController, LiftSensors, Position, Velocity};
+
+/// Maximum number of calls generated per case
+pub const MAX_CALLS: usize = 32;
+
+/// Anything that can turn sensor readings into an action
+pub trait Planner {
+    fn plan(&mut self, sensors: &dyn LiftSensors, time_step: f32) -> Action;
+}
+
+impl Planner for LiftController {
+    fn plan(&mut self, sensors: &dyn LiftSensors, time_step: f32) -> Action {
+        self.poll(sensors, time_step)
+    }
+}
+
+/// Configuration of a property run
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyConfig {
+    /// Number of random cases to run
+    pub cases: u32,
+    /// Seed of the first case, case `n` uses `seed + n`
+    pub seed: u64,
+    /// Highest number of floors in a generated building
+    pub max_floors: Floor,
+    /// Highest number of calls in a generated case, at most `MAX_CALLS`
+    pub max_calls: usize,
+    /// The car must never move faster than this
+    pub max_velocity: Velocity,
+    pub time_step: f32,
+    /// Calls arrive during the first half of this many steps, and must all be served by the end
+    pub max_steps: u32,
+}
+
+impl Default for PropertyConfig {
+    fn default() -> Self {
+        PropertyConfig {
+            cases: 64,
+            seed: 0,
+            max_floors: 30,
+            max_calls: 16,
+            max_velocity: 1.0,
+            time_step: 0.1,
+            max_steps: 10_000,
+        }
+    }
+}
+
+/// A property that did not hold, rerun with `cases: 1` and the given seed to reproduce it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PropertyFailure {
+    /// A call was still registered when the case ended
+    CallNotServed { seed: u64, floor: Floor },
+    /// The planner commanded a velocity above `max_velocity`
+    SpeedExceeded {
+        seed: u64,
+        step: u32,
+        velocity: Velocity,
+    },
+}
+
+/// Run the properties for `config.cases` random cases, using a fresh planner for every case
+pub fn check_properties<P: Planner, F: FnMut() -> P>(
+    config: &PropertyConfig,
+    mut new_planner: F,
+) -> Result<(), PropertyFailure> {
+    (0..config.cases).try_for_each(|case| {
+        run_case(
+            config,
+            config.seed.wrapping_add(case as u64),
+            &mut new_planner(),
+        )
+    })
+}
+
+fn run_case(
+    config: &PropertyConfig,
+    seed: u64,
+    planner: &mut dyn Planner,
+) -> Result<(), PropertyFailure> {
+    let mut rng = Rng::new(seed);
+
+    let lowest_floor = -(rng.below(4) as Floor);
+    let floors = 2 + rng.below(config.max_floors.max(2) as u32 - 1) as Floor;
+    let random_floor = |rng: &mut Rng| lowest_floor + rng.below(floors as u32) as Floor;
+
+    let arrival_window = (config.max_steps / 2).max(1);
+    let mut schedule = [(0u32, 0 as Floor); MAX_CALLS];
+    let calls = 1 + rng.below(config.max_calls.clamp(1, MAX_CALLS) as u32) as usize;
+    for call in schedule.iter_mut().take(calls) {
+        *call = (rng.below(arrival_window), random_floor(&mut rng));
+    }
+    let schedule = &schedule[..calls];
+
+    let mut car = Car {
+        position: random_floor(&mut rng) as Position,
+        velocity: 0.0,
+        calls: CallQueue::new(),
+    };
+
+    for step in 0..config.max_steps {
+        for (_, floor) in schedule.iter().filter(|(arrival, _)| *arrival == step) {
+            // Capacity matches the schedule, so the queue can never be full
+            let _ = car.calls.insert(*floor);
+        }
+
+        let action = planner.plan(&car, config.time_step);
+        if action.target_velocity.is_nan() || action.target_velocity.abs() > config.max_velocity {
+            return Err(PropertyFailure::SpeedExceeded {
+                seed,
+                step,
+                velocity: action.target_velocity,
+            });
+        }
+        car.accept_action(action, config.time_step);
+    }
+
+    match car.calls.iter().next() {
+        Some(floor) => Err(PropertyFailure::CallNotServed {
+            seed,
+            floor: *floor,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Kinematic car reaching the commanded velocity instantly
+struct Car {
+    position: Position,
+    velocity: Velocity,
+    calls: CallQueue<MAX_CALLS>,
+}
+
+impl Car {
+    fn accept_action(&mut self, action: Action, time_step: f32) {
+        self.position += action.target_velocity * time_step;
+        self.velocity = action.target_velocity;
+        if action.is_stopped_at_current_floor {
+            self.calls.remove(self.position.round() as Floor);
+        }
+    }
+}
+
+impl LiftSensors for Car {
+    fn current_floor(&self) -> Position {
+        self.position
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        self.velocity
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        self.calls.as_slice()
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Stubborn;
+
+    impl Planner for Stubborn {
+        fn plan(&mut self, _: &dyn LiftSensors, _: f32) -> Action {
+            Action {
+                target_velocity: 0.0,
+                is_stopped_at_current_floor: false,
+            }
+        }
+    }
+
+    #[test]
+    fn controller_serves_every_call() {
+        let config = PropertyConfig::default();
+        assert_eq!(
+            Ok(()),
+            check_properties(&config, || LiftController::new(1.0, 0.01, 0.01))
+        );
+    }
+
+    #[test]
+    fn violations_are_reported() {
+        let config = PropertyConfig::default();
+        assert!(matches!(
+            check_properties(&config, || LiftController::new(2.0, 0.01, 0.01)),
+            Err(PropertyFailure::SpeedExceeded { .. })
+        ));
+        assert!(matches!(
+            check_properties(&config, || Stubborn),
+            Err(PropertyFailure::CallNotServed { .. })
+        ));
+    }
+}
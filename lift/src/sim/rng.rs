@@ -0,0 +1,80 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+/// Small seeded pseudo random number generator for the simulations.
+///
+/// Every stochastic part of the simulation draws from one of these, so a run is fully determined
+/// by its seeds. The generator is xorshift64* seeded through SplitMix64, and the float samplers
+/// only use `micromath`, so the same seed gives the same numbers on every platform, with or
+/// without `std` and in the browser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rng(u64);
+
+impl Rng {
+    pub const fn new(seed: u64) -> Self {
+        // SplitMix64 spreads similar seeds apart, and never leaves the xorshift state at zero
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng(if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Uniform number in `0..bound`, zero if the bound is zero
+    pub fn below(&mut self, bound: u32) -> u32 {
+        // Multiply and shift rather than modulo, which would favour small numbers
+        ((self.next_u32() as u64 * bound as u64) >> 32) as u32
+    }
+
+    /// Uniform number in `(0, 1]`
+    pub fn uniform(&mut self) -> f32 {
+        ((self.next_u32() >> 8) + 1) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Standard normal sample, using the Box-Muller transform
+    pub fn gaussian(&mut self) -> f32 {
+        let radius = (-2.0 * self.uniform().ln()).sqrt();
+        let angle = 2.0 * core::f32::consts::PI * self.uniform();
+        radius * angle.cos()
+    }
+
+    /// Poisson distributed count with the given mean, fine for the small means of a time step
+    pub fn poisson(&mut self, mean: f32) -> u32 {
+        let limit = (-mean).exp();
+        let mut count = 0;
+        let mut product = self.uniform();
+        while product > limit {
+            count += 1;
+            product *= self.uniform();
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_is_stable() {
+        let mut rng = Rng::new(42);
+        let values = [rng.next_u32(), rng.next_u32(), rng.below(10), rng.below(10)];
+
+        // Changing these breaks every recorded seed, only do so deliberately
+        assert_eq!([833678567, 2416485297, 4, 2], values);
+        assert_eq!(0.80366784, rng.uniform());
+        assert_eq!(-0.33414873, rng.gaussian());
+    }
+}
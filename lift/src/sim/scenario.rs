@@ -0,0 +1,455 @@
+//! Data-driven scenarios: a building, a car, a controller configuration, scripted calls and
+//! faults with timestamps, and the expectations the run must meet. Scenarios are plain serde
+//! types, and load from JSON behind the `json` feature.
+
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::faults::Fault;
+use super::metrics::{Metrics, MetricsReport};
+use super::physics::PhysicsConfig;
+use super::plant::SimulatedLift;
+use super::property::Planner;
+use crate::{Action, Floor, LiftController, Position, Velocity};
+
+/// Slack when comparing scripted times with the accumulated simulation time
+const TIME_TOLERANCE: f32 = 1e-4;
+
+/// Floors served by the car, `lowest_floor` up to and including `highest_floor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BuildingConfig {
+    pub lowest_floor: Floor,
+    pub highest_floor: Floor,
+}
+
+impl BuildingConfig {
+    pub fn contains(&self, floor: Floor) -> bool {
+        (self.lowest_floor..=self.highest_floor).contains(&floor)
+    }
+}
+
+/// Arguments of `LiftController::new`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ControllerConfig {
+    pub prefered_velocity: Velocity,
+    pub floor_leeway: Position,
+    pub velocity_epsilon: Velocity,
+}
+
+impl ControllerConfig {
+    pub fn build(&self) -> LiftController {
+        LiftController::new(
+            self.prefered_velocity,
+            self.floor_leeway,
+            self.velocity_epsilon,
+        )
+    }
+}
+
+/// The simulated car
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CarConfig {
+    pub initial_floor: Floor,
+    /// Passengers the car can carry, the simulation default when absent
+    pub capacity: Option<usize>,
+    /// Kinematic car when absent
+    pub physics: Option<PhysicsConfig>,
+}
+
+/// Something that happens during the run
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScriptedAction {
+    Call { floor: Floor },
+    Passenger { origin: Floor, destination: Floor },
+    EmergencyStop { activated: bool },
+    Fault { fault: Fault, duration: f32 },
+}
+
+impl ScriptedAction {
+    /// Make the action happen to `lift`, now
+    pub fn apply(&self, lift: &mut SimulatedLift) {
+        match *self {
+            ScriptedAction::Call { floor } => {
+                lift.register_call(floor);
+            }
+            ScriptedAction::Passenger {
+                origin,
+                destination,
+            } => {
+                lift.spawn_passenger(origin, destination);
+            }
+            ScriptedAction::EmergencyStop { activated } => lift.set_emergency_stop(activated),
+            ScriptedAction::Fault { fault, duration } => {
+                lift.schedule_fault(lift.time(), duration, fault)
+            }
+        }
+    }
+}
+
+/// An action and the second of simulated time it happens at
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScriptedEvent {
+    pub at: f32,
+    pub action: ScriptedAction,
+}
+
+/// Applies scripted events to a lift once its simulated time reaches them, call `feed` before
+/// every step
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptPlayer {
+    /// Sorted by time
+    events: Vec<ScriptedEvent>,
+    next: usize,
+}
+
+impl ScriptPlayer {
+    pub fn new(events: &[ScriptedEvent]) -> Self {
+        let mut events = events.to_vec();
+        events.sort_by(|a, b| a.at.total_cmp(&b.at));
+        ScriptPlayer { events, next: 0 }
+    }
+
+    /// Apply the events that are due at the current time of `lift`
+    pub fn feed(&mut self, lift: &mut SimulatedLift) {
+        while let Some(event) = self
+            .events
+            .get(self.next)
+            .filter(|event| event.at <= lift.time() + TIME_TOLERANCE)
+        {
+            event.action.apply(lift);
+            self.next += 1;
+        }
+    }
+
+    /// If every event has been applied
+    pub fn is_finished(&self) -> bool {
+        self.next == self.events.len()
+    }
+}
+
+/// A condition the run must meet
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expectation {
+    /// No calls are registered when the run ends
+    AllCallsServed,
+    /// No passengers are waiting or riding when the run ends
+    AllPassengersDelivered,
+    /// The car is within the floor leeway of `floor` at second `at`
+    AtFloor { at: f32, floor: Floor },
+    /// The car never moves faster than this
+    MaxSpeed(Velocity),
+    /// No passenger waits longer than this many seconds
+    MaxWaitTime(f32),
+    /// No passenger takes longer than this many seconds from arriving to reaching the destination
+    MaxJourneyTime(f32),
+}
+
+/// A complete scenario
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scenario {
+    pub name: String,
+    pub building: BuildingConfig,
+    pub car: CarConfig,
+    pub controller: ControllerConfig,
+    /// Seed of the sensor noise
+    pub seed: u64,
+    pub time_step: f32,
+    /// Seconds of simulated time to run
+    pub duration: f32,
+    pub events: Vec<ScriptedEvent>,
+    pub expectations: Vec<Expectation>,
+}
+
+/// Reason a scenario cannot be run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScenarioError {
+    /// The JSON was malformed or did not match `Scenario`
+    #[cfg(feature = "json")]
+    Json,
+    /// The time step or duration is not a positive number
+    InvalidTiming,
+    /// A floor in the car or the script is outside the building
+    FloorOutOfRange(Floor),
+}
+
+/// An expectation that was not met, with the value that was observed instead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExpectationFailure {
+    pub expectation: Expectation,
+    /// Calls or passengers left, the position of the car, or the highest speed or time seen
+    pub observed: f32,
+}
+
+/// Result of running a scenario
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioOutcome {
+    pub report: MetricsReport,
+    pub failures: Vec<ExpectationFailure>,
+}
+
+impl ScenarioOutcome {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Scenario {
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<Self, ScenarioError> {
+        let scenario: Self = serde_json::from_str(json).map_err(|_| ScenarioError::Json)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        if !(self.time_step > 0.0 && self.duration > 0.0 && self.duration.is_finite()) {
+            return Err(ScenarioError::InvalidTiming);
+        }
+
+        let scripted = self.events.iter().flat_map(|event| match event.action {
+            ScriptedAction::Call { floor } => [Some(floor), None],
+            ScriptedAction::Passenger {
+                origin,
+                destination,
+            } => [Some(origin), Some(destination)],
+            _ => [None, None],
+        });
+        let expected = self
+            .expectations
+            .iter()
+            .map(|expectation| match expectation {
+                Expectation::AtFloor { floor, .. } => Some(*floor),
+                _ => None,
+            });
+        core::iter::once(Some(self.car.initial_floor))
+            .chain(scripted)
+            .chain(expected)
+            .flatten()
+            .find(|floor| !self.building.contains(*floor))
+            .map_or(Ok(()), |floor| Err(ScenarioError::FloorOutOfRange(floor)))
+    }
+
+    /// The car at the start of the run, without any of the events applied
+    pub fn initial_lift(&self) -> SimulatedLift {
+        let mut lift = SimulatedLift::new(self.car.initial_floor as Position);
+        lift.set_seed(self.seed);
+        lift.set_physics(self.car.physics);
+        if let Some(capacity) = self.car.capacity {
+            lift.set_capacity(capacity);
+        }
+        lift
+    }
+
+    /// Run the scenario with the configured controller
+    pub fn run(&self) -> Result<ScenarioOutcome, ScenarioError> {
+        self.run_with(&mut self.controller.build())
+    }
+
+    /// Run the scenario with another planner, the controller configuration then only sets the
+    /// leeway of `AtFloor`
+    pub fn run_with(&self, planner: &mut dyn Planner) -> Result<ScenarioOutcome, ScenarioError> {
+        self.run_observed(planner, &mut |_, _| {})
+    }
+
+    /// Same as `run_with`, calling `on_step` with the lift and the action after every step
+    pub fn run_observed(
+        &self,
+        planner: &mut dyn Planner,
+        on_step: &mut dyn FnMut(&SimulatedLift, Action),
+    ) -> Result<ScenarioOutcome, ScenarioError> {
+        self.validate()?;
+
+        let mut lift = self.initial_lift();
+        let mut script = ScriptPlayer::new(&self.events);
+
+        let mut positions = self
+            .expectations
+            .iter()
+            .filter_map(|expectation| match expectation {
+                Expectation::AtFloor { at, .. } => Some((*at, *expectation, None)),
+                _ => None,
+            })
+            .collect::<Vec<(f32, Expectation, Option<Position>)>>();
+
+        let mut metrics = Metrics::new();
+        let mut max_speed: Velocity = 0.0;
+        let steps = (self.duration / self.time_step).round() as u32;
+        for _ in 0..steps {
+            script.feed(&mut lift);
+            for (at, _, position) in positions.iter_mut() {
+                if position.is_none() && *at <= lift.time() + TIME_TOLERANCE {
+                    *position = Some(lift.position());
+                }
+            }
+
+            let action = planner.plan(&lift, self.time_step);
+            lift.accept_action(action, self.time_step);
+            metrics.record(&lift, self.time_step);
+            max_speed = max_speed.max(lift.velocity().abs());
+            on_step(&lift, action);
+        }
+
+        let report = metrics.report();
+        let failures = self
+            .expectations
+            .iter()
+            .filter_map(|expectation| {
+                let (met, observed) = match *expectation {
+                    Expectation::AllCallsServed => {
+                        let left = lift.calls().len();
+                        (left == 0, left as f32)
+                    }
+                    Expectation::AllPassengersDelivered => {
+                        let left = lift.waiting().len() + lift.riders().len();
+                        (left == 0, left as f32)
+                    }
+                    Expectation::AtFloor { at, floor } => {
+                        let position = positions
+                            .iter()
+                            .find(|(a, e, _)| *a == at && *e == *expectation)
+                            .and_then(|(_, _, position)| *position)
+                            .unwrap_or_else(|| lift.position());
+                        let leeway = self.controller.floor_leeway;
+                        ((position - floor as Position).abs() <= leeway, position)
+                    }
+                    Expectation::MaxSpeed(limit) => (max_speed <= limit, max_speed),
+                    Expectation::MaxWaitTime(limit) => {
+                        (report.wait_time.max <= limit, report.wait_time.max)
+                    }
+                    Expectation::MaxJourneyTime(limit) => {
+                        (report.journey_time.max <= limit, report.journey_time.max)
+                    }
+                };
+                (!met).then_some(ExpectationFailure {
+                    expectation: *expectation,
+                    observed,
+                })
+            })
+            .collect();
+
+        Ok(ScenarioOutcome { report, failures })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn scenario() -> Scenario {
+        Scenario {
+            name: "two passengers".into(),
+            building: BuildingConfig {
+                lowest_floor: 0,
+                highest_floor: 5,
+            },
+            car: CarConfig {
+                initial_floor: 0,
+                capacity: None,
+                physics: None,
+            },
+            controller: ControllerConfig {
+                prefered_velocity: 1.0,
+                floor_leeway: 0.01,
+                velocity_epsilon: 0.01,
+            },
+            seed: 1,
+            time_step: 0.1,
+            duration: 120.0,
+            events: vec![
+                ScriptedEvent {
+                    at: 5.0,
+                    action: ScriptedAction::Passenger {
+                        origin: 4,
+                        destination: 1,
+                    },
+                },
+                ScriptedEvent {
+                    at: 0.0,
+                    action: ScriptedAction::Call { floor: 2 },
+                },
+            ],
+            expectations: vec![
+                Expectation::AllCallsServed,
+                Expectation::AllPassengersDelivered,
+                Expectation::AtFloor { at: 2.5, floor: 2 },
+                Expectation::MaxSpeed(1.0),
+                Expectation::MaxJourneyTime(60.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn runs_scripted_scenario() {
+        let outcome = scenario().run().unwrap();
+        assert_eq!(Vec::<ExpectationFailure>::new(), outcome.failures);
+        assert_eq!(1, outcome.report.journey_time.count);
+
+        let mut steps = 0;
+        scenario()
+            .run_observed(&mut scenario().controller.build(), &mut |_, _| steps += 1)
+            .unwrap();
+        assert_eq!(1200, steps);
+    }
+
+    #[test]
+    fn reports_unmet_expectations() {
+        let mut scenario = scenario();
+        scenario.expectations = vec![
+            Expectation::MaxSpeed(0.5),
+            Expectation::AtFloor { at: 1.0, floor: 2 },
+        ];
+        let outcome = scenario.run().unwrap();
+        assert!(!outcome.passed());
+        assert_eq!(1.0, outcome.failures[0].observed);
+        assert!((outcome.failures[1].observed - 1.0).abs() < 1e-3);
+
+        scenario.events[0].action = ScriptedAction::Call { floor: 6 };
+        assert_eq!(Err(ScenarioError::FloorOutOfRange(6)), scenario.run());
+        scenario.time_step = 0.0;
+        assert_eq!(Err(ScenarioError::InvalidTiming), scenario.validate());
+    }
+
+    #[test]
+    fn script_player_applies_events_when_due() {
+        let scenario = scenario();
+        let mut lift = scenario.initial_lift();
+        let mut script = ScriptPlayer::new(&scenario.events);
+        let hold = Action {
+            target_velocity: 0.0,
+            is_stopped_at_current_floor: true,
+        };
+
+        script.feed(&mut lift);
+        assert_eq!(&[2], lift.calls());
+        assert!(!script.is_finished());
+
+        while lift.time() < 4.85 {
+            lift.accept_action(hold, 0.1);
+            script.feed(&mut lift);
+        }
+        assert_eq!(0, lift.waiting().len());
+        lift.accept_action(hold, 0.1);
+        script.feed(&mut lift);
+        assert_eq!(1, lift.waiting().len());
+        assert!(script.is_finished());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn loads_json() {
+        let json = serde_json::to_string(&scenario()).unwrap();
+        assert_eq!(Ok(scenario()), Scenario::from_json(&json));
+        assert_eq!(Err(ScenarioError::Json), Scenario::from_json("[]"));
+    }
+}
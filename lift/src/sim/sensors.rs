@@ -0,0 +1,188 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use alloc::vec::Vec;
+
+use super::rng::Rng;
+use crate::{Position, Velocity};
+
+/// Error added to a simulated sensor reading, a constant bias plus Gaussian noise
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NoiseModel {
+    /// Offset added to every reading
+    pub bias: f32,
+    /// Standard deviation of the noise
+    pub std_dev: f32,
+}
+
+impl NoiseModel {
+    /// A perfect sensor
+    pub const NONE: NoiseModel = NoiseModel {
+        bias: 0.0,
+        std_dev: 0.0,
+    };
+
+    fn apply(&self, value: f32, rng: &mut Rng) -> f32 {
+        if self.std_dev == 0.0 {
+            return value + self.bias;
+        }
+        value + self.bias + self.std_dev * rng.gaussian()
+    }
+}
+
+impl Default for NoiseModel {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Delay between the car moving and the controller seeing it
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LatencyModel {
+    /// Mean delay of a reading in seconds
+    pub latency: f32,
+    /// Each reading is delayed by up to this many seconds more or less than `latency`,
+    /// so readings may arrive out of order
+    pub jitter: f32,
+    /// Seconds between readings, zero to take a reading on every step
+    pub sample_period: f32,
+}
+
+impl LatencyModel {
+    /// Readings are taken on every step and delivered instantly
+    pub const NONE: LatencyModel = LatencyModel {
+        latency: 0.0,
+        jitter: 0.0,
+        sample_period: 0.0,
+    };
+}
+
+impl Default for LatencyModel {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Slack when comparing times, so accumulated time steps land on the expected step
+const TIME_TOLERANCE: f32 = 1e-5;
+
+/// A reading on its way to the controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct InFlight {
+    delivered_at: f32,
+    position: Position,
+    velocity: Velocity,
+}
+
+/// Turns the true state of the car into the readings the controller sees
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct SensorModel {
+    pub position_noise: NoiseModel,
+    pub velocity_noise: NoiseModel,
+    pub rng: Rng,
+    /// Encoder counts per floor, if the position is read from a quantized encoder
+    pub encoder_resolution: Option<u32>,
+    pub latency: LatencyModel,
+    /// Encoder count and derived velocity from the last measurement
+    encoder: Option<(i64, Velocity)>,
+    /// Seconds since the sensors were created
+    time: f32,
+    /// Seconds since the last reading was taken
+    since_sample: f32,
+    in_flight: Vec<InFlight>,
+}
+
+impl SensorModel {
+    pub const fn new() -> Self {
+        SensorModel {
+            position_noise: NoiseModel::NONE,
+            velocity_noise: NoiseModel::NONE,
+            rng: Rng::new(0),
+            encoder_resolution: None,
+            latency: LatencyModel::NONE,
+            encoder: None,
+            time: 0.0,
+            since_sample: 0.0,
+            in_flight: Vec::new(),
+        }
+    }
+
+    /// Advance the sensors by `time_step` seconds, returns the newest reading to reach the
+    /// controller if any did. A zero time step always takes a reading, for when the car or the
+    /// sensors are reconfigured
+    pub fn measure(
+        &mut self,
+        position: Position,
+        velocity: Velocity,
+        time_step: f32,
+    ) -> Option<(Position, Velocity)> {
+        self.time += time_step;
+        self.since_sample += time_step;
+
+        if time_step == 0.0 || self.since_sample + TIME_TOLERANCE >= self.latency.sample_period {
+            let elapsed = self.since_sample;
+            self.since_sample = 0.0;
+            let (position, velocity) = self.sample(position, velocity, elapsed);
+
+            let jitter = match self.latency.jitter {
+                jitter if jitter > 0.0 => jitter * (2.0 * self.rng.uniform() - 1.0),
+                _ => 0.0,
+            };
+            self.in_flight.push(InFlight {
+                delivered_at: self.time + (self.latency.latency + jitter).max(0.0),
+                position,
+                velocity,
+            });
+        }
+
+        // Readings are handed over in the order they arrive, not the order they were taken
+        let time = self.time;
+        let mut delivered: Option<InFlight> = None;
+        self.in_flight.retain(|reading| {
+            if reading.delivered_at > time + TIME_TOLERANCE {
+                return true;
+            }
+            if delivered.is_none_or(|d| reading.delivered_at >= d.delivered_at) {
+                delivered = Some(*reading);
+            }
+            false
+        });
+        delivered.map(|reading| (reading.position, reading.velocity))
+    }
+
+    /// Take a reading `elapsed` seconds after the previous one. With no time elapsed the
+    /// velocity derived from the encoder is kept, as there is nothing to difference over
+    fn sample(
+        &mut self,
+        position: Position,
+        velocity: Velocity,
+        elapsed: f32,
+    ) -> (Position, Velocity) {
+        let position = self.position_noise.apply(position, &mut self.rng);
+        let resolution = match self.encoder_resolution {
+            Some(resolution) => resolution as f32,
+            None => {
+                self.encoder = None;
+                return (position, self.velocity_noise.apply(velocity, &mut self.rng));
+            }
+        };
+
+        let count = (position * resolution).floor() as i64;
+        let velocity = match self.encoder {
+            Some((last_count, _)) if elapsed > 0.0 => {
+                (count - last_count) as f32 / resolution / elapsed
+            }
+            Some((_, last_velocity)) => last_velocity,
+            None => 0.0,
+        };
+        self.encoder = Some((count, velocity));
+
+        (count as f32 / resolution, velocity)
+    }
+}
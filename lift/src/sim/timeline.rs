@@ -0,0 +1,178 @@
+//! Time series of a simulation run, exported as CSV or JSON for plotting in external tools.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use super::plant::SimulatedLift;
+use crate::{Action, Direction, EventKind, Position, Velocity};
+
+/// State of the car at the end of one time step
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimelinePoint {
+    pub time: f32,
+    /// True position, not the sensor reading
+    pub position: Position,
+    /// True velocity, not the sensor reading
+    pub velocity: Velocity,
+    pub commanded_velocity: Velocity,
+    pub is_door_closed: bool,
+    /// Transitions reported by the controller during the step
+    pub events: Vec<EventKind>,
+}
+
+/// Points of a run, one per time step
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timeline {
+    pub points: Vec<TimelinePoint>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the lift after it has accepted `action`
+    pub fn record<I: IntoIterator<Item = EventKind>>(
+        &mut self,
+        lift: &SimulatedLift,
+        action: Action,
+        events: I,
+    ) {
+        self.points.push(TimelinePoint {
+            time: lift.time(),
+            position: lift.position(),
+            velocity: lift.velocity(),
+            commanded_velocity: action.target_velocity,
+            is_door_closed: lift.is_door_closed(),
+            events: events.into_iter().collect(),
+        });
+    }
+
+    /// One row per point with a header line. Events are separated by `;` within their column.
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("time,position,velocity,commanded_velocity,door_closed,events\n");
+        for point in &self.points {
+            // Writing to a String cannot fail
+            let _ = write!(
+                csv,
+                "{},{},{},{},{},",
+                point.time,
+                point.position,
+                point.velocity,
+                point.commanded_velocity,
+                point.is_door_closed
+            );
+            for (i, event) in point.events.iter().enumerate() {
+                if i > 0 {
+                    csv.push(';');
+                }
+                write_event(&mut csv, event);
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        // Every field serializes to plain numbers, booleans and strings
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+fn write_event(out: &mut String, event: &EventKind) {
+    let _ = match event {
+        EventKind::Departed { floor, direction } => {
+            write!(out, "departed {} {}", floor, direction_name(*direction))
+        }
+        EventKind::Arrived { floor } => write!(out, "arrived {}", floor),
+        EventKind::DirectionChanged { from, to } => write!(
+            out,
+            "direction {} {}",
+            direction_name(*from),
+            direction_name(*to)
+        ),
+        EventKind::TargetSelected { floor } => write!(out, "target {}", floor),
+        EventKind::EmergencyStop { activated: true } => write!(out, "emergency stop"),
+        EventKind::EmergencyStop { activated: false } => write!(out, "emergency stop released"),
+        EventKind::Chime { floor, direction } => {
+            write!(out, "chime {} {}", floor, direction_name(*direction))
+        }
+        EventKind::Fault { faults } => write!(
+            out,
+            "fault{}{}",
+            if faults.sensor_reading {
+                " sensor reading"
+            } else {
+                ""
+            },
+            if faults.emergency_stop {
+                " emergency stop"
+            } else {
+                ""
+            }
+        ),
+    };
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Neutral => "neutral",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventQueue, LiftController};
+
+    fn run() -> Timeline {
+        let mut lift = SimulatedLift::new(0.0);
+        let mut controller = LiftController::new(1.0, 0.01, 0.01);
+        let mut events = EventQueue::<8>::new();
+        let mut timeline = Timeline::new();
+        lift.register_call(2);
+        for _ in 0..30 {
+            let timestamp = (lift.time() * 1000.0) as u64;
+            let action = controller.poll_with_events(&lift, 0.1, timestamp, &mut events);
+            lift.accept_action(action, 0.1);
+            timeline.record(&lift, action, events.drain().map(|event| event.kind));
+        }
+        timeline
+    }
+
+    #[test]
+    fn records_and_exports_csv() {
+        let timeline = run();
+        assert_eq!(30, timeline.points.len());
+        assert!(timeline.points[0]
+            .events
+            .contains(&EventKind::TargetSelected { floor: 2 }));
+
+        let csv = timeline.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(
+            Some("time,position,velocity,commanded_velocity,door_closed,events"),
+            lines.next()
+        );
+        let first = lines.next().unwrap();
+        assert!(first.starts_with("0.1,0.1,1,1,true,"));
+        assert!(first.contains("target 2"));
+        assert!(csv.contains("arrived 2"));
+        assert_eq!(30, lines.count() + 1);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn exports_json() {
+        let json = run().to_json();
+        let parsed: Timeline = serde_json::from_str(&json).unwrap();
+        assert_eq!(run(), parsed);
+    }
+}
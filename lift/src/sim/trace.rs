@@ -0,0 +1,188 @@
+//! Recording and replay of the sensor readings a planner saw and the actions it returned. A trace
+//! recorded in the field or in the browser demo can be fed back into a controller to reproduce
+//! odd behavior, or kept as a regression test.
+
+use alloc::vec::Vec;
+
+use super::property::Planner;
+use crate::{Action, Floor, LiftSensors, Position, Velocity};
+
+/// Owned copy of what a `LiftSensors` implementation reported at one poll
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SensorReadings {
+    pub current_floor: Position,
+    pub current_velocity: Velocity,
+    pub floors_to_stop_at: Vec<Floor>,
+    pub is_emergency_stop_activated: bool,
+}
+
+impl SensorReadings {
+    pub fn capture(sensors: &dyn LiftSensors) -> Self {
+        SensorReadings {
+            current_floor: sensors.current_floor(),
+            current_velocity: sensors.current_velocity(),
+            floors_to_stop_at: sensors.floors_to_stop_at().to_vec(),
+            is_emergency_stop_activated: sensors.is_emergency_stop_activated(),
+        }
+    }
+}
+
+impl LiftSensors for SensorReadings {
+    fn current_floor(&self) -> Position {
+        self.current_floor
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        self.current_velocity
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        &self.floors_to_stop_at
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        self.is_emergency_stop_activated
+    }
+}
+
+/// One poll of a recorded run
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceSample {
+    /// Seconds since the recording started, at the start of this poll
+    pub time: f32,
+    pub time_step: f32,
+    pub sensors: SensorReadings,
+    pub action: Action,
+}
+
+/// Sequence of polls, in the order they happened
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trace {
+    pub samples: Vec<TraceSample>,
+}
+
+/// First poll where a replayed planner did not return the recorded action
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divergence {
+    pub index: usize,
+    pub time: f32,
+    pub recorded: Action,
+    pub replayed: Action,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed every recorded reading into `planner` and compare its actions with the recorded ones
+    pub fn replay(&self, planner: &mut dyn Planner) -> Result<(), Divergence> {
+        self.samples
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, sample)| {
+                let replayed = planner.plan(&sample.sensors, sample.time_step);
+                if replayed == sample.action {
+                    Ok(())
+                } else {
+                    Err(Divergence {
+                        index,
+                        time: sample.time,
+                        recorded: sample.action,
+                        replayed,
+                    })
+                }
+            })
+    }
+}
+
+/// Planner wrapper appending every poll to a trace
+#[derive(Debug, Clone)]
+pub struct Recorder<P> {
+    planner: P,
+    trace: Trace,
+    time: f32,
+}
+
+impl<P: Planner> Recorder<P> {
+    pub fn new(planner: P) -> Self {
+        Recorder {
+            planner,
+            trace: Trace::new(),
+            time: 0.0,
+        }
+    }
+
+    pub fn planner(&self) -> &P {
+        &self.planner
+    }
+
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    pub fn into_trace(self) -> Trace {
+        self.trace
+    }
+}
+
+impl<P: Planner> Planner for Recorder<P> {
+    fn plan(&mut self, sensors: &dyn LiftSensors, time_step: f32) -> Action {
+        let readings = SensorReadings::capture(sensors);
+        let action = self.planner.plan(&readings, time_step);
+        self.trace.samples.push(TraceSample {
+            time: self.time,
+            time_step,
+            sensors: readings,
+            action,
+        });
+        self.time += time_step;
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimulatedLift;
+    use crate::LiftController;
+
+    fn record() -> Trace {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.register_call(3);
+        let mut recorder = Recorder::new(LiftController::new(1.0, 0.01, 0.01));
+        for step in 0..100 {
+            if step == 40 {
+                lift.register_call(1);
+            }
+            let action = recorder.plan(&lift, 0.1);
+            lift.accept_action(action, 0.1);
+        }
+        recorder.into_trace()
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let trace = record();
+        assert_eq!(100, trace.samples.len());
+        assert!(trace.samples[40].sensors.floors_to_stop_at.contains(&1));
+        assert_eq!(
+            Ok(()),
+            trace.replay(&mut LiftController::new(1.0, 0.01, 0.01))
+        );
+    }
+
+    #[test]
+    fn replay_reports_first_divergence() {
+        let trace = record();
+        let divergence = trace
+            .replay(&mut LiftController::new(0.5, 0.01, 0.01))
+            .unwrap_err();
+        assert_eq!(0, divergence.index);
+        assert_eq!(1.0, divergence.recorded.target_velocity);
+        assert_eq!(0.5, divergence.replayed.target_velocity);
+    }
+}
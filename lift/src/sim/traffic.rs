@@ -0,0 +1,167 @@
+#[allow(unused_imports)]
+use micromath::F32Ext;
+
+use alloc::vec::Vec;
+
+use super::plant::SimulatedLift;
+use super::rng::Rng;
+use crate::Floor;
+
+/// Standard traffic templates for evaluating lift policies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TrafficPattern {
+    /// Morning arrivals, almost everyone travels from the lobby to their floor
+    UpPeak,
+    /// Evening departures, almost everyone travels from their floor to the lobby
+    DownPeak,
+    /// Lunch time, people leave for and return from the lobby with some interfloor trips
+    Lunch,
+    /// Trips between any two floors, weighted by population
+    Uniform,
+}
+
+impl TrafficPattern {
+    /// Share of trips leaving the lobby, arriving at the lobby and between other floors
+    fn mix(&self) -> (f32, f32, f32) {
+        match self {
+            TrafficPattern::UpPeak => (0.9, 0.05, 0.05),
+            TrafficPattern::DownPeak => (0.05, 0.9, 0.05),
+            TrafficPattern::Lunch => (0.45, 0.45, 0.1),
+            TrafficPattern::Uniform => (0.0, 0.0, 1.0),
+        }
+    }
+}
+
+/// Building and load for a traffic generator
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrafficConfig {
+    pub pattern: TrafficPattern,
+    /// Mean passenger arrivals per minute, across the building
+    pub arrival_rate: f32,
+    /// The floor of the first entry in `populations`
+    pub lowest_floor: Floor,
+    /// The main entrance of the building
+    pub lobby: Floor,
+    /// Number of people working or living on each floor, from `lowest_floor` and up
+    pub populations: Vec<u32>,
+}
+
+/// Generates passenger arrivals following a traffic pattern, as a Poisson process
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficGenerator {
+    config: TrafficConfig,
+    rng: Rng,
+}
+
+impl TrafficGenerator {
+    /// Generators with the same config and seed produce the same arrivals
+    pub fn new(config: TrafficConfig, seed: u64) -> Self {
+        TrafficGenerator {
+            config,
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn config(&self) -> &TrafficConfig {
+        &self.config
+    }
+
+    /// Passengers arriving during the next `time_step` seconds, as origin and destination
+    pub fn arrivals(&mut self, time_step: f32) -> Vec<(Floor, Floor)> {
+        let mean = self.config.arrival_rate / 60.0 * time_step;
+        let count = self.rng.poisson(mean);
+        (0..count).filter_map(|_| self.trip()).collect()
+    }
+
+    /// Spawn the passengers arriving during the next `time_step` seconds, returns how many arrived
+    pub fn feed(&mut self, lift: &mut SimulatedLift, time_step: f32) -> usize {
+        let arrivals = self.arrivals(time_step);
+        for (origin, destination) in &arrivals {
+            lift.spawn_passenger(*origin, *destination);
+        }
+        arrivals.len()
+    }
+
+    fn trip(&mut self) -> Option<(Floor, Floor)> {
+        let lobby = self.config.lobby;
+        let (from_lobby, to_lobby, _) = self.config.pattern.mix();
+        let draw = self.rng.uniform();
+
+        let (origin, destination) = if draw < from_lobby {
+            (lobby, self.populated_floor(Some(lobby))?)
+        } else if draw < from_lobby + to_lobby {
+            (self.populated_floor(Some(lobby))?, lobby)
+        } else {
+            let origin = self.populated_floor(None)?;
+            (origin, self.populated_floor(Some(origin))?)
+        };
+        Some((origin, destination))
+    }
+
+    /// A floor picked in proportion to its population, never `except`
+    fn populated_floor(&mut self, except: Option<Floor>) -> Option<Floor> {
+        let lowest_floor = self.config.lowest_floor;
+        let weight = |index: usize, population: u32| match except {
+            Some(floor) if floor == lowest_floor + index as Floor => 0,
+            _ => population,
+        };
+        let populations = &self.config.populations;
+        let total: u32 = populations
+            .iter()
+            .enumerate()
+            .map(|(index, population)| weight(index, *population))
+            .sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = self.rng.below(total);
+        populations
+            .iter()
+            .enumerate()
+            .find(|(index, population)| {
+                let weight = weight(*index, **population);
+                if pick < weight {
+                    return true;
+                }
+                pick -= weight;
+                false
+            })
+            .map(|(index, _)| lowest_floor + index as Floor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(pattern: TrafficPattern) -> TrafficConfig {
+        TrafficConfig {
+            pattern,
+            arrival_rate: 120.0,
+            lowest_floor: -1,
+            lobby: 0,
+            populations: alloc::vec![0, 0, 50, 50, 100],
+        }
+    }
+
+    #[test]
+    fn patterns_follow_their_templates() {
+        let mut generator = TrafficGenerator::new(config(TrafficPattern::UpPeak), 3);
+        let arrivals: Vec<_> = (0..600).flat_map(|_| generator.arrivals(0.1)).collect();
+
+        // 120 per minute over a minute
+        assert!((100..140).contains(&arrivals.len()));
+        let from_lobby = arrivals.iter().filter(|(origin, _)| *origin == 0).count();
+        assert!(from_lobby * 10 > arrivals.len() * 8);
+        assert!(arrivals.iter().all(|(o, d)| o != d && *o != -1 && *d != -1));
+
+        let mut generator = TrafficGenerator::new(config(TrafficPattern::DownPeak), 3);
+        let arrivals: Vec<_> = (0..600).flat_map(|_| generator.arrivals(0.1)).collect();
+        let to_lobby = arrivals.iter().filter(|(_, dest)| *dest == 0).count();
+        assert!(to_lobby * 10 > arrivals.len() * 8);
+    }
+}
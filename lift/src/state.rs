@@ -0,0 +1,218 @@
+use crate::crc::crc16;
+use crate::{Direction, Faults, Mode, Position};
+
+/// Size in bytes of an encoded controller state
+pub const STATE_BLOB_SIZE: usize = 12;
+
+/// Marker at the start of every state blob, guards against reading erased or unrelated memory
+const MAGIC: [u8; 2] = *b"LC";
+
+/// Version of the blob layout, to be incremented whenever the layout changes
+///
+/// 1. Direction and mode
+/// 2. Adds the latched faults and the learned leveling error
+const VERSION: u8 = 2;
+
+/// Bits of the faults byte
+const SENSOR_READING_FAULT: u8 = 0x01;
+const EMERGENCY_STOP_FAULT: u8 = 0x02;
+
+/// The persistable part of the controller state.
+///
+/// This is meant to be stored by the host when power is lost, such that a restarted controller
+/// continues serving floors in the same direction rather than starting over from `Neutral`.
+/// The configured parameters are not part of the state, as they are provided when constructing the controller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ControllerState {
+    /// The direction of the elevator when the state was saved
+    pub direction: Direction,
+
+    /// The mode the controller was in when the state was saved
+    pub mode: Mode,
+
+    /// The faults latched when the state was saved, such that a restart does not hide them
+    pub faults: Faults,
+
+    /// Leveling error learned by the health monitor, the reference for the leveling error trend
+    pub leveling_error: Option<Position>,
+}
+
+/// Reasons a state blob could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StateError {
+    /// The blob is shorter than `STATE_BLOB_SIZE`
+    TooShort,
+    /// The blob does not start with the expected marker, typically erased or never written memory
+    BadMagic,
+    /// The blob was written with a layout this version of the crate does not know
+    UnsupportedVersion(u8),
+    /// The checksum does not match the contents
+    BadChecksum,
+    /// The checksum matches, but a field holds an invalid value
+    InvalidField,
+}
+
+impl Default for ControllerState {
+    fn default() -> Self {
+        ControllerState {
+            direction: Direction::Neutral,
+            mode: Mode::Idle,
+            faults: Faults::NONE,
+            leveling_error: None,
+        }
+    }
+}
+
+impl ControllerState {
+    /// Encode the state into a fixed-size blob suitable for writing directly to EEPROM or flash
+    ///
+    /// Layout: magic (2 bytes), version, direction, mode, fault bits, leveling error (f32, NaN when unknown),
+    /// CRC-16 of the preceding bytes, all little endian
+    pub fn to_bytes(&self) -> [u8; STATE_BLOB_SIZE] {
+        let mut bytes = [0u8; STATE_BLOB_SIZE];
+        bytes[0..2].copy_from_slice(&MAGIC);
+        bytes[2] = VERSION;
+        bytes[3] = direction_to_byte(self.direction);
+        bytes[4] = mode_to_byte(self.mode);
+        bytes[5] = faults_to_byte(self.faults);
+        let leveling_error = self.leveling_error.unwrap_or(f32::NAN);
+        bytes[6..10].copy_from_slice(&leveling_error.to_le_bytes());
+        let crc = crc16(&bytes[..10]);
+        bytes[10..12].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    /// Decode a blob created by `to_bytes`, validating the marker, version and checksum
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        if bytes.len() < STATE_BLOB_SIZE {
+            return Err(StateError::TooShort);
+        }
+        if bytes[0..2] != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        if bytes[2] != VERSION {
+            return Err(StateError::UnsupportedVersion(bytes[2]));
+        }
+        if crc16(&bytes[..10]).to_le_bytes() != bytes[10..12] {
+            return Err(StateError::BadChecksum);
+        }
+
+        let leveling_error = f32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+        let leveling_error = match leveling_error {
+            error if error.is_nan() => None,
+            error if error.is_finite() => Some(error),
+            _ => return Err(StateError::InvalidField),
+        };
+        match (
+            byte_to_direction(bytes[3]),
+            byte_to_mode(bytes[4]),
+            byte_to_faults(bytes[5]),
+        ) {
+            (Some(direction), Some(mode), Some(faults)) => Ok(ControllerState {
+                direction,
+                mode,
+                faults,
+                leveling_error,
+            }),
+            _ => Err(StateError::InvalidField),
+        }
+    }
+}
+
+pub(crate) fn faults_to_byte(faults: Faults) -> u8 {
+    let mut byte = 0;
+    if faults.sensor_reading {
+        byte |= SENSOR_READING_FAULT;
+    }
+    if faults.emergency_stop {
+        byte |= EMERGENCY_STOP_FAULT;
+    }
+    byte
+}
+
+pub(crate) fn byte_to_faults(byte: u8) -> Option<Faults> {
+    if byte & !(SENSOR_READING_FAULT | EMERGENCY_STOP_FAULT) != 0 {
+        return None;
+    }
+    Some(Faults {
+        sensor_reading: byte & SENSOR_READING_FAULT != 0,
+        emergency_stop: byte & EMERGENCY_STOP_FAULT != 0,
+    })
+}
+
+pub(crate) fn direction_to_byte(direction: Direction) -> u8 {
+    match direction {
+        Direction::Neutral => 0,
+        Direction::Up => 1,
+        Direction::Down => 2,
+    }
+}
+
+pub(crate) fn byte_to_direction(byte: u8) -> Option<Direction> {
+    match byte {
+        0 => Some(Direction::Neutral),
+        1 => Some(Direction::Up),
+        2 => Some(Direction::Down),
+        _ => None,
+    }
+}
+
+pub(crate) fn mode_to_byte(mode: Mode) -> u8 {
+    match mode {
+        Mode::Idle => 0,
+        Mode::Serving => 1,
+        Mode::EmergencyStop => 2,
+    }
+}
+
+pub(crate) fn byte_to_mode(byte: u8) -> Option<Mode> {
+    match byte {
+        0 => Some(Mode::Idle),
+        1 => Some(Mode::Serving),
+        2 => Some(Mode::EmergencyStop),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let state = ControllerState {
+            direction: Direction::Down,
+            mode: Mode::Serving,
+            faults: Faults {
+                sensor_reading: false,
+                emergency_stop: true,
+            },
+            leveling_error: Some(0.004),
+        };
+        assert_eq!(Ok(state), ControllerState::from_bytes(&state.to_bytes()));
+        let state = ControllerState::default();
+        assert_eq!(Ok(state), ControllerState::from_bytes(&state.to_bytes()));
+    }
+
+    #[test]
+    fn corrupt_blob_is_rejected() {
+        let state = ControllerState {
+            direction: Direction::Up,
+            mode: Mode::Serving,
+            ..ControllerState::default()
+        };
+        let mut bytes = state.to_bytes();
+        bytes[3] ^= 0x01;
+        assert_eq!(
+            Err(StateError::BadChecksum),
+            ControllerState::from_bytes(&bytes)
+        );
+        assert_eq!(
+            Err(StateError::BadMagic),
+            ControllerState::from_bytes(&[0xFF; STATE_BLOB_SIZE])
+        );
+    }
+}
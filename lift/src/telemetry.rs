@@ -0,0 +1,363 @@
+//! Compact framed telemetry protocol for streaming controller snapshots and events over a UART.
+//!
+//! Every frame has the layout
+//!
+//! | byte      | content                                        |
+//! |-----------|------------------------------------------------|
+//! | 0         | sync byte `0xA5`                               |
+//! | 1         | protocol version                               |
+//! | 2         | payload kind, 1 = snapshot, 2 = event          |
+//! | 3         | payload length `n`                             |
+//! | 4..4+n    | payload, multi-byte values are little endian   |
+//! | 4+n..6+n  | CRC-16/CCITT-FALSE of bytes 1..4+n, little endian |
+//!
+//! The decoder works on a byte at a time, so it can be fed directly from a receive interrupt or a serial port.
+//! After a frame with a bad length or checksum it resynchronises on the next sync byte received after the
+//! start of that frame, so a frame cut short does not take the frame after it along.
+//!
+//! Versions of the frame layout:
+//!
+//! 1. Snapshots and events
+//! 2. Adds the chime event, tag 5. A version 1 decoder rejects frames of version 2
+//! 3. Adds the latched fault bits to snapshots, growing them to 20 bytes
+//! 4. Adds the fault event, tag 6
+
+use crate::crc::crc16;
+use crate::state::{
+    byte_to_direction, byte_to_faults, byte_to_mode, direction_to_byte, faults_to_byte,
+    mode_to_byte,
+};
+use crate::{Event, EventKind, Snapshot};
+
+/// First byte of every frame
+pub const SYNC: u8 = 0xA5;
+
+/// Version of the frame layout, see the module documentation
+pub const VERSION: u8 = 4;
+
+const KIND_SNAPSHOT: u8 = 1;
+const KIND_EVENT: u8 = 2;
+
+const SNAPSHOT_SIZE: usize = 20;
+const EVENT_SIZE: usize = 14;
+const HEADER_SIZE: usize = 4;
+const CRC_SIZE: usize = 2;
+const MAX_PAYLOAD_SIZE: usize = SNAPSHOT_SIZE;
+
+/// Size of the largest possible frame
+pub const MAX_FRAME_SIZE: usize = HEADER_SIZE + MAX_PAYLOAD_SIZE + CRC_SIZE;
+
+/// The content of a telemetry frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Payload {
+    Snapshot(Snapshot),
+    Event(Event),
+}
+
+/// Reasons a frame was rejected by the decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError {
+    UnsupportedVersion(u8),
+    UnknownKind(u8),
+    BadLength,
+    BadChecksum,
+    InvalidPayload,
+}
+
+/// Encode a payload into a frame, returning the number of bytes of `buffer` used
+pub fn encode(payload: &Payload, buffer: &mut [u8; MAX_FRAME_SIZE]) -> usize {
+    let (kind, size) = match payload {
+        Payload::Snapshot(snapshot) => {
+            encode_snapshot(snapshot, &mut buffer[HEADER_SIZE..]);
+            (KIND_SNAPSHOT, SNAPSHOT_SIZE)
+        }
+        Payload::Event(event) => {
+            encode_event(event, &mut buffer[HEADER_SIZE..]);
+            (KIND_EVENT, EVENT_SIZE)
+        }
+    };
+
+    buffer[0] = SYNC;
+    buffer[1] = VERSION;
+    buffer[2] = kind;
+    buffer[3] = size as u8;
+    let end = HEADER_SIZE + size;
+    let crc = crc16(&buffer[1..end]);
+    buffer[end..end + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+    end + CRC_SIZE
+}
+
+/// Byte-wise frame decoder, skipping any bytes outside of frames
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    buffer: [u8; MAX_FRAME_SIZE],
+    len: usize,
+}
+
+impl FrameDecoder {
+    pub const fn new() -> Self {
+        FrameDecoder {
+            buffer: [0; MAX_FRAME_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Feed one received byte to the decoder.
+    /// Returns the decoded payload or the reason for rejecting it once a full frame has been received.
+    /// After a rejected frame, call `next_frame` until it returns `None`
+    pub fn push(&mut self, byte: u8) -> Option<Result<Payload, FrameError>> {
+        if self.len == 0 && byte != SYNC {
+            return None;
+        }
+
+        self.buffer[self.len] = byte;
+        self.len += 1;
+        self.next_frame()
+    }
+
+    /// Decode a frame that is already complete in the buffer. When a frame is rejected for its length or
+    /// checksum, the bytes received after its sync byte are searched again and may hold whole frames
+    pub fn next_frame(&mut self) -> Option<Result<Payload, FrameError>> {
+        if self.len < HEADER_SIZE {
+            return None;
+        }
+
+        let size = self.buffer[3] as usize;
+        if size > MAX_PAYLOAD_SIZE {
+            self.consume(1);
+            return Some(Err(FrameError::BadLength));
+        }
+        let frame_size = HEADER_SIZE + size + CRC_SIZE;
+        if self.len < frame_size {
+            return None;
+        }
+
+        let decoded = decode(&self.buffer[..frame_size]);
+        match decoded {
+            Err(FrameError::BadChecksum) => self.consume(1),
+            _ => self.consume(frame_size),
+        }
+        Some(decoded)
+    }
+
+    /// Drop the first `count` bytes of the buffer and any bytes before the next sync byte
+    fn consume(&mut self, count: usize) {
+        let start = self.buffer[count..self.len]
+            .iter()
+            .position(|byte| *byte == SYNC)
+            .map_or(self.len, |offset| count + offset);
+        self.buffer.copy_within(start..self.len, 0);
+        self.len -= start;
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decode one complete frame
+fn decode(frame: &[u8]) -> Result<Payload, FrameError> {
+    let end = frame.len() - CRC_SIZE;
+    if crc16(&frame[1..end]).to_le_bytes() != frame[end..] {
+        return Err(FrameError::BadChecksum);
+    }
+    if frame[1] != VERSION {
+        return Err(FrameError::UnsupportedVersion(frame[1]));
+    }
+
+    let payload = &frame[HEADER_SIZE..end];
+    match (frame[2], payload.len()) {
+        (KIND_SNAPSHOT, SNAPSHOT_SIZE) => decode_snapshot(payload).map(Payload::Snapshot),
+        (KIND_EVENT, EVENT_SIZE) => decode_event(payload).map(Payload::Event),
+        (KIND_SNAPSHOT, _) | (KIND_EVENT, _) => Err(FrameError::BadLength),
+        (kind, _) => Err(FrameError::UnknownKind(kind)),
+    }
+}
+
+fn encode_snapshot(snapshot: &Snapshot, out: &mut [u8]) {
+    out[0] = direction_to_byte(snapshot.direction);
+    out[1] = mode_to_byte(snapshot.mode);
+    out[2] = snapshot.target.is_some() as u8;
+    out[3..7].copy_from_slice(&snapshot.target.unwrap_or(0).to_le_bytes());
+    out[7..11].copy_from_slice(&snapshot.prefered_velocity.to_le_bytes());
+    out[11..15].copy_from_slice(&snapshot.floor_leeway.to_le_bytes());
+    out[15..19].copy_from_slice(&snapshot.velocity_epsilon.to_le_bytes());
+    out[19] = faults_to_byte(snapshot.faults);
+}
+
+fn decode_snapshot(bytes: &[u8]) -> Result<Snapshot, FrameError> {
+    let direction = byte_to_direction(bytes[0]).ok_or(FrameError::InvalidPayload)?;
+    let mode = byte_to_mode(bytes[1]).ok_or(FrameError::InvalidPayload)?;
+    let target = match bytes[2] {
+        0 => None,
+        1 => Some(i32::from_le_bytes(word(&bytes[3..7]))),
+        _ => return Err(FrameError::InvalidPayload),
+    };
+    let faults = byte_to_faults(bytes[19]).ok_or(FrameError::InvalidPayload)?;
+
+    Ok(Snapshot {
+        direction,
+        mode,
+        target,
+        prefered_velocity: f32::from_le_bytes(word(&bytes[7..11])),
+        floor_leeway: f32::from_le_bytes(word(&bytes[11..15])),
+        velocity_epsilon: f32::from_le_bytes(word(&bytes[15..19])),
+        faults,
+    })
+}
+
+fn encode_event(event: &Event, out: &mut [u8]) {
+    out[..EVENT_SIZE].fill(0);
+    out[0..8].copy_from_slice(&event.timestamp.to_le_bytes());
+    let (tag, floor, a, b) = match event.kind {
+        EventKind::Departed { floor, direction } => (0, floor, direction_to_byte(direction), 0),
+        EventKind::Arrived { floor } => (1, floor, 0, 0),
+        EventKind::DirectionChanged { from, to } => {
+            (2, 0, direction_to_byte(from), direction_to_byte(to))
+        }
+        EventKind::TargetSelected { floor } => (3, floor, 0, 0),
+        EventKind::EmergencyStop { activated } => (4, 0, activated as u8, 0),
+        EventKind::Chime { floor, direction } => (5, floor, direction_to_byte(direction), 0),
+        EventKind::Fault { faults } => (6, 0, faults_to_byte(faults), 0),
+    };
+    out[8] = tag;
+    out[9..13].copy_from_slice(&floor.to_le_bytes());
+    out[13] = a | (b << 4);
+}
+
+fn decode_event(bytes: &[u8]) -> Result<Event, FrameError> {
+    let mut timestamp = [0u8; 8];
+    timestamp.copy_from_slice(&bytes[0..8]);
+    let floor = i32::from_le_bytes(word(&bytes[9..13]));
+    let a = bytes[13] & 0x0F;
+    let b = bytes[13] >> 4;
+    let direction = |byte| byte_to_direction(byte).ok_or(FrameError::InvalidPayload);
+
+    let kind = match bytes[8] {
+        0 => EventKind::Departed {
+            floor,
+            direction: direction(a)?,
+        },
+        1 => EventKind::Arrived { floor },
+        2 => EventKind::DirectionChanged {
+            from: direction(a)?,
+            to: direction(b)?,
+        },
+        3 => EventKind::TargetSelected { floor },
+        4 => EventKind::EmergencyStop { activated: a != 0 },
+        5 => EventKind::Chime {
+            floor,
+            direction: direction(a)?,
+        },
+        6 => EventKind::Fault {
+            faults: byte_to_faults(a).ok_or(FrameError::InvalidPayload)?,
+        },
+        _ => return Err(FrameError::InvalidPayload),
+    };
+
+    Ok(Event {
+        timestamp: u64::from_le_bytes(timestamp),
+        kind,
+    })
+}
+
+fn word(bytes: &[u8]) -> [u8; 4] {
+    let mut word = [0u8; 4];
+    word.copy_from_slice(bytes);
+    word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, Faults, LiftController};
+
+    #[test]
+    fn decoder_skips_noise_and_rejects_corruption() {
+        let payloads = [
+            Payload::Snapshot(LiftController::new(1.0, 0.01, 0.01).snapshot()),
+            Payload::Event(Event {
+                timestamp: 1234,
+                kind: EventKind::DirectionChanged {
+                    from: Direction::Up,
+                    to: Direction::Down,
+                },
+            }),
+            Payload::Event(Event {
+                timestamp: 5678,
+                kind: EventKind::Fault {
+                    faults: Faults {
+                        sensor_reading: true,
+                        emergency_stop: false,
+                    },
+                },
+            }),
+        ];
+
+        let mut decoder = FrameDecoder::new();
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        for payload in payloads.iter() {
+            let len = encode(payload, &mut buffer);
+            for noise in [0x00, 0x13, 0xFF].iter() {
+                assert_eq!(None, decoder.push(*noise));
+            }
+            let decoded = buffer[..len].iter().filter_map(|b| decoder.push(*b)).next();
+            assert_eq!(Some(Ok(*payload)), decoded);
+        }
+
+        let len = encode(&payloads[1], &mut buffer);
+        buffer[6] ^= 0x40;
+        let decoded = buffer[..len].iter().filter_map(|b| decoder.push(*b)).next();
+        assert_eq!(Some(Err(FrameError::BadChecksum)), decoded);
+    }
+
+    #[test]
+    fn decoder_resyncs_after_a_frame_cut_short() {
+        let chime = Payload::Event(Event {
+            timestamp: 99,
+            kind: EventKind::Chime {
+                floor: -1,
+                direction: Direction::Down,
+            },
+        });
+        let snapshot = Payload::Snapshot(LiftController::new(1.0, 0.01, 0.01).snapshot());
+        let mut stream = [0u8; 3 * MAX_FRAME_SIZE];
+        let mut buffer = [0u8; MAX_FRAME_SIZE];
+        // The first frame loses its last bytes, the next two arrive whole
+        let mut len = encode(&chime, &mut buffer) - 5;
+        stream[..len].copy_from_slice(&buffer[..len]);
+        for payload in [chime, snapshot].iter() {
+            let size = encode(payload, &mut buffer);
+            stream[len..len + size].copy_from_slice(&buffer[..size]);
+            len += size;
+        }
+
+        let mut decoder = FrameDecoder::new();
+        let mut decoded = alloc::vec::Vec::new();
+        for byte in stream[..len].iter() {
+            decoded.extend(decoder.push(*byte));
+            while let Some(frame) = decoder.next_frame() {
+                decoded.push(frame);
+            }
+        }
+        assert_eq!(
+            alloc::vec![Err(FrameError::BadChecksum), Ok(chime), Ok(snapshot)],
+            decoded
+        );
+
+        // A length no frame has
+        for byte in [SYNC, VERSION, KIND_EVENT, 200].iter() {
+            decoded.extend(decoder.push(*byte));
+        }
+        assert_eq!(Some(&Err(FrameError::BadLength)), decoded.last());
+        let size = encode(&snapshot, &mut buffer);
+        let next = buffer[..size]
+            .iter()
+            .filter_map(|b| decoder.push(*b))
+            .next();
+        assert_eq!(Some(Ok(snapshot)), next);
+    }
+}
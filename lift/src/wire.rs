@@ -0,0 +1,74 @@
+//! Wire schema for communication between a controller and a supervisor over constrained links.
+//!
+//! The schema is defined by the serde representation of `Message`. With the `postcard` feature
+//! messages can be encoded to and decoded from compact byte buffers without allocation.
+//!
+//! To keep the schema stable new variants must only ever be appended, and existing variants
+//! and fields must not be reordered or changed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Action, Floor, Snapshot};
+
+/// Commands a supervisor may send to the lift
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command {
+    /// Register a stop at the given floor
+    StopAtFloor(Floor),
+    /// Activate (`true`) or release (`false`) the emergency stop
+    EmergencyStop(bool),
+    /// Ask the lift to respond with a telemetry snapshot
+    RequestTelemetry,
+}
+
+/// A single message on the link between a controller and a supervisor
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Message {
+    /// Supervisor to controller
+    Command(Command),
+    /// Controller to supervisor, the action decided on by the last poll
+    Action(Action),
+    /// Controller to supervisor, the current controller state
+    Telemetry(Snapshot),
+}
+
+/// Encode a message into the provided buffer, returning the used part of the buffer
+#[cfg(feature = "postcard")]
+pub fn encode<'a>(
+    message: &Message,
+    buffer: &'a mut [u8],
+) -> Result<&'a mut [u8], postcard::Error> {
+    postcard::to_slice(message, buffer)
+}
+
+/// Decode a message from a buffer created by `encode`
+#[cfg(feature = "postcard")]
+pub fn decode(bytes: &[u8]) -> Result<Message, postcard::Error> {
+    postcard::from_bytes(bytes)
+}
+
+#[cfg(all(test, feature = "postcard"))]
+mod tests {
+    use super::*;
+    use crate::LiftController;
+
+    #[test]
+    fn round_trip() {
+        let mut buffer = [0u8; 64];
+        let messages = [
+            Message::Command(Command::StopAtFloor(-2)),
+            Message::Action(Action {
+                target_velocity: 0.5,
+                is_stopped_at_current_floor: false,
+            }),
+            Message::Telemetry(LiftController::new(0.5, 0.01, 0.01).snapshot()),
+        ];
+
+        for message in messages.iter() {
+            let encoded = encode(message, &mut buffer).unwrap();
+            assert_eq!(*message, decode(encoded).unwrap());
+        }
+    }
+}
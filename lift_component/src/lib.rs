@@ -0,0 +1,181 @@
+//! WebAssembly component exposing the lift controller and simulation through the WIT interface in
+//! `wit/lift.wit`, for hosts using the component model such as tools built on wasmtime.
+//!
+//! Build with `cargo component build --release`, or with `cargo build --release --target wasm32-wasip2`.
+
+use core::cell::RefCell;
+
+use lift::sim::{DoorState, SimulatedLift};
+use lift::{Direction, Floor, LiftController, LiftSensors, Position, Velocity};
+
+wit_bindgen::generate!({
+    world: "lift",
+    path: "wit",
+});
+
+use exports::lift::sim::elevator::{self, Action, CarState, Readings};
+
+struct Component;
+
+impl elevator::Guest for Component {
+    type LiftController = Controller;
+    type LiftSimulation = Simulation;
+}
+
+export!(Component);
+
+impl From<Direction> for elevator::Direction {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Up => elevator::Direction::Up,
+            Direction::Down => elevator::Direction::Down,
+            Direction::Neutral => elevator::Direction::Neutral,
+        }
+    }
+}
+
+impl LiftSensors for Readings {
+    fn current_floor(&self) -> Position {
+        self.current_floor
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        self.current_velocity
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        &self.floors_to_stop_at
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        self.is_emergency_stop_activated
+    }
+}
+
+// Resource methods only borrow the resource, so the state is kept in a RefCell
+pub struct Controller(RefCell<LiftController>);
+
+impl elevator::GuestLiftController for Controller {
+    fn new(prefered_velocity: f32, floor_leeway: f32, velocity_epsilon: f32) -> Self {
+        Controller(RefCell::new(LiftController::new(
+            prefered_velocity,
+            floor_leeway,
+            velocity_epsilon,
+        )))
+    }
+
+    fn poll(&self, readings: Readings, time_step: f32) -> Action {
+        let action = self.0.borrow_mut().poll(&readings, time_step);
+        Action {
+            target_velocity: action.target_velocity,
+            is_stopped_at_current_floor: action.is_stopped_at_current_floor,
+        }
+    }
+
+    fn direction(&self) -> elevator::Direction {
+        self.0.borrow().snapshot().direction.into()
+    }
+
+    fn time_to_floor(&self, readings: Readings, floor: Floor, average_stop: f32) -> Option<f32> {
+        self.0
+            .borrow()
+            .time_to_floor(&readings, floor, average_stop)
+    }
+}
+
+struct SimulationState {
+    lift: SimulatedLift,
+    controller: LiftController,
+}
+
+pub struct Simulation(RefCell<SimulationState>);
+
+impl elevator::GuestLiftSimulation for Simulation {
+    fn new(prefered_velocity: f32, floor_leeway: f32, velocity_epsilon: f32) -> Self {
+        Simulation(RefCell::new(SimulationState {
+            lift: SimulatedLift::new(0.0),
+            controller: LiftController::new(prefered_velocity, floor_leeway, velocity_epsilon),
+        }))
+    }
+
+    fn stop_at_floor(&self, floor: Floor) -> bool {
+        self.0.borrow_mut().lift.register_call(floor)
+    }
+
+    fn cancel_floor(&self, floor: Floor) -> bool {
+        self.0.borrow_mut().lift.cancel_call(floor)
+    }
+
+    fn pending_stops(&self) -> Vec<Floor> {
+        self.0.borrow().lift.calls().to_vec()
+    }
+
+    fn emergency_stop(&self, activated: bool) {
+        self.0.borrow_mut().lift.set_emergency_stop(activated)
+    }
+
+    fn spawn_passengers(
+        &self,
+        origin: Floor,
+        destination: Floor,
+        count: u32,
+    ) -> Result<(), String> {
+        if origin == destination {
+            return Err(format!(
+                "the passengers are already at floor {}",
+                destination
+            ));
+        }
+        let lift = &mut self.0.borrow_mut().lift;
+        for _ in 0..count {
+            lift.spawn_passenger(origin, destination);
+        }
+        Ok(())
+    }
+
+    fn step(&self, time_step: f32) -> Result<CarState, String> {
+        if !time_step.is_finite() || time_step < 0.0 {
+            return Err(format!(
+                "the time step must be a number of seconds of at least 0, got {}",
+                time_step
+            ));
+        }
+        let mut state = self.0.borrow_mut();
+        let SimulationState { lift, controller } = &mut *state;
+        let action = controller.poll(&*lift, time_step);
+        let floor = lift.position().round() as Floor;
+        let arriving = action.is_stopped_at_current_floor && lift.calls().contains(&floor);
+        lift.accept_action(action, time_step);
+        // Open the doors at every stop, not only when passengers board or alight
+        if arriving && lift.dwell_remaining() == 0.0 {
+            lift.start_dwell(0, 0);
+        }
+        Ok(state.car_state())
+    }
+
+    fn state(&self) -> CarState {
+        self.0.borrow().car_state()
+    }
+
+    fn time_to_floor(&self, floor: Floor, average_stop: f32) -> Option<f32> {
+        let state = self.0.borrow();
+        state
+            .controller
+            .time_to_floor(&state.lift, floor, average_stop)
+    }
+}
+
+impl SimulationState {
+    fn car_state(&self) -> CarState {
+        let snapshot = self.controller.snapshot();
+        CarState {
+            position: self.lift.position(),
+            velocity: self.lift.velocity(),
+            direction: snapshot.direction.into(),
+            next_target: snapshot.target,
+            doors_open: self.lift.door_state() != DoorState::Closed,
+            is_emergency_stop_activated: self.lift.is_emergency_stop_activated(),
+            riders: self.lift.riders().len() as u32,
+        }
+    }
+}
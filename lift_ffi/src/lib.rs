@@ -0,0 +1,328 @@
+//! C interface to the lift controller, for firmware written in C.
+//!
+//! The crate builds as a static library, a header can be generated with
+//! `cbindgen --config cbindgen.toml --output lift.h`.
+//!
+//! The controller lives in caller-provided storage, such that it can be placed in a static variable
+//! without any heap. Sensors are provided as a struct of function pointers sharing a context pointer.
+
+#![cfg_attr(not(test), no_std)]
+
+use core::mem::{align_of, size_of};
+use core::slice;
+
+use lift::{LiftController, LiftSensors, STATE_BLOB_SIZE};
+
+// The aliases are declared here rather than imported such that the header defines them,
+// the `LiftSensors` implementation below fails to build if they no longer match the core crate
+
+/// Position in floors, see `lift::Position`
+pub type Position = f32;
+
+/// Velocity in floors per second, see `lift::Velocity`
+pub type Velocity = f32;
+
+/// Floor number, see `lift::Floor`
+pub type Floor = i32;
+
+/// Size in bytes of `LiftControllerStorage`
+pub const LIFT_CONTROLLER_SIZE: usize = 256;
+
+/// Size in bytes of a saved controller state, spelled out for the header
+pub const LIFT_STATE_SIZE: usize = 12;
+
+/// Storage for a controller, to be initialised with `lift_controller_new` before use
+#[repr(C, align(8))]
+pub struct LiftControllerStorage {
+    pub bytes: [u8; LIFT_CONTROLLER_SIZE],
+}
+
+const _: () = assert!(LIFT_STATE_SIZE == STATE_BLOB_SIZE);
+const _: () = assert!(size_of::<LiftController>() <= LIFT_CONTROLLER_SIZE);
+const _: () = assert!(align_of::<LiftController>() <= align_of::<LiftControllerStorage>());
+
+/// Sensor readings provided by the firmware, every function is called with `context`
+#[repr(C)]
+pub struct LiftSensorsC {
+    pub context: *mut core::ffi::c_void,
+    pub current_floor: extern "C" fn(context: *mut core::ffi::c_void) -> Position,
+    pub current_velocity: extern "C" fn(context: *mut core::ffi::c_void) -> Velocity,
+    /// Returns a pointer to the floors to stop at and writes the number of floors to `len`.
+    /// The floors must stay valid until the call into the library returns
+    pub floors_to_stop_at:
+        extern "C" fn(context: *mut core::ffi::c_void, len: *mut usize) -> *const Floor,
+    pub is_emergency_stop_activated: extern "C" fn(context: *mut core::ffi::c_void) -> bool,
+}
+
+/// Recommended action from the controller, see `lift::Action`
+#[repr(C)]
+pub struct LiftAction {
+    pub target_velocity: Velocity,
+    pub is_stopped_at_current_floor: bool,
+}
+
+impl LiftSensors for LiftSensorsC {
+    fn current_floor(&self) -> Position {
+        (self.current_floor)(self.context)
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        (self.current_velocity)(self.context)
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        let mut len = 0;
+        let floors = (self.floors_to_stop_at)(self.context, &mut len);
+        if floors.is_null() || len == 0 {
+            &[]
+        } else {
+            // Safety: the firmware guarantees the floors are valid for the duration of the call
+            unsafe { slice::from_raw_parts(floors, len) }
+        }
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        (self.is_emergency_stop_activated)(self.context)
+    }
+}
+
+fn controller(storage: &LiftControllerStorage) -> &LiftController {
+    // Safety: storage is large and aligned enough, and initialised by `lift_controller_new`
+    unsafe { &*(storage.bytes.as_ptr() as *const LiftController) }
+}
+
+fn controller_mut(storage: &mut LiftControllerStorage) -> &mut LiftController {
+    // Safety: storage is large and aligned enough, and initialised by `lift_controller_new`
+    unsafe { &mut *(storage.bytes.as_mut_ptr() as *mut LiftController) }
+}
+
+/// Initialise a controller in the given storage
+///
+/// # Safety
+/// `storage` must be a valid pointer to a `LiftControllerStorage`
+#[no_mangle]
+pub unsafe extern "C" fn lift_controller_new(
+    storage: *mut LiftControllerStorage,
+    prefered_velocity: Velocity,
+    floor_leeway: Position,
+    velocity_epsilon: Velocity,
+) {
+    let controller = LiftController::new(prefered_velocity, floor_leeway, velocity_epsilon);
+    (storage as *mut LiftController).write(controller);
+}
+
+/// Poll the controller for the next action to perform
+///
+/// # Safety
+/// `storage` must be initialised with `lift_controller_new` and `sensors` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn lift_controller_poll(
+    storage: *mut LiftControllerStorage,
+    sensors: *const LiftSensorsC,
+    time_step: f32,
+) -> LiftAction {
+    let action = controller_mut(&mut *storage).poll(&*sensors, time_step);
+    LiftAction {
+        target_velocity: action.target_velocity,
+        is_stopped_at_current_floor: action.is_stopped_at_current_floor,
+    }
+}
+
+/// Estimate the time until the lift arrives at `floor`.
+/// Returns false if no estimate is available, otherwise the estimate is written to `seconds`
+///
+/// # Safety
+/// `storage` must be initialised with `lift_controller_new`, `sensors` and `seconds` must be valid pointers
+#[no_mangle]
+pub unsafe extern "C" fn lift_controller_time_to_floor(
+    storage: *const LiftControllerStorage,
+    sensors: *const LiftSensorsC,
+    floor: Floor,
+    average_stop: f32,
+    seconds: *mut f32,
+) -> bool {
+    match controller(&*storage).time_to_floor(&*sensors, floor, average_stop) {
+        Some(time) => {
+            *seconds = time;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Write the persistable controller state to `blob`, which must hold `LIFT_STATE_SIZE` bytes
+///
+/// # Safety
+/// `storage` must be initialised with `lift_controller_new` and `blob` must be valid for `LIFT_STATE_SIZE` bytes
+#[no_mangle]
+pub unsafe extern "C" fn lift_controller_save_state(
+    storage: *const LiftControllerStorage,
+    blob: *mut u8,
+) {
+    let bytes = controller(&*storage).save_state().to_bytes();
+    slice::from_raw_parts_mut(blob, LIFT_STATE_SIZE).copy_from_slice(&bytes);
+}
+
+/// Restore the controller state from a blob written by `lift_controller_save_state`.
+/// Returns false if the blob was corrupt, in which case the controller falls back to the default state
+///
+/// # Safety
+/// `storage` must be initialised with `lift_controller_new` and `blob` must be valid for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn lift_controller_restore_state(
+    storage: *mut LiftControllerStorage,
+    blob: *const u8,
+    len: usize,
+) -> bool {
+    let bytes = slice::from_raw_parts(blob, len);
+    controller_mut(&mut *storage)
+        .restore_state_from_bytes(bytes)
+        .is_ok()
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ffi::c_void;
+    use core::mem::MaybeUninit;
+
+    /// A car the controller drives, read by the sensor functions through the context pointer
+    struct Car {
+        position: Position,
+        velocity: Velocity,
+        calls: Vec<Floor>,
+    }
+
+    fn car(context: *mut c_void) -> &'static Car {
+        unsafe { &*(context as *const Car) }
+    }
+
+    extern "C" fn current_floor(context: *mut c_void) -> Position {
+        car(context).position
+    }
+
+    extern "C" fn current_velocity(context: *mut c_void) -> Velocity {
+        car(context).velocity
+    }
+
+    extern "C" fn floors_to_stop_at(context: *mut c_void, len: *mut usize) -> *const Floor {
+        let calls = &car(context).calls;
+        unsafe { *len = calls.len() };
+        calls.as_ptr()
+    }
+
+    extern "C" fn is_emergency_stop_activated(_context: *mut c_void) -> bool {
+        false
+    }
+
+    fn sensors(car: &mut Car) -> LiftSensorsC {
+        LiftSensorsC {
+            context: car as *mut Car as *mut c_void,
+            current_floor,
+            current_velocity,
+            floors_to_stop_at,
+            is_emergency_stop_activated,
+        }
+    }
+
+    fn new_controller() -> LiftControllerStorage {
+        let mut storage = MaybeUninit::<LiftControllerStorage>::uninit();
+        unsafe {
+            lift_controller_new(storage.as_mut_ptr(), 0.5, 0.001, 0.001);
+            storage.assume_init()
+        }
+    }
+
+    #[test]
+    fn storage_holds_a_controller() {
+        assert_eq!(LIFT_CONTROLLER_SIZE, size_of::<LiftControllerStorage>());
+        assert_eq!(8, align_of::<LiftControllerStorage>());
+        assert!(size_of::<LiftController>() <= LIFT_CONTROLLER_SIZE);
+        assert!(align_of::<LiftController>() <= align_of::<LiftControllerStorage>());
+    }
+
+    #[test]
+    fn header_keeps_the_storage_aligned() {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir)).unwrap();
+        let mut header = Vec::new();
+        cbindgen::generate_with_config(dir, config)
+            .unwrap()
+            .write(&mut header);
+        let header = String::from_utf8(header).unwrap();
+        assert!(
+            header.contains("typedef struct LIFT_ALIGNED(8) LiftControllerStorage {")
+                && header.contains("#define LIFT_ALIGNED(n) __attribute__((aligned(n)))"),
+            "{}",
+            header
+        );
+    }
+
+    #[test]
+    fn drives_a_car_to_a_call_through_the_c_interface() {
+        let mut storage = new_controller();
+        let mut car = Car {
+            position: 0.0,
+            velocity: 0.0,
+            calls: vec![3],
+        };
+        let time_step = 0.1;
+
+        let sensors = sensors(&mut car);
+        let mut stopped = false;
+        for step in 0..100 {
+            let action = unsafe { lift_controller_poll(&mut storage, &sensors, time_step) };
+            // Estimates are only given once the car is moving
+            if step == 1 {
+                let mut seconds = 0.0;
+                assert!(unsafe {
+                    lift_controller_time_to_floor(&storage, &sensors, 3, 0.0, &mut seconds)
+                });
+                assert!(seconds > 0.0);
+            }
+            if action.is_stopped_at_current_floor {
+                stopped = true;
+                break;
+            }
+            let car = unsafe { &mut *(sensors.context as *mut Car) };
+            car.velocity = action.target_velocity;
+            car.position += action.target_velocity * time_step;
+        }
+        assert!(stopped);
+        assert!((car.position - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn saved_state_restores_into_a_new_controller() {
+        let mut storage = new_controller();
+        let mut car = Car {
+            position: 0.0,
+            velocity: 0.0,
+            calls: vec![-2],
+        };
+        let sensors = sensors(&mut car);
+        unsafe { lift_controller_poll(&mut storage, &sensors, 0.1) };
+
+        let mut blob = [0u8; LIFT_STATE_SIZE];
+        unsafe { lift_controller_save_state(&storage, blob.as_mut_ptr()) };
+        let mut restored = new_controller();
+        assert!(unsafe { lift_controller_restore_state(&mut restored, blob.as_ptr(), blob.len()) });
+        assert_eq!(
+            controller(&storage).save_state(),
+            controller(&restored).save_state()
+        );
+        assert_eq!(
+            controller(&storage).snapshot().direction,
+            controller(&restored).snapshot().direction
+        );
+
+        // Too short to be a state
+        assert!(!unsafe { lift_controller_restore_state(&mut restored, blob.as_ptr(), 1) });
+    }
+}
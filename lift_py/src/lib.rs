@@ -0,0 +1,331 @@
+//! Python bindings for the lift controller and simulator, for batch experiments from Python.
+//!
+//! Build the module with [maturin](https://www.maturin.rs), `maturin develop --features extension-module`.
+//! Reports and observations are returned as plain dicts and tuples, so they can go straight into
+//! pandas or numpy.
+
+// The pymethods macro converts every PyResult into itself, which clippy flags on the user's code
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use lift::sim::{
+    self, ControllerConfig, DispatchConfig, Metrics, MetricsReport, Observation, Scenario,
+    SimulatedLift, Summary, TrafficConfig, TrafficPattern,
+};
+use lift::{Floor, LiftSensors, Position, Velocity};
+
+/// Sensor values passed in from Python for a single poll
+struct Readings {
+    position: Position,
+    velocity: Velocity,
+    calls: Vec<Floor>,
+    emergency_stop: bool,
+}
+
+impl LiftSensors for Readings {
+    fn current_floor(&self) -> Position {
+        self.position
+    }
+
+    fn current_velocity(&self) -> Velocity {
+        self.velocity
+    }
+
+    fn floors_to_stop_at(&self) -> &[Floor] {
+        &self.calls
+    }
+
+    fn is_emergency_stop_activated(&self) -> bool {
+        self.emergency_stop
+    }
+}
+
+fn controller_config(
+    prefered_velocity: Velocity,
+    floor_leeway: Position,
+    velocity_epsilon: Velocity,
+) -> ControllerConfig {
+    ControllerConfig {
+        prefered_velocity,
+        floor_leeway,
+        velocity_epsilon,
+    }
+}
+
+fn summary_dict<'py>(py: Python<'py>, summary: &Summary) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("count", summary.count)?;
+    dict.set_item("mean", summary.mean)?;
+    dict.set_item("p95", summary.p95)?;
+    dict.set_item("max", summary.max)?;
+    Ok(dict)
+}
+
+fn report_dict<'py>(py: Python<'py>, report: &MetricsReport) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("wait_time", summary_dict(py, &report.wait_time)?)?;
+    dict.set_item("journey_time", summary_dict(py, &report.journey_time)?)?;
+    dict.set_item("stops", report.stops)?;
+    dict.set_item("distance", report.distance)?;
+    dict.set_item("load_factor", report.load_factor)?;
+    dict.set_item("duration", report.duration)?;
+    dict.set_item("energy_consumed", report.energy.consumed)?;
+    dict.set_item("energy_regenerated", report.energy.regenerated)?;
+    Ok(dict)
+}
+
+fn observation_dict<'py>(
+    py: Python<'py>,
+    observation: &Observation,
+) -> PyResult<Bound<'py, PyDict>> {
+    let cars = observation
+        .cars
+        .iter()
+        .map(|car| {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("position", car.position)?;
+            dict.set_item("velocity", car.velocity)?;
+            dict.set_item("calls", car.calls)?;
+            dict.set_item("waiting", car.waiting)?;
+            dict.set_item("riders", car.riders)?;
+            Ok(dict)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let dict = PyDict::new_bound(py);
+    dict.set_item("time", observation.time)?;
+    dict.set_item("cars", cars)?;
+    dict.set_item("call", observation.call)?;
+    Ok(dict)
+}
+
+fn traffic_pattern(name: &str) -> PyResult<TrafficPattern> {
+    match name {
+        "up_peak" => Ok(TrafficPattern::UpPeak),
+        "down_peak" => Ok(TrafficPattern::DownPeak),
+        "lunch" => Ok(TrafficPattern::Lunch),
+        "uniform" => Ok(TrafficPattern::Uniform),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown traffic pattern {:?}, expected up_peak, down_peak, lunch or uniform",
+            name
+        ))),
+    }
+}
+
+/// The lift controller, polled with sensor values from Python
+#[pyclass]
+struct LiftController(lift::LiftController);
+
+#[pymethods]
+impl LiftController {
+    #[new]
+    #[pyo3(signature = (prefered_velocity = 1.0, floor_leeway = 0.01, velocity_epsilon = 0.01))]
+    fn new(
+        prefered_velocity: Velocity,
+        floor_leeway: Position,
+        velocity_epsilon: Velocity,
+    ) -> Self {
+        LiftController(controller_config(prefered_velocity, floor_leeway, velocity_epsilon).build())
+    }
+
+    /// Returns the target velocity and whether the car is stopped at a floor it should serve
+    #[pyo3(signature = (position, velocity, calls, time_step, emergency_stop = false))]
+    fn poll(
+        &mut self,
+        position: Position,
+        velocity: Velocity,
+        calls: Vec<Floor>,
+        time_step: f32,
+        emergency_stop: bool,
+    ) -> (Velocity, bool) {
+        let readings = Readings {
+            position,
+            velocity,
+            calls,
+            emergency_stop,
+        };
+        let action = self.0.poll(&readings, time_step);
+        (action.target_velocity, action.is_stopped_at_current_floor)
+    }
+}
+
+/// A simulated car driven by a controller, collecting metrics as it runs
+#[pyclass]
+struct Simulation {
+    lift: SimulatedLift,
+    controller: lift::LiftController,
+    metrics: Metrics,
+}
+
+#[pymethods]
+impl Simulation {
+    #[new]
+    #[pyo3(signature = (initial_floor = 0, prefered_velocity = 1.0, floor_leeway = 0.01, velocity_epsilon = 0.01))]
+    fn new(
+        initial_floor: Floor,
+        prefered_velocity: Velocity,
+        floor_leeway: Position,
+        velocity_epsilon: Velocity,
+    ) -> Self {
+        Simulation {
+            lift: SimulatedLift::new(initial_floor as Position),
+            controller: controller_config(prefered_velocity, floor_leeway, velocity_epsilon)
+                .build(),
+            metrics: Metrics::new(),
+        }
+    }
+
+    fn register_call(&mut self, floor: Floor) -> bool {
+        self.lift.register_call(floor)
+    }
+
+    /// Returns the id of the passenger, or None if origin and destination are the same
+    fn spawn_passenger(&mut self, origin: Floor, destination: Floor) -> Option<u32> {
+        self.lift.spawn_passenger(origin, destination)
+    }
+
+    fn set_emergency_stop(&mut self, activated: bool) {
+        self.lift.set_emergency_stop(activated);
+    }
+
+    /// Advance the simulation by `steps` steps of `time_step` seconds
+    #[pyo3(signature = (time_step, steps = 1))]
+    fn step(&mut self, time_step: f32, steps: u32) {
+        for _ in 0..steps {
+            let action = self.controller.poll(&self.lift, time_step);
+            self.lift.accept_action(action, time_step);
+            self.metrics.record(&self.lift, time_step);
+        }
+    }
+
+    #[getter]
+    fn position(&self) -> Position {
+        self.lift.position()
+    }
+
+    #[getter]
+    fn velocity(&self) -> Velocity {
+        self.lift.velocity()
+    }
+
+    #[getter]
+    fn time(&self) -> f32 {
+        self.lift.time()
+    }
+
+    #[getter]
+    fn calls(&self) -> Vec<Floor> {
+        self.lift.calls().to_vec()
+    }
+
+    fn report<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        report_dict(py, &self.metrics.report())
+    }
+}
+
+/// Environment assigning arriving passengers to cars, see `lift::sim::DispatchEnv`
+#[pyclass]
+struct DispatchEnv(sim::DispatchEnv);
+
+#[pymethods]
+impl DispatchEnv {
+    #[new]
+    #[pyo3(signature = (
+        populations,
+        cars = 2,
+        capacity = 13,
+        pattern = "uniform",
+        arrival_rate = 4.0,
+        lowest_floor = 0,
+        lobby = 0,
+        time_step = 0.1,
+        duration = 3600.0,
+        prefered_velocity = 1.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        populations: Vec<u32>,
+        cars: usize,
+        capacity: usize,
+        pattern: &str,
+        arrival_rate: f32,
+        lowest_floor: Floor,
+        lobby: Floor,
+        time_step: f32,
+        duration: f32,
+        prefered_velocity: Velocity,
+    ) -> PyResult<Self> {
+        if cars == 0 {
+            return Err(PyValueError::new_err("at least one car is needed"));
+        }
+        Ok(DispatchEnv(sim::DispatchEnv::new(DispatchConfig {
+            cars,
+            capacity,
+            controller: controller_config(prefered_velocity, 0.01, 0.01),
+            traffic: TrafficConfig {
+                pattern: traffic_pattern(pattern)?,
+                arrival_rate,
+                lowest_floor,
+                lobby,
+                populations,
+            },
+            time_step,
+            duration,
+        })))
+    }
+
+    #[getter]
+    fn action_space(&self) -> usize {
+        self.0.action_space()
+    }
+
+    fn reset<'py>(&mut self, py: Python<'py>, seed: u64) -> PyResult<Bound<'py, PyDict>> {
+        observation_dict(py, &self.0.reset(seed))
+    }
+
+    /// Returns the observation, the reward and whether the episode is done
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        car: usize,
+    ) -> PyResult<(Bound<'py, PyDict>, f32, bool)> {
+        let result = self
+            .0
+            .step(car)
+            .map_err(|e| PyValueError::new_err(format!("no car {}", e.0)))?;
+        Ok((
+            observation_dict(py, &result.observation)?,
+            result.reward,
+            result.done,
+        ))
+    }
+}
+
+/// Metrics report and the unmet expectations with their observed values
+type ScenarioResult<'py> = (Bound<'py, PyDict>, Vec<(String, f32)>);
+
+/// Run a JSON scenario, returns the metrics report and the unmet expectations
+#[pyfunction]
+fn run_scenario<'py>(py: Python<'py>, json: &str) -> PyResult<ScenarioResult<'py>> {
+    let outcome = Scenario::from_json(json)
+        .and_then(|scenario| scenario.run())
+        .map_err(|e| PyValueError::new_err(format!("invalid scenario: {:?}", e)))?;
+    let failures = outcome
+        .failures
+        .iter()
+        .map(|failure| (format!("{:?}", failure.expectation), failure.observed))
+        .collect();
+    Ok((report_dict(py, &outcome.report)?, failures))
+}
+
+#[pymodule]
+fn lift_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<LiftController>()?;
+    m.add_class::<Simulation>()?;
+    m.add_class::<DispatchEnv>()?;
+    m.add_function(wrap_pyfunction!(run_scenario, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Headless simulator running a scenario file, for development and local testing.
+//!
+//! ```text
+//! lift-sim [--speed FACTOR] [--live] <scenario.json>
+//! ```
+//!
+//! By default the scenario runs as fast as possible, `--speed 1` runs it in real time and
+//! `--speed 10` ten times faster. `--live` prints the state of the car every simulated second.
+//! The metrics and any unmet expectations are printed at the end, and the exit code is 1 if an
+//! expectation failed and 2 if the scenario could not be loaded.
+
+use std::process::ExitCode;
+use std::time::Duration;
+use std::{env, fs, thread};
+
+use lift::sim::{MetricsReport, Scenario, Summary};
+
+const USAGE: &str = "usage: lift-sim [--speed FACTOR] [--live] <scenario.json>";
+
+struct Options {
+    path: String,
+    /// Simulated seconds per real second, as fast as possible when absent
+    speed: Option<f32>,
+    live: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut path = None;
+    let mut speed = None;
+    let mut live = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--live" => live = true,
+            "--speed" => {
+                let factor = args
+                    .next()
+                    .and_then(|s| s.parse::<f32>().ok())
+                    .filter(|f| *f > 0.0)
+                    .ok_or("--speed expects a positive number")?;
+                speed = Some(factor);
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    Ok(Options {
+        path: path.ok_or(USAGE)?,
+        speed,
+        live,
+    })
+}
+
+fn load(path: &str) -> Result<Scenario, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    Scenario::from_json(&json).map_err(|e| format!("invalid scenario {}: {:?}", path, e))
+}
+
+fn print_summary(name: &str, summary: &Summary) {
+    println!(
+        "{:<12} n={:<5} mean={:.1}s p95={:.1}s max={:.1}s",
+        name, summary.count, summary.mean, summary.p95, summary.max
+    );
+}
+
+fn print_report(report: &MetricsReport) {
+    print_summary("wait", &report.wait_time);
+    print_summary("journey", &report.journey_time);
+    println!(
+        "{:<12} stops={} distance={:.1} floors load={:.2} duration={:.1}s",
+        "car", report.stops, report.distance, report.load_factor, report.duration
+    );
+    println!(
+        "{:<12} consumed={:.1}Wh regenerated={:.1}Wh net={:.1}Wh",
+        "energy",
+        report.energy.consumed,
+        report.energy.regenerated,
+        report.energy.net()
+    );
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+    let scenario = match load(&options.path) {
+        Ok(scenario) => scenario,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    println!("running {}", scenario.name);
+    let step_delay = options
+        .speed
+        .map(|speed| Duration::from_secs_f32(scenario.time_step / speed));
+    let mut next_print = 0.0;
+    let outcome = scenario.run_observed(&mut scenario.controller.build(), &mut |lift, action| {
+        if options.live && lift.time() >= next_print {
+            println!(
+                "t={:>7.1}s position={:>6.2} velocity={:>5.2} commanded={:>5.2} calls={:?} waiting={} riding={}",
+                lift.time(),
+                lift.position(),
+                lift.velocity(),
+                action.target_velocity,
+                lift.calls(),
+                lift.waiting().len(),
+                lift.riders().len()
+            );
+            next_print += 1.0;
+        }
+        if let Some(delay) = step_delay {
+            thread::sleep(delay);
+        }
+    });
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            eprintln!("invalid scenario {}: {:?}", options.path, e);
+            return ExitCode::from(2);
+        }
+    };
+
+    print_report(&outcome.report);
+    for failure in &outcome.failures {
+        println!(
+            "FAILED {:?}, observed {}",
+            failure.expectation, failure.observed
+        );
+    }
+
+    if outcome.passed() {
+        println!("passed");
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
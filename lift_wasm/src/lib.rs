@@ -1,130 +1,1820 @@
+mod sweep;
 mod utils;
+mod worker;
 
-#[macro_use]
-extern crate lazy_static;
-
+use js_sys::{Float32Array, Function};
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
 use lift::*;
-
-use std::sync::Mutex;
+use lift::sim::{
+    DwellModel, Fault, Metrics, Scenario, ScriptPlayer, SimulatedLift, TrafficConfig, TrafficGenerator, TrafficPattern
+};
 
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-struct Lift {
-    position: Position,
-    velocity: Velocity,
-    floors_to_stop_at: Vec<Floor>,
-    is_emergency_stop_activated: bool,
-    is_stopped: bool
+/// Direction the controller is travelling in
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TravelDirection {
+    Up,
+    Down,
+    Neutral
 }
 
-impl Lift {
-    fn remove_current_floor(&mut self) {
-        let current_floor = self.position.round() as Floor;
-        if let Some(index) = self
-            .floors_to_stop_at
-            .iter()
-            .enumerate()
-            .find(|(_, e)| **e == current_floor)
-            .map(|(i, _)| i)
-        {
-            self.floors_to_stop_at.remove(index);
-        };
+impl From<Direction> for TravelDirection {
+    fn from(direction: Direction) -> TravelDirection {
+        match direction {
+            Direction::Up => TravelDirection::Up,
+            Direction::Down => TravelDirection::Down,
+            Direction::Neutral => TravelDirection::Neutral
+        }
     }
 }
 
-impl LiftSensors for Lift {
-    fn current_floor(&self) -> Position {
-        self.position
-    }
+/// Motion of the car doors
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing
+}
 
-    fn current_velocity(&self) -> Velocity {
-        self.velocity
+impl From<lift::sim::DoorState> for DoorState {
+    fn from(state: lift::sim::DoorState) -> DoorState {
+        match state {
+            lift::sim::DoorState::Closed => DoorState::Closed,
+            lift::sim::DoorState::Opening => DoorState::Opening,
+            lift::sim::DoorState::Open => DoorState::Open,
+            lift::sim::DoorState::Closing => DoorState::Closing
+        }
     }
+}
+
+/// What the controller is doing
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum OperatingMode {
+    /// Waiting at a floor without calls to serve
+    Idle,
+    Serving,
+    /// On the way to the next floor to stop there, see `LiftSimulation::emergency_stop_at_floor`
+    StoppingAtFloor,
+    /// Held still by the emergency stop
+    EmergencyStop
+}
 
-    fn floors_to_stop_at(&self) -> &[Floor] {
-        &self.floors_to_stop_at
+impl From<Mode> for OperatingMode {
+    fn from(mode: Mode) -> OperatingMode {
+        match mode {
+            Mode::Idle => OperatingMode::Idle,
+            Mode::Serving => OperatingMode::Serving,
+            Mode::EmergencyStop => OperatingMode::EmergencyStop
+        }
     }
+}
 
-    fn is_emergency_stop_activated(&self) -> bool {
-        self.is_emergency_stop_activated
+/// Faults the simulated car can suffer, the values are the fault codes
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulatedFault {
+    /// Position and velocity readings stop updating
+    SensorFreeze = 1,
+    /// The velocity reading has the wrong sign
+    VelocitySignFlip = 2,
+    /// The emergency stop reads as active, whatever its real state
+    StuckEmergencyStop = 3,
+    /// The door closed signal reads as open
+    DoorClosedDropped = 4
+}
+
+impl From<SimulatedFault> for Fault {
+    fn from(fault: SimulatedFault) -> Fault {
+        match fault {
+            SimulatedFault::SensorFreeze => Fault::SensorFreeze,
+            SimulatedFault::VelocitySignFlip => Fault::VelocitySignFlip,
+            SimulatedFault::StuckEmergencyStop => Fault::StuckEmergencyStop,
+            SimulatedFault::DoorClosedDropped => Fault::DoorClosedDropped
+        }
     }
 }
 
+const FAULTS: [SimulatedFault; 4] = [
+    SimulatedFault::SensorFreeze,
+    SimulatedFault::VelocitySignFlip,
+    SimulatedFault::StuckEmergencyStop,
+    SimulatedFault::DoorClosedDropped
+];
+
+/// State of a car after a step, everything needed to render a frame
 #[wasm_bindgen]
+#[derive(Serialize)]
 pub struct SimulationResult {
     pub position: Position,
-    pub is_stopped: bool
+    /// Position interpolated between the last two steps by the time `advance_to` has carried over,
+    /// for smooth motion at any display refresh rate
+    pub render_position: Position,
+    pub is_stopped: bool,
+    /// Floors per second, positive upwards
+    pub velocity: Velocity,
+    pub direction: TravelDirection,
+    /// The floor the controller is heading for, undefined when idle
+    pub next_target: Option<Floor>,
+    /// The floor closest to the car
+    pub nearest_floor: Floor,
+    /// The doors are open while passengers board and alight, the car cannot move
+    pub doors_open: bool,
+    pub door_state: DoorState,
+    /// How far the doors are open, from 0 when closed to 1 when fully open
+    pub door_open_fraction: f32,
+    pub is_faulted: bool,
+    /// Number of passengers in the car
+    pub riders: u32,
+    /// The emergency stop reads as active, either pressed or stuck by a fault
+    pub is_emergency_stop_activated: bool,
+    /// The emergency stop has been pressed and holds until released with `emergency_stop(false)`
+    pub is_emergency_stop_latched: bool,
+    pub mode: OperatingMode,
+    /// Floors travelled since the start or the last reset
+    pub distance: f64,
+    /// Number of times the car has started moving from a floor
+    pub motor_starts: u32,
+    /// Energy drawn from the grid less the energy fed back, in Wh
+    pub energy: f32,
+    /// The arrival lantern of the car, lit in the direction it leaves in from when the car commits to the stop
+    /// until it has left the floor
+    pub lantern: Option<CallDirection>,
+    /// Strikes of the latest arrival chime since the last result: 1 when the car leaves upwards, 2 downwards,
+    /// 0 without a chime
+    pub chime: u8
 }
 
-impl From<&Lift> for SimulationResult {
-    fn from(lift: &Lift) -> SimulationResult {
+impl From<&LiftSimulation> for SimulationResult {
+    fn from(simulation: &LiftSimulation) -> SimulationResult {
+        let lift = &simulation.lift;
+        let snapshot = simulation.controller.snapshot();
         SimulationResult {
-            position: lift.position,
-            is_stopped: lift.is_stopped
+            position: lift.position(),
+            render_position: simulation.render_position(simulation.accumulator / simulation.fixed_step),
+            is_stopped: lift.is_stopped(),
+            velocity: lift.velocity(),
+            direction: snapshot.direction.into(),
+            next_target: snapshot.target,
+            nearest_floor: lift.position().round() as Floor,
+            doors_open: lift.door_state() != lift::sim::DoorState::Closed,
+            door_state: lift.door_state().into(),
+            door_open_fraction: lift.door_open_fraction(),
+            is_faulted: FAULTS.iter().any(|fault| lift.is_fault_active((*fault).into())),
+            riders: lift.riders().len() as u32,
+            is_emergency_stop_activated: lift.is_emergency_stop_activated(),
+            is_emergency_stop_latched: lift.is_emergency_stop_latched(),
+            mode: if simulation.stopping_at_floor { OperatingMode::StoppingAtFloor } else { snapshot.mode.into() },
+            distance: simulation.controller.counters().distance,
+            motor_starts: simulation.controller.counters().motor_starts,
+            energy: simulation.metrics.energy().net(),
+            lantern: simulation.controller.hall_lantern().and_then(|lantern| match lantern.direction {
+                Direction::Up => Some(CallDirection::Up),
+                Direction::Down => Some(CallDirection::Down),
+                Direction::Neutral => None
+            }),
+            chime: simulation.chime
         }
     }
 }
 
-lazy_static! {
-    static ref LIFT: Mutex<Lift> = Mutex::new(Lift {
-        position: 0.0,
-        velocity: 0.0,
-        floors_to_stop_at: Vec::new(),
-        is_emergency_stop_activated: false,
-        is_stopped: false
-    });
+/// A stop on the planned route of a car
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct RouteStop {
+    pub floor: Floor,
+    /// Estimated seconds until the car reaches the stop, NaN if it cannot tell
+    pub eta: f32
+}
+
+/// Positions and stopped flags of a car after each step of `step_simulation_n`
+#[wasm_bindgen]
+pub struct SimulationBatch {
+    positions: Vec<f32>,
+    stopped: Vec<u8>
+}
+
+#[wasm_bindgen]
+impl SimulationBatch {
+    /// Position after each step, as a Float32Array
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    /// 1 if the car was stopped at a floor after the step and 0 otherwise, as a Uint8Array
+    pub fn stopped(&self) -> Vec<u8> {
+        self.stopped.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
 }
 
 const VELOCITY: Velocity = 1.0;
 
-lazy_static! {
-    static ref CONTROLLER: Mutex<LiftController> = Mutex::new(LiftController::new(VELOCITY, 0.01, 0.01));
+/// Default number of passengers that fit in a car
+const CAPACITY: u32 = 13;
+
+/// Default seconds per physics step of `advance_to`
+const FIXED_STEP: f32 = 0.01;
+
+/// Default milliseconds a button ignores presses after being pressed, as contacts bounce
+const DEBOUNCE_MS: f64 = 50.0;
+
+/// What became of a press with `press_button`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonPress {
+    /// A new stop was registered
+    Registered,
+    /// The stop was already registered, the press is ignored
+    AlreadyRegistered,
+    /// The button was pressed within the debounce time of the previous press
+    Bounced,
+    /// The building does not allow stopping at the floor, or the car is stopping for an emergency
+    Refused
+}
+
+/// Values per car written by `set_state_mirror`: position, velocity and door open fraction
+const MIRROR_STRIDE: u32 = 3;
+
+/// Highest time scale, at which a second takes ten thousand fixed steps by default
+const MAX_TIME_SCALE: f32 = 100.0;
+
+/// Most seconds `advance_to` catches up on in one call, so a page returning from the
+/// background does not stall while the simulation runs through the time it was away
+const MAX_CATCH_UP: f32 = 0.25;
+
+/// Longest time step in seconds `step_simulation` takes, before the time scale. Longer frames are
+/// split by the caller, so one call cannot take an unbounded number of fixed steps
+const MAX_TIME_STEP: f32 = 1.0;
+
+/// Most steps `step_simulation_n` takes in one call
+const MAX_BATCH_STEPS: usize = 100_000;
+
+/// Most floors a range of floors passed to the simulation may span, more than any building has
+const MAX_FLOOR_RANGE: usize = 1000;
+
+/// Range a positive `SimulationConfig::fixed_step` is clamped to, in seconds
+const MIN_FIXED_STEP: f32 = 0.0001;
+const MAX_FIXED_STEP: f32 = 0.1;
+
+/// Tuning of the controller and the car, passed when constructing a simulation.
+/// Created with the defaults, change the fields before passing it on
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Floors per second the controller travels at
+    pub prefered_velocity: Velocity,
+    /// Distance from a floor, in floors, within which the car counts as stopped at it
+    pub floor_leeway: Position,
+    /// Velocity below which the car counts as standing still
+    pub velocity_epsilon: Velocity,
+    /// Largest change in velocity in floors/s², unlimited when undefined
+    pub max_acceleration: Option<f32>,
+    /// Seconds to open and close the doors on every stop
+    pub door_time: f32,
+    /// Seconds per passenger boarding the car
+    pub per_boarding: f32,
+    /// Seconds per passenger alighting from the car
+    pub per_alighting: f32,
+    /// Seconds per physics step taken by `advance_to`, the default is used if it is not positive.
+    /// Kept between 0.0001 and 0.1
+    pub fixed_step: f32,
+    /// Number of passengers that fit in the car
+    pub capacity: u32
+}
+
+#[wasm_bindgen]
+impl SimulationConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SimulationConfig {
+        let dwell = DwellModel::TYPICAL;
+        SimulationConfig {
+            prefered_velocity: VELOCITY,
+            floor_leeway: 0.01,
+            velocity_epsilon: 0.01,
+            max_acceleration: None,
+            door_time: dwell.door_time,
+            per_boarding: dwell.per_boarding,
+            per_alighting: dwell.per_alighting,
+            fixed_step: FIXED_STEP,
+            capacity: CAPACITY
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// A car at floor 0 and its controller, as configured
+    fn build(&self) -> (SimulatedLift, LiftController) {
+        let mut lift = SimulatedLift::new(0.0);
+        lift.set_dwell_model(DwellModel {
+            door_time: self.door_time,
+            per_boarding: self.per_boarding,
+            per_alighting: self.per_alighting,
+            hall_call_distance: None
+        });
+        lift.set_capacity(self.capacity as usize);
+        let mut controller = LiftController::new(self.prefered_velocity, self.floor_leeway, self.velocity_epsilon);
+        controller.set_max_acceleration(self.max_acceleration);
+        (lift, controller)
+    }
+
+    /// The fixed step within its range, or the default if it is not positive
+    fn physics_step(&self) -> f32 {
+        if self.fixed_step > 0.0 {
+            self.fixed_step.clamp(MIN_FIXED_STEP, MAX_FIXED_STEP)
+        } else {
+            FIXED_STEP
+        }
+    }
+
+    /// Check the values make a working car and controller, such as in a restored state
+    fn validate(&self) -> Result<(), String> {
+        if !(self.prefered_velocity.is_finite() && self.prefered_velocity > 0.0) {
+            return Err(format!("the prefered velocity must be above 0, got {}", self.prefered_velocity));
+        }
+        if !(0.0..0.5).contains(&self.floor_leeway) {
+            return Err(format!("the floor leeway must be at least 0 and below 0.5, got {}", self.floor_leeway));
+        }
+        if !(self.velocity_epsilon.is_finite() && self.velocity_epsilon >= 0.0) {
+            return Err(format!("the velocity epsilon must be at least 0, got {}", self.velocity_epsilon));
+        }
+        if let Some(acceleration) = self.max_acceleration.filter(|acceleration| !(acceleration.is_finite() && *acceleration > 0.0)) {
+            return Err(format!("the max acceleration must be above 0, got {}", acceleration));
+        }
+        if ![self.door_time, self.per_boarding, self.per_alighting].iter().all(|time| time.is_finite() && *time >= 0.0) {
+            return Err("the door and boarding times must be at least 0 seconds".to_string());
+        }
+        if !self.fixed_step.is_finite() {
+            return Err(format!("the fixed step must be a number of seconds, got {}", self.fixed_step));
+        }
+        Ok(())
+    }
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Types for the values wasm-bindgen can only pass as `any`, added to the generated .d.ts
+#[wasm_bindgen(typescript_custom_section)]
+const TYPESCRIPT_TYPES: &'static str = r#"
+/** Count, mean, 95th percentile and maximum of a set of durations in seconds */
+export interface MetricsSummary {
+    count: number;
+    mean: number;
+    p95: number;
+    max: number;
+}
+
+/** Energy in Wh */
+export interface EnergyReport {
+    consumed: number;
+    regenerated: number;
+}
+
+/** Returned by `LiftSimulation.metrics` */
+export interface MetricsReport {
+    wait_time: MetricsSummary;
+    journey_time: MetricsSummary;
+    stops: number;
+    distance: number;
+    load_factor: number;
+    duration: number;
+    energy: EnergyReport;
+}
+
+/** A car as given to a dispatcher policy, see `BuildingSimulation.set_dispatcher` */
+export interface DispatchCandidate {
+    /** The fields of `SimulationResult`, with the enums given by name */
+    state: { [field: string]: number | string | boolean | null };
+    pending_stops: number[];
+    eta: number | null;
+}
+
+/** Returns the index of the car to assign a call at `floor` to */
+export type DispatcherPolicy = (cars: DispatchCandidate[], floor: number, direction: CallDirection | undefined) => number;
+
+/** Called with the arguments documented for each `LiftEvent` */
+export type LiftEventCallback = (...args: (number | boolean)[]) => void;
+"#;
+
+/// Events a callback can be registered for with `set_callback`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiftEvent {
+    /// Called with the floor
+    Arrived,
+    /// Called with the floor and the direction of travel
+    Departed,
+    /// Called with the previous and the new direction
+    DirectionChanged,
+    /// Called with the floor
+    DoorsOpened,
+    /// Called with the floor
+    DoorsClosed,
+    /// Called with true when activated and false when released
+    EmergencyStop,
+    /// Called with the floor when `press_button` registers a new stop, to light the button
+    ButtonAcknowledged
+}
+
+/// The callbacks registered for each event, invoked while stepping the simulation
+#[derive(Default)]
+struct EventCallbacks {
+    arrived: Option<Function>,
+    departed: Option<Function>,
+    direction_changed: Option<Function>,
+    doors_opened: Option<Function>,
+    doors_closed: Option<Function>,
+    emergency_stop: Option<Function>,
+    button_acknowledged: Option<Function>
+}
+
+impl EventCallbacks {
+    fn set(&mut self, event: LiftEvent, callback: Option<Function>) {
+        let slot = match event {
+            LiftEvent::Arrived => &mut self.arrived,
+            LiftEvent::Departed => &mut self.departed,
+            LiftEvent::DirectionChanged => &mut self.direction_changed,
+            LiftEvent::DoorsOpened => &mut self.doors_opened,
+            LiftEvent::DoorsClosed => &mut self.doors_closed,
+            LiftEvent::EmergencyStop => &mut self.emergency_stop,
+            LiftEvent::ButtonAcknowledged => &mut self.button_acknowledged
+        };
+        *slot = callback;
+    }
+}
+
+// An exception thrown by a callback is not allowed to stop the simulation, so the results are ignored.
+// The arguments are only converted when there is a callback, which also keeps the simulation usable outside wasm
+fn call1(callback: &Option<Function>, arg: impl Into<JsValue>) {
+    if let Some(callback) = callback {
+        let _ = callback.call1(&JsValue::NULL, &arg.into());
+    }
+}
+
+fn call2(callback: &Option<Function>, first: impl Into<JsValue>, second: impl Into<JsValue>) {
+    if let Some(callback) = callback {
+        let _ = callback.call2(&JsValue::NULL, &first.into(), &second.into());
+    }
+}
+
+impl LiftObserver for EventCallbacks {
+    fn on_departed(&mut self, floor: Floor, direction: Direction) {
+        call2(&self.departed, floor, TravelDirection::from(direction));
+    }
+
+    fn on_arrived(&mut self, floor: Floor) {
+        call1(&self.arrived, floor);
+    }
+
+    fn on_direction_changed(&mut self, from: Direction, to: Direction) {
+        call2(&self.direction_changed, TravelDirection::from(from), TravelDirection::from(to));
+    }
+
+    fn on_emergency_stop(&mut self, activated: bool) {
+        call1(&self.emergency_stop, activated);
+    }
+}
+
+/// Traffic templates for `start_traffic`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Traffic {
+    /// Morning arrivals travelling from the lobby
+    UpPeak,
+    /// Evening departures travelling to the lobby
+    DownPeak,
+    /// Trips to and from the lobby with some interfloor trips
+    Lunch,
+    /// Trips between any two floors
+    Interfloor
+}
+
+impl From<Traffic> for TrafficPattern {
+    fn from(traffic: Traffic) -> TrafficPattern {
+        match traffic {
+            Traffic::UpPeak => TrafficPattern::UpPeak,
+            Traffic::DownPeak => TrafficPattern::DownPeak,
+            Traffic::Lunch => TrafficPattern::Lunch,
+            Traffic::Interfloor => TrafficPattern::Uniform
+        }
+    }
+}
+
+/// People on each floor when generating traffic, only the relative sizes matter
+const FLOOR_POPULATION: u32 = 20;
+
+/// Floor height used when the building does not give one, in meters
+const FLOOR_HEIGHT: f32 = 3.0;
+
+/// The floors of a building, used to validate calls and to render the shaft.
+///
+/// Calls to express floors, which the cars pass without stopping, are always rejected.
+/// Restricted floors can only be selected from inside a car, calls from their landings are rejected
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Building {
+    lowest_floor: Floor,
+    floor_count: u32,
+    /// Elevation of each landing in meters, from the lowest floor up
+    elevations: Vec<f32>,
+    restricted_floors: Vec<Floor>,
+    express_floors: Vec<Floor>
 }
 
 #[wasm_bindgen]
-pub fn emergency_stop(status: bool) {
-    let mut lift = LIFT.lock().unwrap();
-    lift.is_emergency_stop_activated = status
+impl Building {
+    /// A building of 'floor_count' floors from 'lowest_floor' and up, all 3 meters tall.
+    /// Fails for fewer than two or more than 1000 floors, or floors beyond the highest floor number
+    #[wasm_bindgen(constructor)]
+    pub fn new(lowest_floor: Floor, floor_count: u32) -> Result<Building, JsError> {
+        Building::check_floors(lowest_floor, floor_count).map_err(|e| JsError::new(&e))?;
+        Ok(Building {
+            lowest_floor,
+            floor_count,
+            elevations: (0..floor_count).map(|i| i as f32 * FLOOR_HEIGHT).collect(),
+            restricted_floors: Vec::new(),
+            express_floors: Vec::new()
+        })
+    }
+
+    pub fn lowest_floor(&self) -> Floor {
+        self.lowest_floor
+    }
+
+    pub fn highest_floor(&self) -> Floor {
+        self.lowest_floor + self.floor_count as Floor - 1
+    }
+
+    pub fn floor_count(&self) -> u32 {
+        self.floor_count
+    }
+
+    /// Set the height of each floor in meters, from the lowest floor up to the floor below the top
+    pub fn set_floor_heights(&mut self, heights: Vec<f32>) -> Result<(), JsError> {
+        if heights.len() + 1 != self.floor_count as usize {
+            return Err(JsError::new(&format!("expected {} floor heights, got {}", self.floor_count - 1, heights.len())));
+        }
+        let elevations: Vec<f32> = core::iter::once(0.0)
+            .chain(heights.iter().scan(0.0, |elevation, height| {
+                *elevation += height;
+                Some(*elevation)
+            }))
+            .collect();
+        FloorMap::with_elevations(self.lowest_floor, &elevations)
+            .map_err(|e| JsError::new(&format!("invalid floor heights: {:?}", e)))?;
+        self.elevations = elevations;
+        Ok(())
+    }
+
+    /// Height of each floor in meters, from the lowest floor up to the floor below the top
+    pub fn floor_heights(&self) -> Vec<f32> {
+        self.elevations.windows(2).map(|pair| pair[1] - pair[0]).collect()
+    }
+
+    /// Elevation of the landing at 'floor' in meters above the lowest floor
+    pub fn elevation(&self, floor: Floor) -> f32 {
+        self.floor_map().elevation(floor)
+    }
+
+    /// Meters above the lowest floor of a position as reported by the simulation
+    pub fn to_meters(&self, position: Position) -> f32 {
+        self.floor_map().to_meters(position)
+    }
+
+    pub fn set_restricted_floors(&mut self, floors: Vec<Floor>) {
+        self.restricted_floors = floors;
+    }
+
+    pub fn restricted_floors(&self) -> Vec<Floor> {
+        self.restricted_floors.clone()
+    }
+
+    pub fn set_express_floors(&mut self, floors: Vec<Floor>) {
+        self.express_floors = floors;
+    }
+
+    pub fn express_floors(&self) -> Vec<Floor> {
+        self.express_floors.clone()
+    }
+
+    /// If a floor button inside a car may select 'floor'
+    pub fn accepts_car_call(&self, floor: Floor) -> bool {
+        (self.lowest_floor..=self.highest_floor()).contains(&floor) && !self.express_floors.contains(&floor)
+    }
+
+    /// If a car may be called from the landing at 'floor'
+    pub fn accepts_landing_call(&self, floor: Floor) -> bool {
+        self.accepts_car_call(floor) && !self.restricted_floors.contains(&floor)
+    }
+}
+
+impl Building {
+    /// Traffic over the floors where passengers may travel between, with floor 0 as the lobby if
+    /// the building has one and the lowest floor otherwise
+    fn traffic(&self, pattern: TrafficPattern, arrival_rate: f32) -> TrafficConfig {
+        let floors = self.lowest_floor..=self.highest_floor();
+        let lobby = if floors.contains(&0) { 0 } else { self.lowest_floor };
+        TrafficConfig {
+            pattern,
+            arrival_rate,
+            lowest_floor: self.lowest_floor,
+            lobby,
+            populations: floors
+                .map(|floor| if floor != lobby && self.accepts_landing_call(floor) { FLOOR_POPULATION } else { 0 })
+                .collect()
+        }
+    }
+
+    fn check_car_call(&self, floor: Floor) -> Result<(), JsError> {
+        if !(self.lowest_floor..=self.highest_floor()).contains(&floor) {
+            return Err(JsError::new(&format!(
+                "floor {} is not in the building, which has floors {} to {}",
+                floor, self.lowest_floor, self.highest_floor()
+            )));
+        }
+        if self.express_floors.contains(&floor) {
+            return Err(JsError::new(&format!("floor {} is an express floor, cars do not stop there", floor)));
+        }
+        Ok(())
+    }
+
+    fn check_landing_call(&self, floor: Floor) -> Result<(), JsError> {
+        self.check_car_call(floor)?;
+        if self.restricted_floors.contains(&floor) {
+            return Err(JsError::new(&format!("floor {} is restricted, cars can only be sent there from inside", floor)));
+        }
+        Ok(())
+    }
+
+    fn floor_map(&self) -> FloorMap<'_> {
+        FloorMap::with_elevations(self.lowest_floor, &self.elevations).expect("elevations are validated when set")
+    }
+
+    /// The floors a building may have, checked by `new` and `validate`
+    fn check_floors(lowest_floor: Floor, floor_count: u32) -> Result<(), String> {
+        if floor_count < 2 {
+            return Err("a building needs at least two floors".to_string());
+        }
+        if floor_count as usize > MAX_FLOOR_RANGE {
+            return Err(format!("a building has at most {} floors, got {}", MAX_FLOOR_RANGE, floor_count));
+        }
+        if i64::from(lowest_floor) + i64::from(floor_count) - 1 > i64::from(Floor::MAX) {
+            return Err(format!("{} floors from floor {} do not fit", floor_count, lowest_floor));
+        }
+        Ok(())
+    }
+
+    /// Run the checks of `new` and `set_floor_heights` again, for a building that was deserialized
+    fn validate(&self) -> Result<(), String> {
+        Building::check_floors(self.lowest_floor, self.floor_count)?;
+        if self.elevations.len() != self.floor_count as usize {
+            return Err(format!("expected {} elevations, got {}", self.floor_count, self.elevations.len()));
+        }
+        if !self.elevations.iter().all(|elevation| elevation.is_finite()) {
+            return Err("the elevations must be numbers of meters".to_string());
+        }
+        FloorMap::with_elevations(self.lowest_floor, &self.elevations)
+            .map_err(|e| format!("invalid floor heights: {:?}", e))?;
+        Ok(())
+    }
+}
+
+/// Everything `LiftSimulation::save_state` stores, the callbacks and the clock are left out
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    lift: SimulatedLift,
+    controller: LiftController,
+    config: SimulationConfig,
+    building: Option<Building>,
+    metrics: Metrics,
+    previous_position: Position,
+    paused: bool
 }
 
+/// A simulated lift together with its controller.
+/// Every instance is independent, such that several lifts can be simulated on one page
 #[wasm_bindgen]
-pub fn stop_lift_at_floor(floor: Floor) {
-    let mut lift = LIFT.lock().unwrap();
-    lift.floors_to_stop_at.push(floor);
+pub struct LiftSimulation {
+    lift: SimulatedLift,
+    controller: LiftController,
+    /// Calls are not validated without a building
+    building: Option<Building>,
+    callbacks: EventCallbacks,
+    fixed_step: f32,
+    /// Time in milliseconds of the last call to `advance_to`
+    clock: Option<f64>,
+    /// Seconds not yet simulated because they make up less than a fixed step
+    accumulator: f32,
+    /// Position before the last step
+    previous_position: Position,
+    /// Kept to restore the initial state on `reset`
+    config: SimulationConfig,
+    paused: bool,
+    time_scale: f32,
+    traffic: Option<TrafficGenerator>,
+    /// The scenario loaded with `load_scenario`, kept to start it over on `reset`
+    scenario: Option<Scenario>,
+    /// Events of the scenario left to play
+    script: Option<ScriptPlayer>,
+    metrics: Metrics,
+    /// Bringing the car to a floor before activating the emergency stop
+    stopping_at_floor: bool,
+    /// Array and offset the state is written to after every step
+    mirror: Option<(Float32Array, u32)>,
+    debounce_ms: f64,
+    /// Floor and time of the recent presses with `press_button`
+    presses: Vec<(Floor, f64)>,
+    /// Strikes of the latest chime since the start of the last call to step the simulation
+    chime: u8
+}
+
+#[wasm_bindgen]
+impl LiftSimulation {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> LiftSimulation {
+        Self::with_config(&SimulationConfig::new())
+    }
+
+    pub fn with_config(config: &SimulationConfig) -> LiftSimulation {
+        utils::set_panic_hook();
+        let (lift, controller) = config.build();
+        LiftSimulation {
+            lift,
+            controller,
+            building: None,
+            callbacks: EventCallbacks::default(),
+            fixed_step: config.physics_step(),
+            clock: None,
+            accumulator: 0.0,
+            previous_position: 0.0,
+            config: *config,
+            paused: false,
+            time_scale: 1.0,
+            traffic: None,
+            scenario: None,
+            script: None,
+            metrics: Metrics::new(),
+            stopping_at_floor: false,
+            mirror: None,
+            debounce_ms: DEBOUNCE_MS,
+            presses: Vec::new(),
+            chime: 0
+        }
+    }
+
+    /// Stop time, steps are ignored until the simulation is resumed
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Put the car back at floor 0 with no calls and the controller in its initial state, or start a loaded
+    /// scenario over. The building, the callbacks, the time scale and whether the simulation is paused are kept,
+    /// traffic is stopped
+    pub fn reset(&mut self) {
+        let mut initial = Self::with_config(&self.config);
+        initial.building = self.building.take();
+        initial.callbacks = core::mem::take(&mut self.callbacks);
+        initial.paused = self.paused;
+        initial.time_scale = self.time_scale;
+        initial.debounce_ms = self.debounce_ms;
+        initial.mirror = self.mirror.take();
+        if let Some(scenario) = self.scenario.take() {
+            initial.play(scenario);
+        }
+        *self = initial;
+        self.write_mirror();
+    }
+
+    pub fn emergency_stop(&mut self, status: bool) {
+        self.stopping_at_floor = false;
+        self.lift.set_emergency_stop(status)
+    }
+
+    /// A controlled emergency stop: drop every registered stop, continue to the next floor in the direction of travel,
+    /// open the doors there and then activate the emergency stop. Release it with `emergency_stop(false)`
+    pub fn emergency_stop_at_floor(&mut self) {
+        if !self.lift.is_emergency_stop_activated() {
+            self.stopping_at_floor = true;
+            self.clear_calls();
+        }
+    }
+
+    /// Make 'fault' active for the next 'duration' seconds of simulated time, or until `clear_faults` when infinite
+    pub fn inject_fault(&mut self, fault: SimulatedFault, duration: f32) -> Result<(), JsError> {
+        if duration.is_nan() || duration <= 0.0 {
+            return Err(JsError::new("the duration must be above 0 seconds"));
+        }
+        self.lift.schedule_fault(self.lift.time(), duration, fault.into());
+        Ok(())
+    }
+
+    pub fn clear_faults(&mut self) {
+        self.lift.clear_faults();
+    }
+
+    /// The codes of the faults active now, as a Uint32Array of `SimulatedFault` values
+    pub fn active_faults(&self) -> Vec<u32> {
+        FAULTS
+            .iter()
+            .filter(|fault| self.lift.is_fault_active((**fault).into()))
+            .map(|fault| *fault as u32)
+            .collect()
+    }
+
+    /// If the car is on its way to a floor to stop there after `emergency_stop_at_floor`
+    pub fn is_stopping_at_floor(&self) -> bool {
+        self.stopping_at_floor
+    }
+
+    /// Press a floor button inside the car, fails if the building does not allow stopping at 'floor'
+    pub fn stop_lift_at_floor(&mut self, floor: Floor) -> Result<(), JsError> {
+        self.check_car_call(floor)?;
+        if self.stopping_at_floor {
+            return Err(JsError::new("the car is stopping for an emergency"));
+        }
+        self.lift.register_call(floor);
+        Ok(())
+    }
+
+    /// Press a floor button inside the car at 'time_ms', such as `performance.now()`, behaving like a physical button.
+    /// Presses within the debounce time of the last press of the same button and presses of a lit button are ignored.
+    /// The `ButtonAcknowledged` callback is called when a new stop is registered
+    pub fn press_button(&mut self, floor: Floor, time_ms: f64) -> ButtonPress {
+        let debounce_ms = self.debounce_ms;
+        self.presses.retain(|(_, pressed)| time_ms - pressed < debounce_ms);
+        if self.presses.iter().any(|(pressed, _)| *pressed == floor) {
+            return ButtonPress::Bounced;
+        }
+        self.presses.push((floor, time_ms));
+
+        if self.lift.calls().contains(&floor) {
+            return ButtonPress::AlreadyRegistered;
+        }
+        if self.stop_lift_at_floor(floor).is_err() {
+            return ButtonPress::Refused;
+        }
+        call1(&self.callbacks.button_acknowledged, floor);
+        ButtonPress::Registered
+    }
+
+    /// Milliseconds a button ignores presses after being pressed, 50 by default
+    pub fn set_debounce(&mut self, ms: f64) -> Result<(), JsError> {
+        if !ms.is_finite() || ms < 0.0 {
+            return Err(JsError::new("the debounce time must be a number of milliseconds of at least 0"));
+        }
+        self.debounce_ms = ms;
+        Ok(())
+    }
+
+    /// 'count' passengers arrive at 'origin' wanting to go to 'destination', and call the car.
+    /// They board when the car stops with room to spare. Fails, spawning nobody, if the
+    /// floors are the same or the building does not allow the trip
+    pub fn spawn_passengers(&mut self, origin: Floor, destination: Floor, count: u32) -> Result<(), JsError> {
+        if origin == destination {
+            return Err(JsError::new(&format!("the passengers are already at floor {}", destination)));
+        }
+        if let Some(building) = &self.building {
+            building.check_landing_call(origin)?;
+            building.check_car_call(destination)?;
+        }
+        for _ in 0..count {
+            self.lift.spawn_passenger(origin, destination);
+        }
+        Ok(())
+    }
+
+    /// Spawn passengers continuously following 'pattern', 'rate' passengers per minute on average.
+    /// The same seed gives the same passengers. Needs a building to know the floors
+    pub fn start_traffic(&mut self, pattern: Traffic, rate: f32, seed: u32) -> Result<(), JsError> {
+        let building = self.building.as_ref().ok_or_else(|| JsError::new("traffic needs a building, see set_building"))?;
+        if !rate.is_finite() || rate < 0.0 {
+            return Err(JsError::new("the rate must be a number of at least 0"));
+        }
+        let config = building.traffic(pattern.into(), rate);
+        self.traffic = Some(TrafficGenerator::new(config, seed.into()));
+        Ok(())
+    }
+
+    /// Replace the car, controller and building with those of a scenario file, as read by lift_sim, and play its
+    /// scripted calls, passengers, emergency stops and faults as the simulation steps. The expectations are not checked.
+    /// The configuration takes the tuning of the scenario, and `reset` starts the scenario over
+    pub fn load_scenario(&mut self, json: &str) -> Result<(), JsError> {
+        let scenario: Scenario = serde_json::from_str(json)?;
+        scenario.validate().map_err(|e| JsError::new(&format!("invalid scenario: {:?}", e)))?;
+        let floors = &scenario.building;
+        if floors.highest_floor < floors.lowest_floor {
+            return Err(JsError::new("the highest floor of the scenario is below the lowest"));
+        }
+        let count = (i64::from(floors.highest_floor) - i64::from(floors.lowest_floor) + 1) as u32;
+        let building = Building::new(floors.lowest_floor, count)?;
+        let lift = scenario.initial_lift();
+        let dwell = lift.dwell_model();
+        let config = SimulationConfig {
+            prefered_velocity: scenario.controller.prefered_velocity,
+            floor_leeway: scenario.controller.floor_leeway,
+            velocity_epsilon: scenario.controller.velocity_epsilon,
+            max_acceleration: None,
+            door_time: dwell.door_time,
+            per_boarding: dwell.per_boarding,
+            per_alighting: dwell.per_alighting,
+            fixed_step: self.config.fixed_step,
+            capacity: lift.capacity() as u32
+        };
+        config.validate().map_err(|e| JsError::new(&format!("invalid scenario: {}", e)))?;
+
+        self.config = config;
+        self.scenario = Some(scenario);
+        self.building = Some(building);
+        self.reset();
+        Ok(())
+    }
+
+    /// If a scenario is loaded and has events left to play
+    pub fn is_scenario_running(&self) -> bool {
+        self.script.as_ref().is_some_and(|script| !script.is_finished())
+    }
+
+    pub fn stop_traffic(&mut self) {
+        self.traffic = None;
+    }
+
+    pub fn is_traffic_running(&self) -> bool {
+        self.traffic.is_some()
+    }
+
+    /// Statistics since the start or the last reset: wait and journey times, stops, floors travelled, load factor,
+    /// simulated duration and energy in Wh, as an object
+    #[wasm_bindgen(unchecked_return_type = "MetricsReport")]
+    pub fn metrics(&self) -> Result<JsValue, JsError> {
+        let json = serde_json::to_string(&self.metrics.report())?;
+        js_sys::JSON::parse(&json).map_err(|_| JsError::new("the metrics could not be converted"))
+    }
+
+    /// Number of passengers waiting at the landing of 'floor'
+    pub fn waiting_at(&self, floor: Floor) -> u32 {
+        self.lift.waiting_at(floor).count() as u32
+    }
+
+    /// Number of passengers waiting at each floor from 'lowest_floor' to 'highest_floor', as a Uint32Array.
+    /// Fails if the range spans more than 1000 floors
+    pub fn waiting_per_floor(&self, lowest_floor: Floor, highest_floor: Floor) -> Result<Vec<u32>, JsError> {
+        let mut waiting = vec![0; floor_count(lowest_floor, highest_floor)?];
+        self.add_waiting(lowest_floor, &mut waiting);
+        Ok(waiting)
+    }
+
+    /// Number of passengers in the car
+    pub fn riders(&self) -> u32 {
+        self.lift.riders().len() as u32
+    }
+
+    /// Number of passengers that fit in the car
+    pub fn capacity(&self) -> u32 {
+        self.config.capacity
+    }
+
+    /// Number of passengers who have reached their destination
+    pub fn delivered(&self) -> u32 {
+        self.lift.completed_trips().len() as u32
+    }
+
+    /// Remove a registered stop before it is served, returns false if there was none.
+    /// A car travelling to the floor stops at the next floor on its way instead
+    pub fn cancel_floor(&mut self, floor: Floor) -> bool {
+        self.lift.cancel_call(floor)
+    }
+
+    /// The floors the car has registered stops at, not sorted
+    pub fn pending_stops(&self) -> Vec<Floor> {
+        self.lift.calls().to_vec()
+    }
+
+    /// Validate calls against 'building' from now on. Registered stops the building does not allow, such as
+    /// floors outside it, are dropped, returning how many
+    pub fn set_building(&mut self, building: &Building) -> u32 {
+        let refused: Vec<Floor> =
+            self.lift.calls().iter().copied().filter(|floor| !building.accepts_car_call(*floor)).collect();
+        for floor in &refused {
+            self.lift.cancel_call(*floor);
+        }
+        self.building = Some(building.clone());
+        refused.len() as u32
+    }
+
+    pub fn building(&self) -> Option<Building> {
+        self.building.clone()
+    }
+
+    /// Step the simulation by the time as specified in 'time_step', fails unless it is a number of seconds from 0 to 1
+    pub fn step_simulation(&mut self, time_step: f32) -> Result<SimulationResult, JsError> {
+        check_time_step(time_step)?;
+        self.chime = 0;
+        if !self.paused {
+            // Long steps, also from a high time scale, are split to stay within the fixed step
+            let duration = time_step * self.time_scale;
+            let substeps = (duration / self.fixed_step).ceil().max(1.0) as usize;
+            for _ in 0..substeps {
+                self.step(duration / substeps as f32);
+            }
+        }
+        Ok((&*self).into())
+    }
+
+    /// Run the simulation 'scale' times faster than the time passed to it, between 0 and 100.
+    /// The physics still advances in steps no longer than the fixed step
+    pub fn set_time_scale(&mut self, scale: f32) -> Result<(), JsError> {
+        if !(scale > 0.0 && scale <= MAX_TIME_SCALE) {
+            return Err(JsError::new(&format!("the time scale must be above 0 and at most {}", MAX_TIME_SCALE)));
+        }
+        self.time_scale = scale;
+        Ok(())
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// The state of the car and controller as a JSON string, for instance to keep in localStorage
+    pub fn save_state(&self) -> Result<String, JsError> {
+        let state = SavedState {
+            lift: self.lift.clone(),
+            controller: self.controller.clone(),
+            config: self.config,
+            building: self.building.clone(),
+            metrics: self.metrics.clone(),
+            previous_position: self.previous_position,
+            paused: self.paused
+        };
+        Ok(serde_json::to_string(&state)?)
+    }
+
+    /// Restore a state returned by `save_state`. The callbacks are kept, and the clock of `advance_to` starts over
+    pub fn load_state(&mut self, #[wasm_bindgen(unchecked_param_type = "string")] state: JsValue) -> Result<(), JsError> {
+        let json = state.as_string().ok_or_else(|| JsError::new("the state must be a string"))?;
+        let state: SavedState = serde_json::from_str(&json)?;
+        self.restore(state).map_err(|e| JsError::new(&format!("invalid state: {}", e)))
+    }
+    /// Advance the simulation to 'time_ms', such as the timestamp of an animation frame, in steps of the fixed step.
+    /// Time left over is carried to the next call, so the motion does not depend on the frame rate.
+    /// The first call only starts the clock
+    pub fn advance_to(&mut self, time_ms: f64) -> Result<SimulationResult, JsError> {
+        if !time_ms.is_finite() {
+            return Err(JsError::new("the time must be a number of milliseconds"));
+        }
+        self.chime = 0;
+        if let Some(clock) = self.clock.filter(|_| !self.paused) {
+            let elapsed = (((time_ms - clock) / 1000.0) as f32).clamp(0.0, MAX_CATCH_UP);
+            self.accumulator += elapsed * self.time_scale;
+            while self.accumulator >= self.fixed_step {
+                self.step(self.fixed_step);
+                self.accumulator -= self.fixed_step;
+            }
+        }
+        self.clock = Some(time_ms);
+        Ok((&*self).into())
+    }
+
+    /// Position a fraction 'alpha' of the way from the position before the last step to the current one
+    pub fn render_position(&self, alpha: f32) -> Position {
+        let alpha = alpha.clamp(0.0, 1.0);
+        self.previous_position + (self.lift.position() - self.previous_position) * alpha
+    }
+
+    /// Step the simulation 'steps' times by 'time_step', returning the state after each step in one call
+    pub fn step_simulation_n(&mut self, steps: usize, time_step: f32) -> Result<SimulationBatch, JsError> {
+        if steps > MAX_BATCH_STEPS {
+            return Err(JsError::new(&format!("at most {} steps can be taken in one call, got {}", MAX_BATCH_STEPS, steps)));
+        }
+        check_time_step(time_step)?;
+        let mut batch = SimulationBatch {
+            positions: Vec::with_capacity(steps),
+            stopped: Vec::with_capacity(steps)
+        };
+        for _ in 0..steps {
+            let result = self.step_simulation(time_step)?;
+            batch.positions.push(result.position);
+            batch.stopped.push(result.is_stopped as u8);
+        }
+        Ok(batch)
+    }
+
+    pub fn last_simulation_result(&self) -> SimulationResult {
+        self.into()
+    }
+
+    /// Write the position, velocity and door open fraction of the car to 'buffer' from 'offset' after every step.
+    /// With a Float32Array over a SharedArrayBuffer a render thread can read the state without messages.
+    /// Stop writing when undefined
+    pub fn set_state_mirror(&mut self, buffer: Option<Float32Array>, offset: u32) -> Result<(), JsError> {
+        if let Some(buffer) = &buffer {
+            if offset.saturating_add(MIRROR_STRIDE) > buffer.length() {
+                return Err(JsError::new(&format!(
+                    "the mirror needs {} values from offset {}, the array has {}",
+                    MIRROR_STRIDE, offset, buffer.length()
+                )));
+            }
+        }
+        self.mirror = buffer.map(|buffer| (buffer, offset));
+        self.write_mirror();
+        Ok(())
+    }
+
+    /// Call 'callback' whenever 'event' happens during a step, or stop calling it when undefined
+    pub fn set_callback(
+        &mut self,
+        event: LiftEvent,
+        #[wasm_bindgen(unchecked_param_type = "LiftEventCallback | undefined")] callback: Option<Function>
+    ) {
+        self.callbacks.set(event, callback);
+    }
+
+    pub fn time_to_floor(&self, floor: Floor, average_stop: f32) -> Option<f32> {
+        self.controller.time_to_floor(&self.lift, floor, average_stop)
+    }
+
+    /// The registered stops in the order the car will serve them, with the estimated seconds until it reaches each.
+    /// The car first serves the stops ahead of it, then turns around for the rest.
+    /// The estimates follow the route at the current speed, stopping 'average_stop' seconds at every stop before,
+    /// and are NaN while the car stands still
+    pub fn planned_route(&self, average_stop: f32) -> Vec<RouteStop> {
+        let snapshot = self.controller.snapshot();
+        let speed = self.lift.current_velocity().abs();
+        let moving = speed >= snapshot.velocity_epsilon && snapshot.direction != Direction::Neutral;
+        let mut position = self.lift.current_floor();
+        let mut eta = 0.0;
+        self.controller
+            .planned_route(self.lift.current_floor(), self.lift.calls())
+            .enumerate()
+            .map(|(stops, floor)| {
+                if stops > 0 {
+                    eta += average_stop;
+                }
+                eta += (floor as f32 - position).abs() / speed;
+                position = floor as f32;
+                RouteStop { floor, eta: if moving { eta } else { f32::NAN } }
+            })
+            .collect()
+    }
+
+    /// Estimated seconds until the car reaches each floor from 'lowest_floor' to 'highest_floor',
+    /// as a Float32Array. Floors the car is not on its way to are NaN. Fails if the range spans more than 1000 floors
+    pub fn time_to_floors(&self, lowest_floor: Floor, highest_floor: Floor, average_stop: f32) -> Result<Vec<f32>, JsError> {
+        let mut etas = vec![None; floor_count(lowest_floor, highest_floor)?];
+        self.controller.times_to_floors(&self.lift, lowest_floor, average_stop, &mut etas);
+        Ok(etas.into_iter().map(|eta| eta.unwrap_or(f32::NAN)).collect())
+    }
+}
+
+impl LiftSimulation {
+    fn check_car_call(&self, floor: Floor) -> Result<(), JsError> {
+        self.building.as_ref().map_or(Ok(()), |building| building.check_car_call(floor))
+    }
+
+    /// Count the passengers waiting at each floor from 'lowest_floor' into 'waiting'
+    fn add_waiting(&self, lowest_floor: Floor, waiting: &mut [u32]) {
+        for passenger in self.lift.waiting() {
+            let index = i64::from(passenger.origin) - i64::from(lowest_floor);
+            if index >= 0 && (index as usize) < waiting.len() {
+                waiting[index as usize] += 1;
+            }
+        }
+    }
+
+    fn clear_calls(&mut self) {
+        while let Some(&floor) = self.lift.calls().first() {
+            self.lift.cancel_call(floor);
+        }
+    }
+
+    /// Start 'scenario' from its initial car and controller
+    fn play(&mut self, scenario: Scenario) {
+        self.lift = scenario.initial_lift();
+        self.controller = scenario.controller.build();
+        self.previous_position = self.lift.position();
+        self.script = Some(ScriptPlayer::new(&scenario.events));
+        self.scenario = Some(scenario);
+    }
+
+    /// Replace the state with a saved one after checking it the way the setters would
+    fn restore(&mut self, state: SavedState) -> Result<(), String> {
+        state.config.validate()?;
+        if let Some(building) = &state.building {
+            building.validate()?;
+        }
+        self.lift = state.lift;
+        self.controller = state.controller;
+        self.config = state.config;
+        self.fixed_step = state.config.physics_step();
+        self.building = state.building;
+        self.metrics = state.metrics;
+        self.previous_position = state.previous_position;
+        self.paused = state.paused;
+        // The saved state does not include a scenario
+        self.scenario = None;
+        self.script = None;
+        self.clock = None;
+        self.accumulator = 0.0;
+        self.chime = 0;
+        self.write_mirror();
+        Ok(())
+    }
+
+    /// Advance the car and controller by a single step
+    fn step(&mut self, time_step: f32) {
+        if let Some(traffic) = &mut self.traffic {
+            traffic.feed(&mut self.lift, time_step);
+        }
+        if let Some(script) = &mut self.script {
+            script.feed(&mut self.lift);
+        }
+        if self.stopping_at_floor {
+            self.clear_calls();
+        }
+        let floor = self.lift.position().round() as Floor;
+        let doors_were_open = self.lift.dwell_remaining() > 0.0;
+        self.previous_position = self.lift.position();
+        let action = step_car(&mut self.lift, &mut self.controller, &mut self.metrics, &mut self.callbacks, time_step);
+        if let Some(chime) = self.controller.chime() {
+            self.chime = chime.strikes();
+        }
+        // Let the passengers out before holding the car
+        if self.stopping_at_floor && action.is_stopped_at_current_floor {
+            self.stopping_at_floor = false;
+            self.lift.start_dwell(0, 0);
+            self.lift.set_emergency_stop(true);
+        }
+
+        match (doors_were_open, self.lift.dwell_remaining() > 0.0) {
+            (false, true) => call1(&self.callbacks.doors_opened, floor),
+            (true, false) => call1(&self.callbacks.doors_closed, floor),
+            _ => {}
+        }
+        self.write_mirror();
+    }
+
+    fn write_mirror(&self) {
+        if let Some((buffer, offset)) = &self.mirror {
+            buffer.set_index(*offset, self.lift.position());
+            buffer.set_index(offset + 1, self.lift.velocity());
+            buffer.set_index(offset + 2, self.lift.door_open_fraction());
+        }
+    }
+}
+
+impl Default for LiftSimulation {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-/// Step the simulation by the time as specified in 'time_step'
-/// Returns true if the lift is still moving and false if its stopped
+/// Direction a passenger wants to travel, as pressed on a landing panel
 #[wasm_bindgen]
-pub fn step_simulation(time_step: f32) -> SimulationResult {
-    let mut lift = LIFT.lock().unwrap();
-    let mut controller = CONTROLLER.lock().unwrap();
-    let action = controller.poll(&*lift, time_step);
-    if action.is_stopped_at_current_floor {
-        lift.remove_current_floor();
-        lift.is_stopped = true;
-        return (&*lift).into();
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallDirection {
+    Up,
+    Down
+}
+
+impl From<CallDirection> for Direction {
+    fn from(direction: CallDirection) -> Direction {
+        match direction {
+            CallDirection::Up => Direction::Up,
+            CallDirection::Down => Direction::Down
+        }
     }
-    lift.position += action.target_velocity * time_step;
-    lift.velocity = action.target_velocity;
+}
+
+/// The car assigned to a call from a landing
+#[wasm_bindgen]
+pub struct HallCallAssignment {
+    pub car: usize,
+    /// Estimated seconds until the car arrives
+    pub eta: f32
+}
+
+/// What the hall lantern and display at a landing show
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct HallDisplay {
+    pub floor: Floor,
+    /// The car assigned to the call from this landing, undefined if there is none
+    pub car: Option<usize>,
+    /// The direction the car leaves in once it arrives
+    pub direction: Option<CallDirection>,
+    /// Estimated seconds until the car arrives, NaN if there is no car assigned
+    pub eta: f32
+}
 
-    lift.is_stopped = false;
-    (&*lift).into()
+/// A call from a landing and the car serving it
+#[derive(Clone, Copy, Debug)]
+struct HallCall {
+    floor: Floor,
+    direction: CallDirection,
+    car: usize
 }
 
+/// A car as a JavaScript dispatcher policy sees it
+#[derive(Serialize)]
+struct DispatchCandidate {
+    state: SimulationResult,
+    pending_stops: Vec<Floor>,
+    eta: Option<f32>
+}
+
+/// Seconds a car is expected to spend at each stop, used when dispatching calls
+const AVERAGE_STOP: f32 = 5.0;
+
+/// Several cars sharing the landings of a building.
+/// Calls from the landings are given to the car the group dispatcher expects to arrive first
 #[wasm_bindgen]
-pub fn last_simulation_result() -> SimulationResult {
-    let lift = LIFT.lock().unwrap();
-    (&*lift).into()
+pub struct BuildingSimulation {
+    cars: Vec<LiftSimulation>,
+    dispatcher: GroupDispatcher,
+    building: Option<Building>,
+    hall_calls: Vec<HallCall>,
+    /// Assigns calls from the landings instead of the group dispatcher, see `set_dispatcher`
+    policy: Option<Function>
 }
 
 #[wasm_bindgen]
-pub fn time_to_floor(floor: Floor, average_stop: f32) -> Option<f32> {
-    let lift = LIFT.lock().unwrap();
-    let controller = CONTROLLER.lock().unwrap();
-    controller.time_to_floor(&*lift, floor, average_stop)
-}
\ No newline at end of file
+impl BuildingSimulation {
+    /// Create a building with 'cars' cars, all starting at floor 0
+    #[wasm_bindgen(constructor)]
+    pub fn new(cars: usize) -> BuildingSimulation {
+        Self::with_config(cars, &SimulationConfig::new())
+    }
+
+    /// Create a building with 'cars' cars, all tuned by 'config'
+    pub fn with_config(cars: usize, config: &SimulationConfig) -> BuildingSimulation {
+        BuildingSimulation {
+            cars: (0..cars).map(|_| LiftSimulation::with_config(config)).collect(),
+            dispatcher: GroupDispatcher::new(AVERAGE_STOP),
+            building: None,
+            hall_calls: Vec::new(),
+            policy: None
+        }
+    }
+
+    /// Validate calls of every car against 'building' from now on, see `LiftSimulation::set_building`
+    pub fn set_building(&mut self, building: &Building) -> u32 {
+        let refused = self.cars.iter_mut().map(|car| car.set_building(building)).sum();
+        self.building = Some(building.clone());
+        self.clear_served_hall_calls();
+        refused
+    }
+
+    pub fn building(&self) -> Option<Building> {
+        self.building.clone()
+    }
+
+    pub fn car_count(&self) -> usize {
+        self.cars.len()
+    }
+
+    pub fn emergency_stop(&mut self, status: bool) {
+        self.cars.iter_mut().for_each(|car| car.emergency_stop(status))
+    }
+
+    /// Write the state of every car to 'buffer' after every step, see `LiftSimulation::set_state_mirror`.
+    /// Car i is written from offset 3i, so the array needs three values per car. Stop writing when undefined
+    pub fn set_state_mirror(&mut self, buffer: Option<Float32Array>) -> Result<(), JsError> {
+        if let Some(buffer) = &buffer {
+            let needed = self.cars.len() as u32 * MIRROR_STRIDE;
+            if buffer.length() < needed {
+                return Err(JsError::new(&format!("the mirror needs {} values, the array has {}", needed, buffer.length())));
+            }
+        }
+        for (car, simulation) in self.cars.iter_mut().enumerate() {
+            simulation.set_state_mirror(buffer.clone(), car as u32 * MIRROR_STRIDE)?;
+        }
+        Ok(())
+    }
+
+    /// Bring every car to a floor before stopping it, see `LiftSimulation::emergency_stop_at_floor`
+    pub fn emergency_stop_at_floor(&mut self) {
+        self.cars.iter_mut().for_each(|car| car.emergency_stop_at_floor());
+        self.hall_calls.clear();
+    }
+
+    /// Call a car to a floor from the landing, returns the car assigned. Fails if no car can serve the call
+    pub fn stop_lift_at_floor(&mut self, floor: Floor) -> Result<usize, JsError> {
+        self.check_landing_call(floor)?;
+        let (car, _) = self.assign(floor, None)?;
+        self.cars[car].stop_lift_at_floor(floor)?;
+        Ok(car)
+    }
+
+    /// Call a car from a landing panel to travel in 'direction', returns the car assigned and its estimated time of arrival.
+    /// Fails if no car can serve the call
+    pub fn call_elevator(&mut self, floor: Floor, direction: CallDirection) -> Result<HallCallAssignment, JsError> {
+        self.check_landing_call(floor)?;
+        let (car, eta) = self.assign(floor, Some(direction))?;
+        self.cars[car].stop_lift_at_floor(floor)?;
+        self.hall_calls.push(HallCall { floor, direction, car });
+        Ok(HallCallAssignment { car, eta })
+    }
+
+    /// 'count' passengers arrive at 'origin' wanting to go to 'destination'. They wait for the car the dispatcher
+    /// assigns, which is returned. Fails if no car can serve them
+    pub fn spawn_passengers(&mut self, origin: Floor, destination: Floor, count: u32) -> Result<usize, JsError> {
+        let direction = if destination > origin { CallDirection::Up } else { CallDirection::Down };
+        self.check_landing_call(origin)?;
+        let (car, _) = self.assign(origin, Some(direction))?;
+        self.cars[car].spawn_passengers(origin, destination, count)?;
+        self.hall_calls.push(HallCall { floor: origin, direction, car });
+        Ok(car)
+    }
+
+    /// Let 'policy' assign the calls from the landings, or the built-in group dispatcher again when undefined.
+    /// The policy is called with the cars, the floor and the direction of the call, undefined for calls without one,
+    /// and returns the index of the car to assign. Each car has its `state` as in `SimulationResult`, its
+    /// `pending_stops`, and the `eta` the built-in dispatcher estimates, null if it cannot serve the call
+    pub fn set_dispatcher(
+        &mut self,
+        #[wasm_bindgen(unchecked_param_type = "DispatcherPolicy | undefined")] policy: Option<Function>
+    ) {
+        self.policy = policy;
+    }
+
+    /// Press a floor button inside a car, fails if there is no such car or the building does not allow stopping at 'floor'
+    pub fn stop_car_at_floor(&mut self, car: usize, floor: Floor) -> Result<(), JsError> {
+        self.car_mut(car)?.stop_lift_at_floor(floor)
+    }
+
+    /// Remove the stops at 'floor' from every car, returns false if no car had one
+    pub fn cancel_floor(&mut self, floor: Floor) -> bool {
+        let mut cancelled = false;
+        for car in &mut self.cars {
+            cancelled |= car.cancel_floor(floor);
+        }
+        self.hall_calls.retain(|call| call.floor != floor);
+        cancelled
+    }
+
+    /// Call 'callback' whenever 'event' happens to a car, fails if there is no such car
+    pub fn set_car_callback(
+        &mut self,
+        car: usize,
+        event: LiftEvent,
+        #[wasm_bindgen(unchecked_param_type = "LiftEventCallback | undefined")] callback: Option<Function>
+    ) -> Result<(), JsError> {
+        self.car_mut(car)?.set_callback(event, callback);
+        Ok(())
+    }
+
+    /// Number of passengers waiting at each floor from 'lowest_floor' to 'highest_floor' for any car, as a Uint32Array.
+    /// Fails if the range spans more than 1000 floors
+    pub fn waiting_per_floor(&self, lowest_floor: Floor, highest_floor: Floor) -> Result<Vec<u32>, JsError> {
+        let mut waiting = vec![0; floor_count(lowest_floor, highest_floor)?];
+        for car in &self.cars {
+            car.add_waiting(lowest_floor, &mut waiting);
+        }
+        Ok(waiting)
+    }
+
+    /// Number of passengers in each car, as a Uint32Array
+    pub fn riders_per_car(&self) -> Vec<u32> {
+        self.cars.iter().map(|car| car.riders()).collect()
+    }
+
+    /// The floors a car has registered stops at, fails if there is no such car
+    pub fn pending_stops(&self, car: usize) -> Result<Vec<Floor>, JsError> {
+        self.car(car).map(|car| car.pending_stops())
+    }
+
+    /// Step every car by the time as specified in 'time_step', returns the result of each car in order
+    pub fn step_simulation(&mut self, time_step: f32) -> Result<Vec<SimulationResult>, JsError> {
+        let results = self.cars.iter_mut().map(|car| car.step_simulation(time_step)).collect();
+        self.clear_served_hall_calls();
+        results
+    }
+
+    pub fn pause(&mut self) {
+        self.cars.iter_mut().for_each(|car| car.pause())
+    }
+
+    pub fn resume(&mut self) {
+        self.cars.iter_mut().for_each(|car| car.resume())
+    }
+
+    /// Run every car 'scale' times faster, see `LiftSimulation::set_time_scale`
+    pub fn set_time_scale(&mut self, scale: f32) -> Result<(), JsError> {
+        for car in &mut self.cars {
+            car.set_time_scale(scale)?;
+        }
+        Ok(())
+    }
+
+    /// Reset every car to its initial state, see `LiftSimulation::reset`
+    pub fn reset(&mut self) {
+        self.cars.iter_mut().for_each(|car| car.reset());
+        self.hall_calls.clear();
+    }
+
+    /// Advance every car to 'time_ms' in fixed steps, see `LiftSimulation::advance_to`
+    pub fn advance_to(&mut self, time_ms: f64) -> Result<Vec<SimulationResult>, JsError> {
+        let results = self.cars.iter_mut().map(|car| car.advance_to(time_ms)).collect();
+        self.clear_served_hall_calls();
+        results
+    }
+
+    /// What the landing of each floor from 'lowest_floor' to 'highest_floor' should show. With calls in both
+    /// directions at a landing, the display shows the car expected first. Fails if the range spans more than 1000 floors
+    pub fn hall_displays(&self, lowest_floor: Floor, highest_floor: Floor) -> Result<Vec<HallDisplay>, JsError> {
+        floor_count(lowest_floor, highest_floor)?;
+        Ok((lowest_floor..=highest_floor).map(|floor| self.hall_display(floor)).collect())
+    }
+
+    pub fn last_simulation_results(&self) -> Vec<SimulationResult> {
+        self.cars.iter().map(|car| car.last_simulation_result()).collect()
+    }
+}
+
+impl BuildingSimulation {
+    fn check_landing_call(&self, floor: Floor) -> Result<(), JsError> {
+        self.building.as_ref().map_or(Ok(()), |building| building.check_landing_call(floor))
+    }
+
+    /// The car to serve a call at 'floor' and its estimated time of arrival, from the policy if one is set
+    fn assign(&self, floor: Floor, direction: Option<CallDirection>) -> Result<(usize, f32), JsError> {
+        let estimate = |car: &LiftSimulation| match direction {
+            Some(direction) => self.dispatcher.estimate_hall_call(&car.controller, &car.lift, floor, direction.into()),
+            None => self.dispatcher.estimate(&car.controller, &car.lift, floor)
+        };
+        let policy = match &self.policy {
+            Some(policy) => policy,
+            None => {
+                let cars = self.cars.iter().map(|car| (&car.controller, &car.lift as &dyn LiftSensors));
+                let assigned = match direction {
+                    Some(direction) => self.dispatcher.assign_hall_call(cars, floor, direction.into()),
+                    None => self.dispatcher.assign(cars, floor)
+                };
+                return assigned.ok_or_else(|| no_car_available(floor));
+            }
+        };
+
+        let candidates: Vec<_> = self
+            .cars
+            .iter()
+            .map(|car| DispatchCandidate { state: car.into(), pending_stops: car.pending_stops(), eta: estimate(car) })
+            .collect();
+        let candidates = js_sys::JSON::parse(&serde_json::to_string(&candidates)?)
+            .map_err(|_| JsError::new("the cars could not be converted"))?;
+        let direction = direction.map_or(JsValue::UNDEFINED, JsValue::from);
+        let chosen = policy
+            .call3(&JsValue::NULL, &candidates, &floor.into(), &direction)
+            .map_err(|_| JsError::new("the dispatcher policy threw an exception"))?;
+        let car = chosen_car(chosen.as_f64(), self.cars.len())
+            .ok_or_else(|| JsError::new(&format!("the dispatcher policy must return the index of a car, below {}", self.cars.len())))?;
+        Ok((car, estimate(&self.cars[car]).unwrap_or(f32::NAN)))
+    }
+
+    fn car(&self, car: usize) -> Result<&LiftSimulation, JsError> {
+        let count = self.cars.len();
+        self.cars.get(car).ok_or_else(|| no_such_car(car, count))
+    }
+
+    fn car_mut(&mut self, car: usize) -> Result<&mut LiftSimulation, JsError> {
+        let count = self.cars.len();
+        self.cars.get_mut(car).ok_or_else(|| no_such_car(car, count))
+    }
+
+    /// Forget the calls whose car has stopped at their landing, or no longer means to
+    fn clear_served_hall_calls(&mut self) {
+        let cars = &self.cars;
+        self.hall_calls.retain(|call| cars[call.car].lift.calls().contains(&call.floor));
+    }
+
+    fn hall_display(&self, floor: Floor) -> HallDisplay {
+        let mut display = HallDisplay { floor, car: None, direction: None, eta: f32::NAN };
+        for call in self.hall_calls.iter().filter(|call| call.floor == floor) {
+            let car = &self.cars[call.car];
+            let eta = self
+                .dispatcher
+                .estimate_hall_call(&car.controller, &car.lift, floor, call.direction.into())
+                .unwrap_or(f32::NAN);
+            if display.car.is_none() || eta < display.eta {
+                display = HallDisplay { floor, car: Some(call.car), direction: Some(call.direction), eta };
+            }
+        }
+        display
+    }
+}
+
+/// The car a dispatcher policy chose, if it returned the index of one of 'cars' cars
+fn chosen_car(chosen: Option<f64>, cars: usize) -> Option<usize> {
+    chosen.filter(|car| car.fract() == 0.0 && *car >= 0.0 && (*car as usize) < cars).map(|car| car as usize)
+}
+
+/// Poll the controller and move the car by a single step, recording it in 'metrics'.
+/// The doors open at every stop for a registered call, not only when passengers board or alight
+fn step_car(
+    lift: &mut SimulatedLift,
+    controller: &mut LiftController,
+    metrics: &mut Metrics,
+    observer: &mut dyn LiftObserver,
+    time_step: f32
+) -> Action {
+    let action = controller.poll_with_observer(lift, time_step, observer);
+    let floor = lift.position().round() as Floor;
+    let arriving = action.is_stopped_at_current_floor && lift.calls().contains(&floor);
+    lift.accept_action(action, time_step);
+    metrics.record(lift, time_step);
+    if arriving && lift.dwell_remaining() == 0.0 {
+        lift.start_dwell(0, 0);
+    }
+    action
+}
+
+/// Number of floors from 'lowest_floor' to 'highest_floor', zero if the highest is below the lowest.
+/// Fails if there are more than `MAX_FLOOR_RANGE`, so a range cannot make the simulation allocate without bound
+fn floor_count(lowest_floor: Floor, highest_floor: Floor) -> Result<usize, JsError> {
+    let count = (i64::from(highest_floor) - i64::from(lowest_floor) + 1).max(0) as usize;
+    if count > MAX_FLOOR_RANGE {
+        return Err(JsError::new(&format!(
+            "floors {} to {} are more than {} floors",
+            lowest_floor, highest_floor, MAX_FLOOR_RANGE
+        )));
+    }
+    Ok(count)
+}
+
+fn check_time_step(time_step: f32) -> Result<(), JsError> {
+    if (0.0..=MAX_TIME_STEP).contains(&time_step) {
+        Ok(())
+    } else {
+        Err(JsError::new(&format!(
+            "the time step must be a number of seconds from 0 to {}, got {}",
+            MAX_TIME_STEP, time_step
+        )))
+    }
+}
+
+fn no_such_car(car: usize, count: usize) -> JsError {
+    JsError::new(&format!("there is no car {}, the building has {} cars", car, count))
+}
+
+fn no_car_available(floor: Floor) -> JsError {
+    JsError::new(&format!("no car can serve floor {}, they are all stopped", floor))
+}
+
+// Only paths that create no JavaScript values can run outside wasm, such as the Ok paths without callbacks
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance_to_carries_the_remainder_and_caps_the_catch_up() {
+        let mut simulation = LiftSimulation::new();
+        simulation.stop_lift_at_floor(3).unwrap();
+        simulation.advance_to(1000.0).unwrap();
+        assert_eq!(0.0, simulation.lift.time());
+
+        simulation.advance_to(1025.0).unwrap();
+        assert!((simulation.lift.time() - 0.02).abs() < 1e-4);
+        assert!((simulation.accumulator - 0.005).abs() < 1e-4);
+        simulation.advance_to(1030.0).unwrap();
+        assert!((simulation.lift.time() - 0.03).abs() < 1e-4);
+
+        // Back from the background after a minute
+        simulation.advance_to(61_030.0).unwrap();
+        let simulated = simulation.lift.time() + simulation.accumulator;
+        assert!((simulated - 0.03 - MAX_CATCH_UP).abs() < 1e-3);
+
+        simulation.pause();
+        simulation.advance_to(62_000.0).unwrap();
+        simulation.resume();
+        simulation.advance_to(62_000.0).unwrap();
+        assert_eq!(simulated, simulation.lift.time() + simulation.accumulator);
+    }
+
+    #[test]
+    fn press_button_ignores_bounces_and_lit_buttons() {
+        let mut simulation = LiftSimulation::new();
+        assert_eq!(ButtonPress::Registered, simulation.press_button(3, 0.0));
+        assert_eq!(ButtonPress::Bounced, simulation.press_button(3, 20.0));
+        assert_eq!(ButtonPress::Registered, simulation.press_button(4, 20.0));
+        assert_eq!(ButtonPress::AlreadyRegistered, simulation.press_button(3, 60.0));
+        assert_eq!(ButtonPress::Bounced, simulation.press_button(3, 100.0));
+
+        simulation.set_debounce(0.0).unwrap();
+        assert_eq!(ButtonPress::AlreadyRegistered, simulation.press_button(4, 100.0));
+        assert_eq!(vec![3, 4], simulation.pending_stops());
+    }
+
+    #[test]
+    fn set_building_drops_stops_it_does_not_allow() {
+        let mut simulation = LiftSimulation::new();
+        for floor in [-1, 2, 3, 9] {
+            simulation.stop_lift_at_floor(floor).unwrap();
+        }
+        let mut building = Building::new(0, 5).unwrap();
+        building.set_express_floors(vec![3]);
+
+        assert_eq!(3, simulation.set_building(&building));
+        assert_eq!(vec![2], simulation.pending_stops());
+        assert_eq!(0, simulation.set_building(&building));
+    }
+
+    #[test]
+    fn planned_route_estimates_follow_the_route() {
+        let mut simulation = LiftSimulation::new();
+        simulation.stop_lift_at_floor(5).unwrap();
+        assert!(simulation.planned_route(2.0)[0].eta.is_nan());
+        while simulation.lift.position() < 2.5 {
+            simulation.step_simulation(0.05).unwrap();
+        }
+        simulation.stop_lift_at_floor(1).unwrap();
+        simulation.stop_lift_at_floor(7).unwrap();
+
+        let position = simulation.lift.position();
+        let speed = simulation.lift.current_velocity().abs();
+        let route = simulation.planned_route(2.0);
+        assert_eq!(vec![5, 7, 1], route.iter().map(|stop| stop.floor).collect::<Vec<_>>());
+        assert!((route[0].eta - (5.0 - position) / speed).abs() < 1e-3);
+        assert!((route[1].eta - route[0].eta - (2.0 + 2.0 / speed)).abs() < 1e-3);
+        assert!((route[2].eta - route[1].eta - (2.0 + 6.0 / speed)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn building_floors_are_bounded() {
+        assert!(Building::new(Floor::MAX - 999, 1000).is_ok());
+        assert!(Building::check_floors(0, 1).is_err());
+        assert!(Building::check_floors(0, 1001).is_err());
+        assert!(Building::check_floors(0, u32::MAX).is_err());
+        assert!(Building::check_floors(Floor::MAX, 2).is_err());
+        assert!(Building::check_floors(Floor::MIN, 2).is_ok());
+
+        let mut building = Building::new(-1, 6).unwrap();
+        assert_eq!(Ok(()), building.validate());
+        building.floor_count = 2000;
+        assert!(building.validate().is_err());
+    }
+
+    #[test]
+    fn dispatcher_policy_must_choose_a_car() {
+        assert_eq!(Some(0), chosen_car(Some(0.0), 3));
+        assert_eq!(Some(2), chosen_car(Some(2.0), 3));
+        for chosen in [None, Some(3.0), Some(-1.0), Some(1.5), Some(f64::NAN), Some(f64::INFINITY)] {
+            assert_eq!(None, chosen_car(chosen, 3));
+        }
+    }
+
+    #[test]
+    fn saved_state_round_trips_and_is_validated() {
+        let mut config = SimulationConfig::new();
+        config.fixed_step = 0.02;
+        let mut simulation = LiftSimulation::with_config(&config);
+        simulation.set_building(&Building::new(-1, 6).unwrap());
+        simulation.stop_lift_at_floor(4).unwrap();
+        simulation.spawn_passengers(0, 2, 3).unwrap();
+        for _ in 0..100 {
+            simulation.step_simulation(0.05).unwrap();
+        }
+        let saved = simulation.save_state().unwrap();
+
+        let mut restored = LiftSimulation::new();
+        restored.restore(serde_json::from_str(&saved).unwrap()).unwrap();
+        assert_eq!(saved, restored.save_state().unwrap());
+        assert_eq!(0.02, restored.fixed_step);
+        assert_eq!(simulation.lift.position(), restored.lift.position());
+
+        let mut state: serde_json::Value = serde_json::from_str(&saved).unwrap();
+        state["building"]["elevations"][2] = 1.0.into();
+        assert!(restored.restore(serde_json::from_value(state).unwrap()).is_err());
+        let mut state: serde_json::Value = serde_json::from_str(&saved).unwrap();
+        state["building"]["floor_count"] = 7.into();
+        assert!(restored.restore(serde_json::from_value(state).unwrap()).is_err());
+        let mut state: serde_json::Value = serde_json::from_str(&saved).unwrap();
+        state["config"]["prefered_velocity"] = 0.0.into();
+        assert!(restored.restore(serde_json::from_value(state).unwrap()).is_err());
+        assert_eq!(saved, restored.save_state().unwrap());
+    }
+}
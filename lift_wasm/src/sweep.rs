@@ -0,0 +1,263 @@
+//! Many independent headless simulations for parameter sweeps, such as comparing configurations under
+//! the same traffic.
+//!
+//! A run holds no JavaScript values, so runs are `Send`. With the `threads` feature `run_for` steps them
+//! on the rayon thread pool. In the browser that takes a module built with atomics
+//! (`-C target-feature=+atomics,+bulk-memory` and a rebuilt standard library) by a crate that also
+//! exports `initThreadPool` from wasm-bindgen-rayon, which starts the pool on Web Workers.
+//!
+//! Without the feature the runs are stepped on scoped threads outside the browser, and one after the
+//! other in a wasm module, as a module without atomics has no threads. The runs can then be spread over
+//! Web Workers instead: each worker holds a `SimulationSweep` with its share of the runs, see
+//! `www/sweep-worker.js`, and `summarize_sweep` combines the metrics they post back.
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use lift::sim::{Metrics, MetricsReport, SimulatedLift, TrafficConfig, TrafficGenerator};
+use lift::LiftController;
+
+use crate::{step_car, Building, SimulationConfig, Traffic};
+
+/// Most fixed steps a run takes in one call to `run_for`, longer durations are split by the caller
+const MAX_RUN_STEPS: f32 = 1_000_000.0;
+
+/// One simulation of a sweep
+struct SweepRun {
+    lift: SimulatedLift,
+    controller: LiftController,
+    traffic: TrafficGenerator,
+    metrics: Metrics,
+    /// The physics step of the configuration of the run
+    fixed_step: f32
+}
+
+// Keep the runs free of JavaScript values, which cannot leave their thread
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<SweepRun>();
+};
+
+impl SweepRun {
+    /// Number of fixed steps to cover 'duration'
+    fn steps(&self, duration: f32) -> f32 {
+        (duration / self.fixed_step).ceil()
+    }
+
+    /// Advance by 'duration' seconds in steps no longer than the fixed step
+    fn run_for(&mut self, duration: f32) {
+        let steps = self.steps(duration);
+        for _ in 0..steps as u32 {
+            self.traffic.feed(&mut self.lift, duration / steps);
+            step_car(&mut self.lift, &mut self.controller, &mut self.metrics, &mut (), duration / steps);
+        }
+    }
+}
+
+/// The metrics of every run of a sweep combined
+#[derive(Debug, PartialEq, Serialize)]
+struct SweepSummary {
+    runs: usize,
+    /// Passengers delivered over all runs
+    served: usize,
+    /// Mean over all passengers of all runs, in seconds
+    mean_wait_time: f32,
+    max_wait_time: f32,
+    mean_journey_time: f32,
+    max_journey_time: f32,
+    /// Means over the runs
+    mean_distance: f32,
+    mean_energy: f32
+}
+
+impl SweepSummary {
+    fn of(reports: &[MetricsReport]) -> SweepSummary {
+        let weighted_mean = |summary: fn(&MetricsReport) -> lift::sim::Summary| {
+            let count: usize = reports.iter().map(|report| summary(report).count).sum();
+            let total: f32 = reports.iter().map(|report| summary(report).mean * summary(report).count as f32).sum();
+            if count > 0 { total / count as f32 } else { 0.0 }
+        };
+        let run_mean = |value: fn(&MetricsReport) -> f32| {
+            let total: f32 = reports.iter().map(value).sum();
+            if reports.is_empty() { 0.0 } else { total / reports.len() as f32 }
+        };
+        SweepSummary {
+            runs: reports.len(),
+            served: reports.iter().map(|report| report.journey_time.count).sum(),
+            mean_wait_time: weighted_mean(|report| report.wait_time),
+            max_wait_time: reports.iter().map(|report| report.wait_time.max).fold(0.0, f32::max),
+            mean_journey_time: weighted_mean(|report| report.journey_time),
+            max_journey_time: reports.iter().map(|report| report.journey_time.max).fold(0.0, f32::max),
+            mean_distance: run_mean(|report| report.distance),
+            mean_energy: run_mean(|report| report.energy.net())
+        }
+    }
+}
+
+/// Independent simulations of one building and traffic pattern, each with its own configuration and seed
+#[wasm_bindgen]
+pub struct SimulationSweep {
+    traffic: TrafficConfig,
+    runs: Vec<SweepRun>
+}
+
+#[wasm_bindgen]
+impl SimulationSweep {
+    /// A sweep without runs over 'building' with 'pattern' traffic, 'rate' passengers per minute on average
+    #[wasm_bindgen(constructor)]
+    pub fn new(building: &Building, pattern: Traffic, rate: f32) -> Result<SimulationSweep, JsError> {
+        if !rate.is_finite() || rate < 0.0 {
+            return Err(JsError::new("the rate must be a number of at least 0"));
+        }
+        Ok(SimulationSweep { traffic: building.traffic(pattern.into(), rate), runs: Vec::new() })
+    }
+
+    /// Add a run tuned by 'config', with traffic from 'seed'. Runs with the same seed get the same passengers
+    pub fn add_run(&mut self, config: &SimulationConfig, seed: u32) {
+        let (lift, controller) = config.build();
+        self.runs.push(SweepRun {
+            lift,
+            controller,
+            traffic: TrafficGenerator::new(self.traffic.clone(), seed.into()),
+            metrics: Metrics::new(),
+            fixed_step: config.physics_step()
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// Advance every run by 'duration' seconds of simulated time in steps of its fixed step.
+    /// Fails without stepping if a run would take more than a million steps, split longer durations over several calls
+    pub fn run_for(&mut self, duration: f32) -> Result<(), JsError> {
+        if !duration.is_finite() || duration < 0.0 {
+            return Err(JsError::new(&format!("the duration must be a number of seconds of at least 0, got {}", duration)));
+        }
+        if self.runs.iter().any(|run| run.steps(duration) > MAX_RUN_STEPS) {
+            return Err(JsError::new(&format!(
+                "{} seconds takes more than {} steps, split it over several calls",
+                duration, MAX_RUN_STEPS
+            )));
+        }
+        self.step_runs(duration);
+        Ok(())
+    }
+
+    /// The metrics of each run in the order they were added, as objects like `LiftSimulation::metrics`
+    #[wasm_bindgen(unchecked_return_type = "MetricsReport[]")]
+    pub fn metrics(&self) -> Result<JsValue, JsError> {
+        to_js(&self.reports())
+    }
+
+    /// The metrics of all runs combined: passengers served, wait and journey times over all passengers,
+    /// and the mean distance and energy of a run
+    pub fn summary(&self) -> Result<JsValue, JsError> {
+        to_js(&SweepSummary::of(&self.reports()))
+    }
+}
+
+impl SimulationSweep {
+    fn reports(&self) -> Vec<MetricsReport> {
+        self.runs.iter().map(|run| run.metrics.report()).collect()
+    }
+
+    #[cfg(feature = "threads")]
+    fn step_runs(&mut self, duration: f32) {
+        use rayon::prelude::*;
+
+        self.runs.par_iter_mut().for_each(|run| run.run_for(duration));
+    }
+
+    #[cfg(all(not(feature = "threads"), not(target_arch = "wasm32")))]
+    fn step_runs(&mut self, duration: f32) {
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk = self.runs.len().div_ceil(threads).max(1);
+        std::thread::scope(|scope| {
+            for runs in self.runs.chunks_mut(chunk) {
+                scope.spawn(move || runs.iter_mut().for_each(|run| run.run_for(duration)));
+            }
+        });
+    }
+
+    #[cfg(all(not(feature = "threads"), target_arch = "wasm32"))]
+    fn step_runs(&mut self, duration: f32) {
+        self.runs.iter_mut().for_each(|run| run.run_for(duration));
+    }
+}
+
+/// Combine the metrics of the runs of several sweeps, as returned by `SimulationSweep::metrics`, like
+/// `SimulationSweep::summary`. For sweeps spread over Web Workers without the `threads` feature
+#[wasm_bindgen]
+pub fn summarize_sweep(#[wasm_bindgen(unchecked_param_type = "MetricsReport[]")] reports: JsValue) -> Result<JsValue, JsError> {
+    let json = js_sys::JSON::stringify(&reports).map_err(|_| JsError::new("the metrics could not be converted"))?;
+    let reports: Vec<MetricsReport> = serde_json::from_str(&String::from(json))?;
+    to_js(&SweepSummary::of(&reports))
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    js_sys::JSON::parse(&serde_json::to_string(value)?).map_err(|_| JsError::new("the metrics could not be converted"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sweep(seeds: &[u32]) -> SimulationSweep {
+        let building = Building::new(0, 8).unwrap();
+        let mut sweep = SimulationSweep::new(&building, Traffic::Interfloor, 6.0).unwrap();
+        for seed in seeds {
+            sweep.add_run(&SimulationConfig::new(), *seed);
+        }
+        sweep
+    }
+
+    #[test]
+    fn threaded_runs_match_a_single_run_with_the_same_seed() {
+        let mut single = sweep(&[7]);
+        single.run_for(300.0).unwrap();
+        let mut many = sweep(&[7, 8, 7, 9, 7]);
+        many.run_for(300.0).unwrap();
+
+        let reports = many.reports();
+        assert!(reports[0].journey_time.count > 0);
+        for index in [0, 2, 4] {
+            assert_eq!(single.reports()[0], reports[index]);
+        }
+    }
+
+    #[test]
+    fn runs_step_by_their_own_fixed_step() {
+        let mut config = SimulationConfig::new();
+        config.fixed_step = 0.05;
+        let mut sweep = sweep(&[1]);
+        sweep.add_run(&config, 1);
+        assert_eq!(1000.0, sweep.runs[0].steps(10.0));
+        assert_eq!(200.0, sweep.runs[1].steps(10.0));
+        sweep.run_for(10.0).unwrap();
+        assert!(sweep.runs.iter().all(|run| (run.metrics.report().duration - 10.0).abs() < 1e-3));
+    }
+
+    #[test]
+    fn summary_weights_each_mean_by_its_own_count() {
+        let mut short = MetricsReport::default();
+        short.wait_time.count = 1;
+        short.wait_time.mean = 10.0;
+        short.journey_time.count = 3;
+        short.journey_time.mean = 20.0;
+        let mut long = MetricsReport::default();
+        long.wait_time.count = 3;
+        long.wait_time.mean = 30.0;
+        long.journey_time.count = 1;
+        long.journey_time.mean = 40.0;
+
+        let summary = SweepSummary::of(&[short, long]);
+        assert_eq!(4, summary.served);
+        assert_eq!(25.0, summary.mean_wait_time);
+        assert_eq!(25.0, summary.mean_journey_time);
+    }
+}
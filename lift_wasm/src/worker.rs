@@ -0,0 +1,111 @@
+//! Message protocol for running a `BuildingSimulation` inside a Web Worker.
+//!
+//! The page posts requests such as `{ "CallElevator": { "floor": 3, "direction": "Up" } }` or `"Pause"`,
+//! the worker passes them to `BuildingSimulation::handle_message` and posts the response back.
+//! A request that fails throws, and the worker is expected to post the message of the error instead
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use lift::Floor;
+
+use crate::{BuildingSimulation, CallDirection, HallDisplay, SimulationResult};
+
+/// What the page can ask of the simulation, one variant per method of `BuildingSimulation`
+#[derive(Debug, Deserialize)]
+enum WorkerRequest {
+    Step { time_step: f32 },
+    AdvanceTo { time_ms: f64 },
+    State,
+    CallElevator { floor: Floor, direction: CallDirection },
+    StopLiftAtFloor { floor: Floor },
+    StopCarAtFloor { car: usize, floor: Floor },
+    SpawnPassengers { origin: Floor, destination: Floor, count: u32 },
+    CancelFloor { floor: Floor },
+    PendingStops { car: usize },
+    HallDisplays { lowest_floor: Floor, highest_floor: Floor },
+    EmergencyStop { activated: bool },
+    EmergencyStopAtFloor,
+    SetTimeScale { scale: f32 },
+    Pause,
+    Resume,
+    Reset
+}
+
+#[derive(Serialize)]
+enum WorkerResponse {
+    Done,
+    /// The state of every car, in order
+    Cars(Vec<SimulationResult>),
+    /// The car a call was given to
+    Assigned { car: usize, eta: Option<f32> },
+    Cancelled(bool),
+    Stops(Vec<Floor>),
+    HallDisplays(Vec<HallDisplay>)
+}
+
+#[wasm_bindgen]
+impl BuildingSimulation {
+    /// Handle a request posted to a Web Worker and return the response to post back, see the `worker` module
+    pub fn handle_message(&mut self, message: JsValue) -> Result<JsValue, JsError> {
+        let json = js_sys::JSON::stringify(&message)
+            .map_err(|_| JsError::new("the message could not be converted"))?;
+        let request: WorkerRequest = serde_json::from_str(&String::from(json))?;
+        let response = self.handle_request(request)?;
+        js_sys::JSON::parse(&serde_json::to_string(&response)?)
+            .map_err(|_| JsError::new("the response could not be converted"))
+    }
+}
+
+impl BuildingSimulation {
+    fn handle_request(&mut self, request: WorkerRequest) -> Result<WorkerResponse, JsError> {
+        Ok(match request {
+            WorkerRequest::Step { time_step } => WorkerResponse::Cars(self.step_simulation(time_step)?),
+            WorkerRequest::AdvanceTo { time_ms } => WorkerResponse::Cars(self.advance_to(time_ms)?),
+            WorkerRequest::State => WorkerResponse::Cars(self.last_simulation_results()),
+            WorkerRequest::CallElevator { floor, direction } => {
+                let assignment = self.call_elevator(floor, direction)?;
+                WorkerResponse::Assigned { car: assignment.car, eta: Some(assignment.eta) }
+            }
+            WorkerRequest::StopLiftAtFloor { floor } => {
+                WorkerResponse::Assigned { car: self.stop_lift_at_floor(floor)?, eta: None }
+            }
+            WorkerRequest::StopCarAtFloor { car, floor } => {
+                self.stop_car_at_floor(car, floor)?;
+                WorkerResponse::Done
+            }
+            WorkerRequest::SpawnPassengers { origin, destination, count } => {
+                WorkerResponse::Assigned { car: self.spawn_passengers(origin, destination, count)?, eta: None }
+            }
+            WorkerRequest::CancelFloor { floor } => WorkerResponse::Cancelled(self.cancel_floor(floor)),
+            WorkerRequest::PendingStops { car } => WorkerResponse::Stops(self.pending_stops(car)?),
+            WorkerRequest::HallDisplays { lowest_floor, highest_floor } => {
+                WorkerResponse::HallDisplays(self.hall_displays(lowest_floor, highest_floor)?)
+            }
+            WorkerRequest::EmergencyStop { activated } => {
+                self.emergency_stop(activated);
+                WorkerResponse::Done
+            }
+            WorkerRequest::EmergencyStopAtFloor => {
+                self.emergency_stop_at_floor();
+                WorkerResponse::Done
+            }
+            WorkerRequest::SetTimeScale { scale } => {
+                self.set_time_scale(scale)?;
+                WorkerResponse::Done
+            }
+            WorkerRequest::Pause => {
+                self.pause();
+                WorkerResponse::Done
+            }
+            WorkerRequest::Resume => {
+                self.resume();
+                WorkerResponse::Done
+            }
+            WorkerRequest::Reset => {
+                self.reset();
+                WorkerResponse::Done
+            }
+        })
+    }
+}
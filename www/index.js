@@ -1,32 +1,50 @@
 import * as wasm from "lift-wasm";
-
+import rushHour from "./scenarios/rush-hour.json";
+import fireRecall from "./scenarios/fire-recall.json";
+import faultHandling from "./scenarios/fault-handling.json";
+
+const simulation = new wasm.LiftSimulation();
+simulation.set_callback(wasm.LiftEvent.Arrived, floor => console.log("Arrived at floor " + floor));
+const building = new wasm.Building(-2, 13);
+simulation.set_building(building);
 const canvas = document.getElementById("lift-canvas");
-let isStopped = false;
-let timeStopped = 0;
-
-
-const toggledFloors = [];
+const warningBanner = document.getElementById("warning-banner");
+
+// Carry the simulation over page reloads
+const savedState = window.localStorage.getItem("lift-state");
+if (savedState !== null) {
+    try {
+        simulation.load_state(savedState);
+    } catch (e) {
+        console.log("Could not restore the saved simulation: " + e);
+    }
+}
+window.addEventListener("beforeunload", () => {
+    window.localStorage.setItem("lift-state", simulation.save_state());
+});
 
-let lastTimestamp;
-const mainLoop = (timestamp) => {
-    if (lastTimestamp === undefined) {
-        lastTimestamp = timestamp;
+// One strike of the gong for up, two for down
+let audio = null;
+const playChime = (strikes) => {
+    if (audio === null) {
+        audio = new AudioContext();
     }
-    const timeStep = (timestamp - lastTimestamp) / 1000.0;
-
-    let lift;
-    if (!isStopped || timeStopped > 1) {
-        lift = wasm.step_simulation(timeStep);
-        isStopped = lift.is_stopped;
-        let floor = Math.round(lift.position);
-        if (toggledFloors.includes(floor)) {
-            toggleFloorIndicator(floor, false);
-        }
-        timeStopped = 0;
-    } else {
-        lift = wasm.last_simulation_result();
-        timeStopped += timeStep;
+    for (let i = 0; i < strikes; i++) {
+        const start = audio.currentTime + i * 0.4;
+        const tone = audio.createOscillator();
+        const gain = audio.createGain();
+        tone.frequency.value = 660;
+        gain.gain.setValueAtTime(0.3, start);
+        gain.gain.exponentialRampToValueAtTime(0.001, start + 0.35);
+        tone.connect(gain).connect(audio.destination);
+        tone.start(start);
+        tone.stop(start + 0.35);
     }
+};
+
+const mainLoop = (timestamp) => {
+    // The simulation runs in fixed steps whatever the frame rate
+    const lift = simulation.advance_to(timestamp);
 
     const ctx = canvas.getContext("2d");
     ctx.clearRect(0, 0, canvas.width, canvas.height);
@@ -43,21 +61,101 @@ const mainLoop = (timestamp) => {
     }
 
     const liftOffset = liftHeight * 3;
+    const liftTop = canvas.height - lift.render_position * liftHeight - liftOffset;
     ctx.fillStyle = "#AdA425";
-    ctx.fillRect(0, canvas.height - lift.position * liftHeight - liftOffset, canvas.width, liftHeight);
+    ctx.fillRect(0, liftTop, canvas.width, liftHeight);
+
+    // The doors slide apart from the middle of the car
+    const doorGap = canvas.width * lift.door_open_fraction;
+    ctx.fillStyle = "#D9D26A";
+    ctx.fillRect((canvas.width - doorGap) / 2, liftTop, doorGap, liftHeight);
 
-    toggledFloors.forEach(floor => {
-        const timer = "floor-button-timer-" + floor;
-        const remainingTime = wasm.time_to_floor(floor, 1.0);
-        if (remainingTime !== undefined && remainingTime > 0) {
-            document.getElementById(timer).innerText =  remainingTime.toFixed(1) + "s";
+    // The arrival lantern above the doors points the way the car leaves in
+    if (lift.lantern !== undefined) {
+        const up = lift.lantern === wasm.CallDirection.Up;
+        ctx.fillStyle = up ? "#3A3" : "#C33";
+        ctx.beginPath();
+        ctx.moveTo(canvas.width / 2 - 6, liftTop + (up ? 10 : 2));
+        ctx.lineTo(canvas.width / 2 + 6, liftTop + (up ? 10 : 2));
+        ctx.lineTo(canvas.width / 2, liftTop + (up ? 2 : 10));
+        ctx.fill();
+    }
+    if (lift.chime > 0) {
+        playChime(lift.chime);
+    }
+
+    // The route the car takes through its stops, numbered in the order they are served
+    const route = simulation.planned_route(1.0);
+    const routeX = canvas.width - 12;
+    ctx.strokeStyle = "#C33";
+    ctx.fillStyle = "#C33";
+    ctx.beginPath();
+    ctx.moveTo(routeX, liftTop + liftHeight / 2);
+    route.forEach((stop, i) => {
+        const stopY = canvas.height - stop.floor * liftHeight - liftOffset + liftHeight / 2;
+        ctx.lineTo(routeX - (i + 1) * 4, stopY);
+        ctx.fillText((i + 1).toString(), routeX - (i + 1) * 4 - 10, stopY);
+    });
+    ctx.stroke();
+
+    // A queue of dots at each landing, one per waiting passenger
+    ctx.fillStyle = "#333";
+    simulation.waiting_per_floor(building.lowest_floor(), building.highest_floor()).forEach((waiting, i) => {
+        const floor = building.lowest_floor() + i;
+        const floorBottom = canvas.height - floor * liftHeight - liftOffset + liftHeight;
+        for (let p = 0; p < waiting; p++) {
+            ctx.beginPath();
+            ctx.arc(4 + (p % 6) * 5, floorBottom - 4 - Math.floor(p / 6) * 5, 2, 0, 2 * Math.PI);
+            ctx.fill();
         }
     });
+    if (lift.riders > 0) {
+        ctx.fillText(lift.riders + "/" + simulation.capacity(), canvas.width / 2 - 8, liftTop + liftHeight / 2);
+    }
+
+    const pendingStops = simulation.pending_stops();
+    const etas = simulation.time_to_floors(building.lowest_floor(), building.highest_floor(), 1.0);
+    floorButtons.forEach(button => {
+        const floor = parseInt(button.value);
+        const pending = pendingStops.includes(floor);
+        button.classList.toggle("active-floor-button", pending);
+
+        const timer = document.getElementById("floor-button-timer-" + floor);
+        const remainingTime = etas[floor - building.lowest_floor()];
+        if (pending && remainingTime > 0) {
+            timer.innerText = remainingTime.toFixed(1) + "s";
+        } else {
+            timer.innerText = "";
+        }
+    });
+
+    // Warn about anything keeping the car from serving calls
+    const warnings = [];
+    if (lift.mode === wasm.OperatingMode.StoppingAtFloor) {
+        warnings.push("Stopping at the next floor");
+    } else if (lift.is_emergency_stop_latched) {
+        warnings.push("Emergency stop active, press STOP to release");
+    }
+    simulation.active_faults().forEach(code => warnings.push("Fault: " + wasm.SimulatedFault[code]));
+    warningBanner.innerText = warnings.join(" | ");
+    warningBanner.style.display = warnings.length > 0 ? "block" : "none";
 
-    lastTimestamp = timestamp;
     window.requestAnimationFrame(mainLoop);
 };
 
+const metricsPanel = document.getElementById("metrics");
+setInterval(() => {
+    const metrics = simulation.metrics();
+    const lift = simulation.last_simulation_result();
+    const served = metrics.journey_time.count;
+    metricsPanel.innerText =
+        "served   " + served + " passengers\n" +
+        "wait     mean " + metrics.wait_time.mean.toFixed(1) + "s, max " + metrics.wait_time.max.toFixed(1) + "s\n" +
+        "travel   " + lift.distance.toFixed(1) + " floors, " + lift.motor_starts + " starts\n" +
+        "energy   " + lift.energy.toFixed(1) + " Wh" +
+        (served > 0 ? ", " + (lift.energy / served).toFixed(2) + " Wh per passenger" : "");
+}, 1000);
+
 const floorButtons = Array.from(document.getElementsByClassName("floor-button"));
 const emergencyStopButton = document.getElementById("emergency-stop-button");
 
@@ -65,7 +163,7 @@ let emergencyStopStatus = false;
 emergencyStopButton.addEventListener("click", () => {
     console.log("Emergency stop button pressed");
     emergencyStopStatus = !emergencyStopStatus;
-    wasm.emergency_stop(emergencyStopStatus);
+    simulation.emergency_stop(emergencyStopStatus);
     if (emergencyStopStatus) {
         emergencyStopButton.classList.add("active-floor-button");
     } else {
@@ -73,40 +171,78 @@ emergencyStopButton.addEventListener("click", () => {
     }
 });
 
-floorButtons.forEach(button => {
-    button.addEventListener("click", () => {
-        const floor = button.value;
-        console.log("Floor button " + floor + " pressed")
-        wasm.stop_lift_at_floor(button.value);
-        toggleFloorIndicator(floor, true);
-    });
-    
+// Continue to the next floor and stop there, the STOP button releases the car again
+document.getElementById("stop-at-floor-button").addEventListener("click", () => {
+    simulation.emergency_stop_at_floor();
+    emergencyStopStatus = true;
+    emergencyStopButton.classList.add("active-floor-button");
+});
+
+const pauseButton = document.getElementById("pause-button");
+pauseButton.addEventListener("click", () => {
+    if (simulation.is_paused()) {
+        simulation.resume();
+        pauseButton.innerText = "Pause";
+    } else {
+        simulation.pause();
+        pauseButton.innerText = "Resume";
+    }
+});
+
+document.getElementById("speed-select").addEventListener("change", event => {
+    simulation.set_time_scale(parseFloat(event.target.value));
+});
+
+document.getElementById("passenger-button").addEventListener("click", () => {
+    const floors = floorButtons.map(button => parseInt(button.value));
+    const origin = floors[Math.floor(Math.random() * floors.length)];
+    const destination = floors[Math.floor(Math.random() * floors.length)];
+    try {
+        simulation.spawn_passengers(origin, destination, 1);
+    } catch (e) {
+        console.log("No passenger added: " + e.message);
+    }
+});
+
+document.getElementById("traffic-select").addEventListener("change", event => {
+    const pattern = event.target.value;
+    if (pattern === "") {
+        simulation.stop_traffic();
+    } else {
+        simulation.start_traffic(wasm.Traffic[pattern], 4.0, Date.now() % 0xFFFFFFFF);
+    }
 });
 
+const scenarios = { rushHour, fireRecall, faultHandling };
+document.getElementById("scenario-select").addEventListener("change", event => {
+    const scenario = scenarios[event.target.value];
+    if (scenario === undefined) {
+        simulation.reset();
+    } else {
+        simulation.load_scenario(JSON.stringify(scenario));
+    }
+});
+
+document.getElementById("reset-button").addEventListener("click", () => {
+    simulation.reset();
+    emergencyStopStatus = false;
+    emergencyStopButton.classList.remove("active-floor-button");
+});
 
-const toggleFloorIndicator = (value, status) => {
-    floorButtons
-        .filter(button => button.value == value)
-        .forEach(button => {
-            const active = "active-floor-button";
-            
-            if (status) {
-                button.classList.add(active);
-                toggledFloors.push(parseInt(value));
-            } else {
-                button.classList.remove(active);
-                while (toggledFloors.includes(value)) {
-                    const index = toggledFloors.indexOf(value);
-                    if (index > -1) {
-                        toggledFloors.splice(index, 1);
-                    }
-                }
-                const timer = "floor-button-timer-" + value;
-                document.getElementById(timer).innerText = "";
-            }
-            
+floorButtons.forEach(button => {
+    const floor = parseInt(button.value);
+    // The panel behaves like hardware: bouncing contacts and presses of a lit button are ignored
+    button.addEventListener("click", () => {
+        const press = simulation.press_button(floor, performance.now());
+        console.log("Floor button " + floor + " pressed: " + wasm.ButtonPress[press]);
     });
-}
+    // Double clicking a lit button cancels the stop
+    button.addEventListener("dblclick", () => {
+        if (simulation.cancel_floor(floor)) {
+            console.log("Floor button " + floor + " cancelled");
+        }
+    });
+});
 
 
 window.requestAnimationFrame(mainLoop);
\ No newline at end of file
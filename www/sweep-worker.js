@@ -0,0 +1,29 @@
+// Runs a share of a parameter sweep off the main thread, start one per core with `new Worker("sweep-worker.js")`.
+// Post `{ id, building: { lowest_floor, floor_count }, traffic, rate, runs: [{ config, seed }], duration }`, where
+// `traffic` names a `Traffic` value and `config` holds fields of `SimulationConfig`. The worker posts back
+// `{ id, response }` with the metrics of each run, or `{ id, error }` if the sweep failed.
+// Combine the responses of all workers with `summarize_sweep`. A module built with the `threads` feature and atomics
+// steps the runs of one sweep on its own thread pool instead
+const wasm = import("lift-wasm");
+
+// Longest duration passed to `run_for` at once, so a long sweep stays within its step limit
+const CHUNK_SECONDS = 600;
+
+self.onmessage = event => {
+    const { id, building, traffic, rate, runs, duration } = event.data;
+    wasm.then(wasm => {
+        try {
+            const floors = new wasm.Building(building.lowest_floor, building.floor_count);
+            const sweep = new wasm.SimulationSweep(floors, wasm.Traffic[traffic], rate);
+            for (const { config, seed } of runs) {
+                sweep.add_run(Object.assign(new wasm.SimulationConfig(), config), seed);
+            }
+            for (let elapsed = 0; elapsed < duration; elapsed += CHUNK_SECONDS) {
+                sweep.run_for(Math.min(CHUNK_SECONDS, duration - elapsed));
+            }
+            self.postMessage({ id, response: sweep.metrics() });
+        } catch (e) {
+            self.postMessage({ id, error: e.message });
+        }
+    });
+};
@@ -2,10 +2,16 @@ const CopyWebpackPlugin = require("copy-webpack-plugin");
 const path = require('path');
 
 module.exports = {
-  entry: "./bootstrap.js",
+  entry: {
+    bootstrap: "./bootstrap.js",
+    worker: "./worker.js",
+    "sweep-worker": "./sweep-worker.js",
+  },
   output: {
     path: path.resolve(__dirname, "dist"),
-    filename: "bootstrap.js",
+    filename: "[name].js",
+    // Chunks are also loaded from the worker, where there is no window
+    globalObject: "self",
   },
   mode: "production",
   plugins: [
@@ -0,0 +1,24 @@
+// Runs a building simulation off the main thread, start it with `new Worker("worker.js?cars=4")`.
+// Post `{ id, request }` where the request is one of those handled by `BuildingSimulation.handle_message`,
+// the worker posts back `{ id, response }`, or `{ id, error }` if the request failed.
+// Post `{ id, mirror }` with a Float32Array over a SharedArrayBuffer to have the state of every car written to it
+const cars = parseInt(new URL(self.location).searchParams.get("cars") || "1");
+
+// As in bootstrap.js, anything depending on wasm has to be imported asynchronously
+const simulation = import("lift-wasm").then(wasm => new wasm.BuildingSimulation(cars));
+
+self.onmessage = event => {
+    const { id, request, mirror } = event.data;
+    simulation.then(simulation => {
+        try {
+            if (mirror !== undefined) {
+                simulation.set_state_mirror(mirror);
+                self.postMessage({ id, response: "Done" });
+                return;
+            }
+            self.postMessage({ id, response: simulation.handle_message(request) });
+        } catch (e) {
+            self.postMessage({ id, error: e.message });
+        }
+    });
+};